@@ -0,0 +1,76 @@
+use assembly_data::{behavior::BehaviorGraph, fdb::mem::Database};
+use color_eyre::eyre::WrapErr;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+/// Checks the skill behaviors of a FDB file, or exports them as DOT or JSON
+struct Options {
+    /// The FDB file
+    file: PathBuf,
+    /// Only use the behaviors of this skill
+    #[structopt(long)]
+    skill: Option<i32>,
+    /// Print the graph in the DOT format
+    #[structopt(long, conflicts_with = "json")]
+    dot: bool,
+    /// Print the graph as JSON
+    #[structopt(long)]
+    json: bool,
+}
+
+fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+    let opts = Options::from_args();
+
+    let buf = std::fs::read(&opts.file)
+        .wrap_err_with(|| format!("Failed to read input file '{}'", opts.file.display()))?;
+    let db = Database::new(&buf);
+    let mut graph = BehaviorGraph::load(db.tables()?)?;
+    if let Some(skill_id) = opts.skill {
+        graph = graph.skill(skill_id);
+    }
+
+    if opts.dot {
+        let mut out = String::new();
+        graph.write_dot(&mut out)?;
+        print!("{}", out);
+        return Ok(());
+    }
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&graph)?);
+        return Ok(());
+    }
+
+    let report = graph.check();
+    for edge in &report.dangling_edges {
+        println!(
+            "Behavior {} references missing behavior {} in {:?}",
+            edge.from, edge.to, edge.parameter
+        );
+    }
+    for root in &report.dangling_roots {
+        println!(
+            "Skill {} starts at missing behavior {}",
+            root.skill_id, root.behavior_id
+        );
+    }
+    for id in &report.missing_templates {
+        println!("Behavior {} has parameters, but no template", id);
+    }
+    for cycle in &report.cycles {
+        println!("Cycle: {:?}", cycle);
+    }
+    for shared in &report.shared {
+        println!(
+            "Behavior {} is shared by the roots {:?}",
+            shared.behavior_id, shared.roots
+        );
+    }
+    println!("Behaviors: {}", graph.behaviors.len());
+    println!("Edges: {}", graph.edges.len());
+    println!("Skills: {}", graph.roots.len());
+    println!("Unreachable: {}", report.unreachable.len());
+    println!("Shared: {}", report.shared.len());
+    Ok(())
+}
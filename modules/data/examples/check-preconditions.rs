@@ -0,0 +1,34 @@
+use assembly_data::{fdb::mem::Database, precondition::validate};
+use color_eyre::eyre::WrapErr;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+/// Finds invalid precondition expressions and references to missing IDs
+struct Options {
+    /// The FDB file
+    file: PathBuf,
+    /// Print the issues as JSON
+    #[structopt(long)]
+    json: bool,
+}
+
+fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+    let opts = Options::from_args();
+
+    let buf = std::fs::read(&opts.file)
+        .wrap_err_with(|| format!("Failed to read input file '{}'", opts.file.display()))?;
+    let db = Database::new(&buf);
+    let issues = validate(db.tables()?)?;
+
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&issues)?);
+        return Ok(());
+    }
+    for issue in &issues {
+        println!("{}", issue);
+    }
+    println!("Issues: {}", issues.len());
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use assembly_data::{fdb::mem::Database, foreign_keys::ForeignKeys};
+use color_eyre::eyre::WrapErr;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+/// Finds references to objects, missions, loot and behaviors that don't exist
+struct Options {
+    /// The FDB file
+    file: PathBuf,
+    /// Print the report as JSON
+    #[structopt(long)]
+    json: bool,
+}
+
+fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+    let opts = Options::from_args();
+
+    let buf = std::fs::read(&opts.file)
+        .wrap_err_with(|| format!("Failed to read input file '{}'", opts.file.display()))?;
+    let db = Database::new(&buf);
+    let report = ForeignKeys::cdclient().check(db.tables()?)?;
+
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    for key in &report.skipped {
+        println!(
+            "Skipped {}.{} -> {}.{}",
+            key.table, key.column, key.target_table, key.target_column
+        );
+    }
+    for broken in &report.broken {
+        println!(
+            "{} {:?}: {} = {:?} is not in {}.{}",
+            broken.table,
+            broken.primary_key,
+            broken.column,
+            broken.value,
+            broken.target_table,
+            broken.target_column
+        );
+    }
+    println!("Broken references: {}", report.broken.len());
+    Ok(())
+}
@@ -0,0 +1,90 @@
+use assembly_data::{
+    fdb::mem::Database,
+    loot::{DropDistribution, LootData, LootDistribution, LootOptions, Simulator},
+    object::ObjectResolver,
+};
+use color_eyre::eyre::{eyre, WrapErr};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+/// Shows the expected loot drops of an object or a loot matrix
+struct Options {
+    /// The FDB file
+    file: PathBuf,
+    /// The ID of the object template (LOT) with a destructible component
+    #[structopt(long, required_unless = "matrix")]
+    lot: Option<i32>,
+    /// The ID of the loot matrix
+    #[structopt(long, conflicts_with = "lot")]
+    matrix: Option<i32>,
+    /// Include items that only drop for players on a mission
+    #[structopt(long)]
+    mission_drops: bool,
+    /// Roll the loot this many times, instead of computing the expected drops
+    #[structopt(long)]
+    simulate: Option<u32>,
+    /// The seed of the random number generator
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+    /// Print the drops as JSON
+    #[structopt(long)]
+    json: bool,
+}
+
+fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+    let opts = Options::from_args();
+
+    let buf = std::fs::read(&opts.file)
+        .wrap_err_with(|| format!("Failed to read input file '{}'", opts.file.display()))?;
+    let tables = Database::new(&buf).tables()?;
+    let data = LootData::load(tables)?;
+    let options = LootOptions::new().mission_drops(opts.mission_drops);
+
+    let (matrix, currency) = match opts.lot {
+        Some(lot) => {
+            let object = ObjectResolver::new(tables)?
+                .resolve(lot)?
+                .ok_or_else(|| eyre!("Object {} does not exist", lot))?;
+            let destructible = object
+                .destructible
+                .ok_or_else(|| eyre!("Object {} has no destructible component", lot))?;
+            let level = destructible.level.unwrap_or(1);
+            let currency = destructible
+                .currency_index
+                .and_then(|index| data.currency(index, level));
+            (destructible.loot_matrix_index, currency)
+        }
+        None => (opts.matrix, None),
+    };
+    let items = match (matrix, opts.simulate) {
+        (Some(index), Some(trials)) => {
+            Simulator::seeded(&data, options, opts.seed).simulate(index, trials)
+        }
+        (Some(index), None) => data.drops(index, &options),
+        (None, _) => DropDistribution::default(),
+    };
+    let loot = LootDistribution { items, currency };
+
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&loot)?);
+        return Ok(());
+    }
+    for item in &loot.items.items {
+        println!(
+            "{:>8}: {:>8.4} per roll, {:>7.3}% chance",
+            item.lot,
+            item.expected_count,
+            item.probability * 100.0
+        );
+    }
+    println!("Items per roll: {:.4}", loot.items.expected_total);
+    if let Some(currency) = &loot.currency {
+        println!(
+            "Coins: {} - {} (average {})",
+            currency.min, currency.max, currency.expected
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,72 @@
+use assembly_data::{fdb::mem::Database, object::ObjectResolver};
+use color_eyre::eyre::{eyre, WrapErr};
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+#[derive(Debug, StructOpt)]
+/// Shows an object template with all of its components
+struct Options {
+    /// The FDB file
+    file: PathBuf,
+    /// The ID of the object template (LOT)
+    lot: i32,
+    /// Print the object as JSON
+    #[structopt(long)]
+    json: bool,
+}
+
+fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+    let opts = Options::from_args();
+
+    let buf = std::fs::read(&opts.file)
+        .wrap_err_with(|| format!("Failed to read input file '{}'", opts.file.display()))?;
+    let db = Database::new(&buf);
+    let resolver = ObjectResolver::new(db.tables()?)?;
+    let object = resolver
+        .resolve(opts.lot)?
+        .ok_or_else(|| eyre!("Object {} does not exist", opts.lot))?;
+
+    if opts.json {
+        println!("{}", serde_json::to_string_pretty(&object)?);
+        return Ok(());
+    }
+
+    println!("{}: {}", object.lot, object.name.as_deref().unwrap_or("?"));
+    if let Some(object_type) = &object.object_type {
+        println!("Type: {}", object_type);
+    }
+    for component in object.components.values() {
+        let table = component.table.as_deref().unwrap_or("?");
+        println!(
+            "Component {} ({}): {} {}",
+            component.component_type,
+            table,
+            component.component_id,
+            if component.rows.len() > 1 {
+                format!("({} rows)", component.rows.len())
+            } else {
+                String::new()
+            }
+        );
+    }
+    if let Some(render) = &object.render {
+        println!("Render asset: {:?}", render.render_asset);
+    }
+    if let Some(physics) = &object.physics {
+        println!("Physics asset: {:?}", physics.physics_asset);
+    }
+    if let Some(script) = &object.script {
+        println!("Script: {:?}", script.script_name);
+    }
+    if let Some(destructible) = &object.destructible {
+        println!(
+            "Life: {:?}, Armor: {:?}, Loot matrix: {:?}",
+            destructible.life, destructible.armor, destructible.loot_matrix_index
+        );
+    }
+    for warning in &object.warnings {
+        println!("Warning: {}", warning);
+    }
+    Ok(())
+}
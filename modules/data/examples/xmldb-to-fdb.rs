@@ -1,5 +1,8 @@
 use assembly_data::{
-    fdb::{core, store, value},
+    fdb::{
+        store,
+        value::{self, owned::Field},
+    },
     xml::{
         common::{expect_decl, expect_end},
         database::{
@@ -9,7 +12,6 @@ use assembly_data::{
         quick::Reader,
     },
 };
-use assembly_fdb::FdbHash;
 use color_eyre::eyre::WrapErr;
 use latin1str::Latin1String;
 use std::{
@@ -59,7 +61,7 @@ fn main() -> color_eyre::Result<()> {
 
     while let Some(table_name) = expect_table(xml, buf)? {
         println!("table '{}'", table_name);
-        let mut dest_table = store::Table::new(128);
+        let mut dest_table = store::Table::new(0);
 
         expect_columns(xml, buf)?;
 
@@ -109,53 +111,36 @@ fn main() -> color_eyre::Result<()> {
         let col_count = dest_table.columns().len();
         let mut auto_inc = 0;
         while let Some(row) = expect_row_or_end_rows(xml, buf, true)? {
-            let mut fields = vec![core::Field::Nothing; col_count];
-            let mut pk = None;
+            let mut fields = vec![Field::Nothing; col_count];
+            let mut has_pk = false;
             for (key, src_value) in row {
                 let col_index = *col_map.get(&key).unwrap();
                 let value_type = dest_table.columns().get(col_index).unwrap().value_type();
                 let dest_value = match value_type {
-                    value::ValueType::Nothing => core::Field::Nothing,
-                    value::ValueType::Integer => core::Field::Integer(src_value.parse().unwrap()),
-                    value::ValueType::Float => core::Field::Float(src_value.parse().unwrap()),
-                    value::ValueType::Text => core::Field::Text(src_value),
-                    value::ValueType::Boolean => core::Field::Boolean(&src_value != "0"),
-                    value::ValueType::BigInt => core::Field::BigInt(src_value.parse().unwrap()),
-                    value::ValueType::VarChar => core::Field::VarChar(src_value),
+                    value::ValueType::Nothing => Field::Nothing,
+                    value::ValueType::Integer => Field::Integer(src_value.parse().unwrap()),
+                    value::ValueType::Float => Field::Float(src_value.parse().unwrap()),
+                    value::ValueType::Text => Field::Text(src_value),
+                    value::ValueType::Boolean => Field::Boolean(&src_value != "0"),
+                    value::ValueType::BigInt => Field::BigInt(src_value.parse().unwrap()),
+                    value::ValueType::VarChar => Field::VarChar(src_value),
                 };
 
                 if col_index == 0 {
-                    match &dest_value {
-                        core::Field::Integer(i) => {
-                            pk = Some((*i % 128) as usize);
-                        }
-                        core::Field::BigInt(i) => {
-                            pk = Some((*i % 128) as usize);
-                        }
-                        core::Field::Text(text) => {
-                            let lat1 = Latin1String::encode(text);
-                            pk = Some((FdbHash::hash(lat1.as_ref()) % 128) as usize);
-                        }
-                        core::Field::VarChar(var_char) => {
-                            let lat1 = Latin1String::encode(var_char);
-                            pk = Some((FdbHash::hash(lat1.as_ref()) % 128) as usize);
-                        }
-                        _ => panic!("Can't use {:?} as PK", &dest_value),
-                    }
+                    has_pk = true;
                 }
 
                 fields[col_index] = dest_value;
             }
-            let pk = if let Some(pk) = pk {
-                pk
-            } else {
+            if !has_pk {
                 auto_inc += 1;
-                fields[0] = core::Field::Integer(auto_inc);
-                (auto_inc as usize) % 128
-            };
-            dest_table.push_row(pk, &fields);
+                fields[0] = Field::Integer(auto_inc);
+            }
+            dest_table.push_row_hashed(&fields);
         }
 
+        dest_table.auto_resize_buckets();
+
         expect_end(xml, buf, "table")?;
         dest_db.push_table(Latin1String::encode(&table_name), dest_table);
     }
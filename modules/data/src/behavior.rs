@@ -0,0 +1,675 @@
+//! # The graph of skill behaviors
+//!
+//! Every skill in `SkillBehavior` starts at a root behavior. A behavior has a
+//! template in `BehaviorTemplate`, which is named in `BehaviorTemplateName`,
+//! and a list of named float parameters in `BehaviorParameter`. Some of these
+//! parameters (see [`ACTION_PARAMETERS`]) hold the ID of another behavior,
+//! which turns the behaviors into a directed graph.
+//!
+//! ```
+//! use assembly_data::{behavior::BehaviorGraph, fdb::{mem::Database, typed::LoadError}};
+//!
+//! fn check(db: Database) -> Result<(), LoadError> {
+//!     let graph = BehaviorGraph::load(db.tables()?)?;
+//!     let report = graph.check();
+//!     for edge in &report.dangling_edges {
+//!         println!("{} -[{}]-> missing {}", edge.from, edge.parameter, edge.to);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! With the `serde-derives` feature, the graph and the report implement
+//! `serde::Serialize` and can be exported as JSON. [`BehaviorGraph::write_dot`]
+//! exports the graph in the DOT format of graphviz.
+
+use std::{
+    collections::{btree_map::Entry, BTreeMap, BTreeSet},
+    fmt,
+};
+
+use assembly_fdb::{
+    mem::Tables,
+    typed::{column_index, field_at, LoadError},
+};
+
+use crate::util::table;
+
+/// The names of the parameters that hold the ID of another behavior
+///
+/// The misspelled `bahavior 2` is used in the original database.
+pub const ACTION_PARAMETERS: &[&str] = &[
+    "action",
+    "action_consumed",
+    "action_failed",
+    "action_false",
+    "action_true",
+    "air_action",
+    "bahavior 2",
+    "behavior",
+    "behavior 0",
+    "behavior 1",
+    "behavior 2",
+    "behavior 3",
+    "behavior 4",
+    "behavior 5",
+    "behavior 6",
+    "behavior 7",
+    "behavior 8",
+    "behavior 9",
+    "behavior 10",
+    "blocked action",
+    "blocked_action",
+    "break_action",
+    "chain_action",
+    "double_jump_action",
+    "falling_action",
+    "ground_action",
+    "hit_action",
+    "hit_action_enemy",
+    "jetpack_action",
+    "jump_action",
+    "miss action",
+    "moving_action",
+    "on_fail_armor",
+    "on_fail_blocked",
+    "on_fail_immune",
+    "on_success",
+    "spawn_fail_action",
+    "start_action",
+    "timeout_action",
+];
+
+/// Check whether the parameter `name` holds the ID of another behavior
+pub fn is_action_parameter(name: &str) -> bool {
+    ACTION_PARAMETERS.contains(&name)
+}
+
+/// A single behavior
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct Behavior {
+    /// The ID of the behavior
+    pub id: i32,
+    /// The template from `BehaviorTemplate`, if there is a row for this behavior
+    pub template_id: Option<i32>,
+    /// The name of the template from `BehaviorTemplateName`
+    pub template_name: Option<String>,
+    /// All parameters from `BehaviorParameter`, including the actions
+    pub parameters: BTreeMap<String, f32>,
+}
+
+/// A parameter of a behavior that references another behavior
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct Edge {
+    /// The behavior with the parameter
+    pub from: i32,
+    /// The name of the parameter
+    pub parameter: String,
+    /// The referenced behavior
+    pub to: i32,
+}
+
+/// The root behavior of a skill
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct Root {
+    /// The ID of the skill
+    pub skill_id: i32,
+    /// The ID of the behavior
+    pub behavior_id: i32,
+}
+
+/// A behavior that can be reached from more than one root behavior
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct SharedBehavior {
+    /// The ID of the behavior
+    pub behavior_id: i32,
+    /// The root behaviors of the skills that reach it, sorted
+    pub roots: Vec<i32>,
+}
+
+/// Problems in a [`BehaviorGraph`], see [`BehaviorGraph::check`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct BehaviorReport {
+    /// Edges to behaviors that don't exist
+    pub dangling_edges: Vec<Edge>,
+    /// Skills with a root behavior that doesn't exist
+    pub dangling_roots: Vec<Root>,
+    /// Behaviors that have parameters, but no row in `BehaviorTemplate`
+    pub missing_templates: Vec<i32>,
+    /// Groups of behaviors that reference each other in a cycle
+    pub cycles: Vec<Vec<i32>>,
+    /// Behaviors that can't be reached from any skill
+    pub unreachable: Vec<i32>,
+    /// Behaviors where the trees of several root behaviors meet, see
+    /// [`BehaviorGraph::partition`]
+    pub shared: Vec<SharedBehavior>,
+}
+
+impl BehaviorReport {
+    /// Check whether no problems were found
+    ///
+    /// Unreachable and shared behaviors are not counted as a problem, because
+    /// the original database contains many of them.
+    pub fn is_ok(&self) -> bool {
+        self.dangling_edges.is_empty()
+            && self.dangling_roots.is_empty()
+            && self.missing_templates.is_empty()
+            && self.cycles.is_empty()
+    }
+}
+
+/// The graph of all behaviors
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct BehaviorGraph {
+    /// The behaviors by ID
+    pub behaviors: BTreeMap<i32, Behavior>,
+    /// The references between behaviors, sorted by `from`
+    pub edges: Vec<Edge>,
+    /// The root behaviors of all skills
+    pub roots: Vec<Root>,
+}
+
+impl BehaviorGraph {
+    /// Load the graph from the `BehaviorTemplate`, `BehaviorTemplateName`,
+    /// `BehaviorParameter` and `SkillBehavior` tables
+    pub fn load(tables: Tables<'_>) -> Result<Self, LoadError> {
+        let mut names = BTreeMap::new();
+        let t = table(tables, "BehaviorTemplateName")?;
+        let col_template_id = column_index::<i32>(t, "templateID")?;
+        let col_name = column_index::<Option<String>>(t, "name")?;
+        for row in t.row_iter() {
+            let template_id: i32 = field_at(t, row, col_template_id, "templateID")?;
+            let name: Option<String> = field_at(t, row, col_name, "name")?;
+            if let Some(name) = name {
+                names.insert(template_id, name);
+            }
+        }
+
+        let mut behaviors = BTreeMap::new();
+        let t = table(tables, "BehaviorTemplate")?;
+        let col_behavior_id = column_index::<i32>(t, "behaviorID")?;
+        let col_template_id = column_index::<i32>(t, "templateID")?;
+        for row in t.row_iter() {
+            let id = field_at(t, row, col_behavior_id, "behaviorID")?;
+            let template_id = field_at(t, row, col_template_id, "templateID")?;
+            behaviors.insert(
+                id,
+                Behavior {
+                    id,
+                    template_id: Some(template_id),
+                    template_name: names.get(&template_id).cloned(),
+                    parameters: BTreeMap::new(),
+                },
+            );
+        }
+
+        let mut edges = Vec::new();
+        let t = table(tables, "BehaviorParameter")?;
+        let col_behavior_id = column_index::<i32>(t, "behaviorID")?;
+        let col_parameter_id = column_index::<String>(t, "parameterID")?;
+        let col_value = column_index::<f32>(t, "value")?;
+        for row in t.row_iter() {
+            let from = field_at(t, row, col_behavior_id, "behaviorID")?;
+            let parameter: String = field_at(t, row, col_parameter_id, "parameterID")?;
+            let value: f32 = field_at(t, row, col_value, "value")?;
+            if is_action_parameter(&parameter) && value > 0.0 {
+                edges.push(Edge {
+                    from,
+                    parameter: parameter.clone(),
+                    to: value as i32,
+                });
+            }
+            behaviors
+                .entry(from)
+                .or_insert_with(|| Behavior {
+                    id: from,
+                    template_id: None,
+                    template_name: None,
+                    parameters: BTreeMap::new(),
+                })
+                .parameters
+                .insert(parameter, value);
+        }
+        edges.sort();
+
+        let mut roots = Vec::new();
+        let t = table(tables, "SkillBehavior")?;
+        let col_skill_id = column_index::<i32>(t, "skillID")?;
+        let col_behavior_id = column_index::<i32>(t, "behaviorID")?;
+        for row in t.row_iter() {
+            roots.push(Root {
+                skill_id: field_at(t, row, col_skill_id, "skillID")?,
+                behavior_id: field_at(t, row, col_behavior_id, "behaviorID")?,
+            });
+        }
+        roots.sort();
+
+        Ok(Self {
+            behaviors,
+            edges,
+            roots,
+        })
+    }
+
+    /// Get the edges that start at the behavior `id`
+    pub fn children(&self, id: i32) -> &[Edge] {
+        let start = self.edges.partition_point(|e| e.from < id);
+        let end = self.edges.partition_point(|e| e.from <= id);
+        &self.edges[start..end]
+    }
+
+    /// Get the IDs of all behaviors that can be reached from `roots`,
+    /// including the roots themselves
+    pub fn reachable_from<I: IntoIterator<Item = i32>>(&self, roots: I) -> BTreeSet<i32> {
+        let mut reachable = BTreeSet::new();
+        let mut stack: Vec<i32> = roots.into_iter().collect();
+        while let Some(id) = stack.pop() {
+            if reachable.insert(id) {
+                stack.extend(self.children(id).iter().map(|e| e.to));
+            }
+        }
+        reachable
+    }
+
+    /// Get the part of the graph that can be reached from the skill `skill_id`
+    pub fn skill(&self, skill_id: i32) -> Self {
+        let roots: Vec<Root> = self
+            .roots
+            .iter()
+            .filter(|r| r.skill_id == skill_id)
+            .copied()
+            .collect();
+        let reachable = self.reachable_from(roots.iter().map(|r| r.behavior_id));
+        Self {
+            behaviors: self
+                .behaviors
+                .iter()
+                .filter(|(id, _)| reachable.contains(id))
+                .map(|(&id, b)| (id, b.clone()))
+                .collect(),
+            edges: self
+                .edges
+                .iter()
+                .filter(|e| reachable.contains(&e.from))
+                .cloned()
+                .collect(),
+            roots,
+        }
+    }
+
+    /// Split the reachable behaviors into trees that don't overlap
+    ///
+    /// Every tree starts at a root behavior of a skill, or at a behavior that
+    /// can be reached from several of these trees. Every behavior that can be
+    /// reached from a skill is in exactly one tree, and a tree only references
+    /// the behaviors of other trees by their start.
+    pub fn partition(&self) -> BTreeMap<i32, BTreeSet<i32>> {
+        let mut starts: BTreeSet<i32> = self.roots.iter().map(|r| r.behavior_id).collect();
+        loop {
+            let mut owner = BTreeMap::new();
+            let mut shared = BTreeSet::new();
+            for &start in &starts {
+                let mut stack = vec![start];
+                while let Some(id) = stack.pop() {
+                    match owner.entry(id) {
+                        Entry::Vacant(entry) => {
+                            entry.insert(start);
+                        }
+                        Entry::Occupied(entry) => {
+                            if *entry.get() != start {
+                                shared.insert(id);
+                            }
+                            continue;
+                        }
+                    }
+                    let children = self.children(id).iter().map(|e| e.to);
+                    stack.extend(children.filter(|to| !starts.contains(to)));
+                }
+            }
+            if shared.is_empty() {
+                let mut trees: BTreeMap<i32, BTreeSet<i32>> = BTreeMap::new();
+                for (id, start) in owner {
+                    trees.entry(start).or_default().insert(id);
+                }
+                return trees;
+            }
+            starts.extend(shared);
+        }
+    }
+
+    /// Find the behaviors that are reached from more than one root behavior
+    ///
+    /// These are the starts of the trees of [`BehaviorGraph::partition`] that
+    /// are not a root behavior themselves.
+    pub fn shared(&self) -> Vec<SharedBehavior> {
+        let roots: BTreeSet<i32> = self.roots.iter().map(|r| r.behavior_id).collect();
+        let mut shared: BTreeMap<i32, Vec<i32>> = self
+            .partition()
+            .into_keys()
+            .filter(|id| !roots.contains(id))
+            .map(|id| (id, Vec::new()))
+            .collect();
+        for &root in &roots {
+            for id in self.reachable_from(Some(root)) {
+                if let Some(reached_by) = shared.get_mut(&id) {
+                    reached_by.push(root);
+                }
+            }
+        }
+        shared
+            .into_iter()
+            .map(|(behavior_id, roots)| SharedBehavior { behavior_id, roots })
+            .collect()
+    }
+
+    /// Find the groups of behaviors that reference each other in a cycle
+    ///
+    /// Every group is sorted, and contains more than one behavior or a
+    /// behavior that references itself.
+    pub fn cycles(&self) -> Vec<Vec<i32>> {
+        let nodes: BTreeSet<i32> = self
+            .behaviors
+            .keys()
+            .copied()
+            .chain(self.edges.iter().map(|e| e.to))
+            .collect();
+        let mut cycles = Vec::new();
+        for mut scc in Tarjan::new(self).run(nodes) {
+            let self_loop = || self.children(scc[0]).iter().any(|e| e.to == scc[0]);
+            if scc.len() > 1 || self_loop() {
+                scc.sort_unstable();
+                cycles.push(scc);
+            }
+        }
+        cycles.sort();
+        cycles
+    }
+
+    /// Check the graph for dangling references, cycles, unreachable and shared behaviors
+    pub fn check(&self) -> BehaviorReport {
+        let reachable = self.reachable_from(self.roots.iter().map(|r| r.behavior_id));
+        BehaviorReport {
+            dangling_edges: self
+                .edges
+                .iter()
+                .filter(|e| !self.behaviors.contains_key(&e.to))
+                .cloned()
+                .collect(),
+            dangling_roots: self
+                .roots
+                .iter()
+                .filter(|r| !self.behaviors.contains_key(&r.behavior_id))
+                .copied()
+                .collect(),
+            missing_templates: self
+                .behaviors
+                .values()
+                .filter(|b| b.template_id.is_none())
+                .map(|b| b.id)
+                .collect(),
+            cycles: self.cycles(),
+            unreachable: self
+                .behaviors
+                .keys()
+                .filter(|id| !reachable.contains(id))
+                .copied()
+                .collect(),
+            shared: self.shared(),
+        }
+    }
+
+    /// Write the graph in the DOT format
+    ///
+    /// Skills are drawn as boxes, and references to missing behaviors as
+    /// dashed nodes.
+    pub fn write_dot<W: fmt::Write>(&self, w: &mut W) -> fmt::Result {
+        writeln!(w, "digraph behaviors {{")?;
+        for root in &self.roots {
+            writeln!(
+                w,
+                "  skill_{0} [shape=box, label=\"skill {0}\"];",
+                root.skill_id
+            )?;
+            writeln!(w, "  skill_{} -> {};", root.skill_id, root.behavior_id)?;
+        }
+        for b in self.behaviors.values() {
+            let name = b.template_name.as_deref().unwrap_or("?");
+            writeln!(w, "  {} [label=\"{}: {}\"];", b.id, b.id, escape(name))?;
+        }
+        let missing: BTreeSet<i32> = self
+            .edges
+            .iter()
+            .map(|e| e.to)
+            .chain(self.roots.iter().map(|r| r.behavior_id))
+            .filter(|id| !self.behaviors.contains_key(id))
+            .collect();
+        for id in missing {
+            writeln!(w, "  {0} [style=dashed, label=\"{0}: missing\"];", id)?;
+        }
+        for e in &self.edges {
+            let label = escape(&e.parameter);
+            writeln!(w, "  {} -> {} [label=\"{}\"];", e.from, e.to, label)?;
+        }
+        writeln!(w, "}}")
+    }
+}
+
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Tarjan's algorithm for strongly connected components, without recursion
+struct Tarjan<'g> {
+    graph: &'g BehaviorGraph,
+    index: BTreeMap<i32, usize>,
+    lowlink: BTreeMap<i32, usize>,
+    stack: Vec<i32>,
+    on_stack: BTreeSet<i32>,
+    components: Vec<Vec<i32>>,
+}
+
+impl<'g> Tarjan<'g> {
+    fn new(graph: &'g BehaviorGraph) -> Self {
+        Self {
+            graph,
+            index: BTreeMap::new(),
+            lowlink: BTreeMap::new(),
+            stack: Vec::new(),
+            on_stack: BTreeSet::new(),
+            components: Vec::new(),
+        }
+    }
+
+    fn run(mut self, nodes: BTreeSet<i32>) -> Vec<Vec<i32>> {
+        for node in nodes {
+            if !self.index.contains_key(&node) {
+                self.visit(node);
+            }
+        }
+        self.components
+    }
+
+    fn enter(&mut self, node: i32) {
+        let index = self.index.len();
+        self.index.insert(node, index);
+        self.lowlink.insert(node, index);
+        self.stack.push(node);
+        self.on_stack.insert(node);
+    }
+
+    fn visit(&mut self, start: i32) {
+        // (node, index of the next child edge)
+        let mut work = vec![(start, 0)];
+        self.enter(start);
+        while let Some(&mut (node, ref mut next)) = work.last_mut() {
+            let children = self.graph.children(node);
+            if let Some(edge) = children.get(*next) {
+                *next += 1;
+                let child = edge.to;
+                if !self.index.contains_key(&child) {
+                    self.enter(child);
+                    work.push((child, 0));
+                } else if self.on_stack.contains(&child) {
+                    let low = self.lowlink[&node].min(self.index[&child]);
+                    self.lowlink.insert(node, low);
+                }
+                continue;
+            }
+            work.pop();
+            if let Some(&(parent, _)) = work.last() {
+                let low = self.lowlink[&parent].min(self.lowlink[&node]);
+                self.lowlink.insert(parent, low);
+            }
+            if self.lowlink[&node] == self.index[&node] {
+                let mut component = Vec::new();
+                while let Some(member) = self.stack.pop() {
+                    self.on_stack.remove(&member);
+                    component.push(member);
+                    if member == node {
+                        break;
+                    }
+                }
+                self.components.push(component);
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "fdb-store"))]
+mod tests {
+    use super::*;
+    use crate::util::tests::{build, TableBuilder};
+    use assembly_fdb::{mem::Database, value::owned::Field, value::ValueType};
+
+    fn database() -> Vec<u8> {
+        let mut names = TableBuilder::new("BehaviorTemplateName")
+            .column("templateID", ValueType::Integer)
+            .column("name", ValueType::Text);
+        names.row(vec![Field::Integer(1), Field::Text("BasicAttack".into())]);
+        names.row(vec![Field::Integer(2), Field::Text("And \"Or\"".into())]);
+
+        let mut templates = TableBuilder::new("BehaviorTemplate")
+            .column("behaviorID", ValueType::Integer)
+            .column("templateID", ValueType::Integer);
+        for (id, template) in [
+            (10, 2),
+            (11, 1),
+            (12, 1),
+            (13, 1),
+            (20, 2),
+            (21, 2),
+            (30, 1),
+        ] {
+            templates.row(vec![Field::Integer(id), Field::Integer(template)]);
+        }
+
+        let mut parameters = TableBuilder::new("BehaviorParameter")
+            .column("behaviorID", ValueType::Integer)
+            .column("parameterID", ValueType::Text)
+            .column("value", ValueType::Float);
+        for (id, name, value) in [
+            (10, "behavior 1", 11.0),
+            (10, "behavior 2", 12.0),
+            (11, "min damage", 3.0),
+            (12, "action", 0.0),
+            (12, "miss action", 99.0),
+            (13, "action", 11.0),
+            (20, "action", 21.0),
+            (21, "action", 20.0),
+            (30, "on_success", 30.0),
+            (40, "action", 10.0),
+        ] {
+            parameters.row(vec![
+                Field::Integer(id),
+                Field::Text(name.into()),
+                Field::Float(value),
+            ]);
+        }
+
+        let mut skills = TableBuilder::new("SkillBehavior")
+            .column("skillID", ValueType::Integer)
+            .column("behaviorID", ValueType::Integer);
+        skills.row(vec![Field::Integer(1), Field::Integer(10)]);
+        skills.row(vec![Field::Integer(2), Field::Integer(50)]);
+        skills.row(vec![Field::Integer(3), Field::Integer(13)]);
+
+        build(vec![names, templates, parameters, skills])
+    }
+
+    #[test]
+    fn test_load() {
+        let buf = database();
+        let graph = BehaviorGraph::load(Database::new(&buf).tables().unwrap()).unwrap();
+        assert_eq!(graph.behaviors.len(), 8);
+        let attack = &graph.behaviors[&11];
+        assert_eq!(attack.template_id, Some(1));
+        assert_eq!(attack.template_name.as_deref(), Some("BasicAttack"));
+        assert_eq!(attack.parameters["min damage"], 3.0);
+
+        let children: Vec<_> = graph.children(10).iter().map(|e| e.to).collect();
+        assert_eq!(children, [11, 12]);
+        // Actions with the value 0 are not references
+        assert!(graph.children(12).iter().all(|e| e.to == 99));
+        assert_eq!(graph.skill(1).behaviors.len(), 3);
+    }
+
+    #[test]
+    fn test_check() {
+        let buf = database();
+        let graph = BehaviorGraph::load(Database::new(&buf).tables().unwrap()).unwrap();
+        let report = graph.check();
+        assert_eq!(
+            report.dangling_edges,
+            [Edge {
+                from: 12,
+                parameter: "miss action".to_owned(),
+                to: 99
+            }]
+        );
+        assert_eq!(
+            report.dangling_roots,
+            [Root {
+                skill_id: 2,
+                behavior_id: 50
+            }]
+        );
+        assert_eq!(report.missing_templates, [40]);
+        assert_eq!(report.cycles, [vec![20, 21], vec![30]]);
+        assert_eq!(report.unreachable, [20, 21, 30, 40]);
+        assert_eq!(
+            report.shared,
+            [SharedBehavior {
+                behavior_id: 11,
+                roots: vec![10, 13]
+            }]
+        );
+        assert!(!report.is_ok());
+
+        let trees: Vec<(i32, Vec<i32>)> = graph
+            .partition()
+            .into_iter()
+            .map(|(start, tree)| (start, tree.into_iter().collect()))
+            .collect();
+        assert_eq!(
+            trees,
+            [
+                (10, vec![10, 12, 99]),
+                (11, vec![11]),
+                (13, vec![13]),
+                (50, vec![50])
+            ]
+        );
+
+        let mut dot = String::new();
+        graph.write_dot(&mut dot).unwrap();
+        assert!(dot.contains("  10 [label=\"10: And \\\"Or\\\"\"];\n"));
+        assert!(dot.contains("  99 [style=dashed, label=\"99: missing\"];\n"));
+        assert!(dot.contains("  12 -> 99 [label=\"miss action\"];\n"));
+    }
+}
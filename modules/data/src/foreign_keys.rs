@@ -0,0 +1,395 @@
+//! # Implicit foreign keys
+//!
+//! The tables of the core database reference each other by ID, but the file
+//! format has no foreign key constraints. Modded databases often contain
+//! references to objects, missions, loot matrices or behaviors that don't
+//! exist (anymore).
+//!
+//! A [`ForeignKey`] declares that every value of a column must be a value of
+//! another column, and [`ForeignKeys::cdclient`] contains the references of the
+//! original database. Custom tables can be added to that set:
+//!
+//! ```
+//! use assembly_data::{
+//!     fdb::mem::Database,
+//!     foreign_keys::{ForeignKey, ForeignKeys},
+//! };
+//!
+//! # let buf = vec![0, 0, 0, 0, 8, 0, 0, 0];
+//! let keys = ForeignKeys::cdclient()
+//!     .with(ForeignKey::new("MyShopItems", "lot", "Objects", "id").ignore(0));
+//! let report = keys.check(Database::new(&buf).tables()?)?;
+//! for broken in &report.broken {
+//!     println!(
+//!         "{}[{:?}].{} = {:?} is not in {}.{}",
+//!         broken.table,
+//!         broken.primary_key,
+//!         broken.column,
+//!         broken.value,
+//!         broken.target_table,
+//!         broken.target_column
+//!     );
+//! }
+//! # Ok::<(), assembly_data::fdb::typed::LoadError>(())
+//! ```
+
+use std::collections::{hash_map::Entry, HashMap, HashSet};
+
+use assembly_fdb::{
+    index::Key,
+    mem::{Table, Tables},
+    typed::LoadError,
+};
+
+/// The references of the original database, as `(table, column, target table, target column)`
+///
+/// The values `0` and `-1` are used for "no reference" in many of these
+/// columns, so [`ForeignKeys::cdclient`] ignores them.
+pub const CDCLIENT_FOREIGN_KEYS: &[(&str, &str, &str, &str)] = &[
+    (
+        "BehaviorParameter",
+        "behaviorID",
+        "BehaviorTemplate",
+        "behaviorID",
+    ),
+    (
+        "BehaviorTemplate",
+        "templateID",
+        "BehaviorTemplateName",
+        "templateID",
+    ),
+    ("ComponentsRegistry", "id", "Objects", "id"),
+    (
+        "DestructibleComponent",
+        "CurrencyIndex",
+        "CurrencyTable",
+        "CurrencyIndex",
+    ),
+    (
+        "DestructibleComponent",
+        "LootMatrixIndex",
+        "LootMatrix",
+        "LootMatrixIndex",
+    ),
+    ("InventoryComponent", "itemid", "Objects", "id"),
+    ("ItemComponent", "currencyLOT", "Objects", "id"),
+    ("ItemSetSkills", "SkillID", "SkillBehavior", "skillID"),
+    (
+        "LootMatrix",
+        "LootTableIndex",
+        "LootTable",
+        "LootTableIndex",
+    ),
+    (
+        "LootMatrix",
+        "RarityTableIndex",
+        "RarityTable",
+        "RarityTableIndex",
+    ),
+    ("LootTable", "itemid", "Objects", "id"),
+    ("MissionNPCComponent", "missionID", "Missions", "id"),
+    ("MissionTasks", "id", "Missions", "id"),
+    ("Missions", "offer_objectID", "Objects", "id"),
+    ("Missions", "target_objectID", "Objects", "id"),
+    ("Missions", "reward_item1", "Objects", "id"),
+    ("Missions", "reward_item2", "Objects", "id"),
+    ("Missions", "reward_item3", "Objects", "id"),
+    ("Missions", "reward_item4", "Objects", "id"),
+    ("ObjectSkills", "objectTemplate", "Objects", "id"),
+    ("ObjectSkills", "skillID", "SkillBehavior", "skillID"),
+    (
+        "PackageComponent",
+        "LootMatrixIndex",
+        "LootMatrix",
+        "LootMatrixIndex",
+    ),
+    (
+        "SkillBehavior",
+        "behaviorID",
+        "BehaviorTemplate",
+        "behaviorID",
+    ),
+    (
+        "VendorComponent",
+        "LootMatrixIndex",
+        "LootMatrix",
+        "LootMatrixIndex",
+    ),
+];
+
+/// A column whose values reference the values of another column
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct ForeignKey {
+    /// The table with the reference
+    pub table: String,
+    /// The column with the reference
+    pub column: String,
+    /// The referenced table
+    pub target_table: String,
+    /// The referenced column
+    pub target_column: String,
+    /// Values that don't reference anything, in addition to `NULL`
+    pub ignore: Vec<Key>,
+}
+
+impl ForeignKey {
+    /// Declare that `table.column` references `target_table.target_column`
+    pub fn new(table: &str, column: &str, target_table: &str, target_column: &str) -> Self {
+        Self {
+            table: table.to_owned(),
+            column: column.to_owned(),
+            target_table: target_table.to_owned(),
+            target_column: target_column.to_owned(),
+            ignore: Vec::new(),
+        }
+    }
+
+    /// Don't check fields with this value
+    pub fn ignore<K: Into<Key>>(mut self, value: K) -> Self {
+        self.ignore.push(value.into());
+        self
+    }
+}
+
+/// A reference to a value that doesn't exist
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct BrokenReference {
+    /// The table with the reference
+    pub table: String,
+    /// The first field of the row
+    pub primary_key: Key,
+    /// The column with the reference
+    pub column: String,
+    /// The value of the reference
+    pub value: Key,
+    /// The referenced table
+    pub target_table: String,
+    /// The referenced column
+    pub target_column: String,
+}
+
+/// The result of [`ForeignKeys::check`]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct ForeignKeyReport {
+    /// All broken references, in the order of the foreign keys and rows
+    pub broken: Vec<BrokenReference>,
+    /// The foreign keys that were not checked, because one of the tables or
+    /// columns doesn't exist
+    pub skipped: Vec<ForeignKey>,
+}
+
+/// A set of foreign keys
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ForeignKeys {
+    keys: Vec<ForeignKey>,
+}
+
+impl ForeignKeys {
+    /// Create an empty set
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create the set of [`CDCLIENT_FOREIGN_KEYS`]
+    pub fn cdclient() -> Self {
+        let keys = CDCLIENT_FOREIGN_KEYS
+            .iter()
+            .map(|&(table, column, target_table, target_column)| {
+                ForeignKey::new(table, column, target_table, target_column)
+                    .ignore(0)
+                    .ignore(-1)
+            })
+            .collect();
+        Self { keys }
+    }
+
+    /// Add a foreign key
+    pub fn with(mut self, key: ForeignKey) -> Self {
+        self.keys.push(key);
+        self
+    }
+
+    /// Add a foreign key
+    pub fn push(&mut self, key: ForeignKey) {
+        self.keys.push(key);
+    }
+
+    /// Get the foreign keys in this set
+    pub fn keys(&self) -> &[ForeignKey] {
+        &self.keys
+    }
+
+    /// Check all foreign keys against a database
+    pub fn check(&self, tables: Tables<'_>) -> Result<ForeignKeyReport, LoadError> {
+        let mut report = ForeignKeyReport::default();
+        let mut targets: HashMap<(&str, &str), Option<HashSet<Key>>> = HashMap::new();
+        for key in &self.keys {
+            let target = (&key.target_table[..], &key.target_column[..]);
+            let values = match targets.entry(target) {
+                Entry::Occupied(entry) => entry.into_mut(),
+                Entry::Vacant(entry) => {
+                    let values = column(tables, target.0, target.1)?.map(|(table, index)| {
+                        table
+                            .row_iter()
+                            .filter_map(|row| row.field_at(index))
+                            .map(Key::from)
+                            .collect()
+                    });
+                    entry.insert(values)
+                }
+            };
+            let (table, index, values) = match (column(tables, &key.table, &key.column)?, values) {
+                (Some((table, index)), Some(values)) => (table, index, values),
+                _ => {
+                    report.skipped.push(key.clone());
+                    continue;
+                }
+            };
+            for row in table.row_iter() {
+                let value = match row.field_at(index).map(Key::from) {
+                    Some(value) => value,
+                    None => continue,
+                };
+                if value == Key::Nothing || key.ignore.contains(&value) || values.contains(&value) {
+                    continue;
+                }
+                report.broken.push(BrokenReference {
+                    table: key.table.clone(),
+                    primary_key: row.field_at(0).map(Key::from).unwrap_or(Key::Nothing),
+                    column: key.column.clone(),
+                    value,
+                    target_table: key.target_table.clone(),
+                    target_column: key.target_column.clone(),
+                });
+            }
+        }
+        Ok(report)
+    }
+}
+
+/// Find a table and the index of one of its columns
+fn column<'a>(
+    tables: Tables<'a>,
+    table: &str,
+    column: &str,
+) -> Result<Option<(Table<'a>, usize)>, LoadError> {
+    let table = match tables.by_name(table) {
+        Some(table) => table?,
+        None => return Ok(None),
+    };
+    Ok(table
+        .column_iter()
+        .position(|c| c.name() == column)
+        .map(|index| (table, index)))
+}
+
+#[cfg(all(test, feature = "fdb-store"))]
+mod tests {
+    use super::*;
+    use crate::util::tests::{build, TableBuilder};
+    use assembly_fdb::{
+        mem::Database,
+        value::{owned::Field, ValueType},
+    };
+
+    fn database() -> Vec<u8> {
+        let mut objects = TableBuilder::new("Objects").column("id", ValueType::Integer);
+        for lot in [1000, 1001] {
+            objects.row(vec![Field::Integer(lot)]);
+        }
+
+        let mut registry = TableBuilder::new("ComponentsRegistry")
+            .column("id", ValueType::Integer)
+            .column("component_type", ValueType::Integer);
+        for lot in [1000, 1002, 1001, 1002] {
+            registry.row(vec![Field::Integer(lot), Field::Integer(2)]);
+        }
+
+        let mut matrix = TableBuilder::new("LootMatrix")
+            .column("LootMatrixIndex", ValueType::Integer)
+            .column("LootTableIndex", ValueType::Integer)
+            .column("RarityTableIndex", ValueType::Integer);
+        for (index, table) in [(1, 10), (2, 11), (3, 0), (4, -1)] {
+            matrix.row(vec![
+                Field::Integer(index),
+                Field::Integer(table),
+                Field::Nothing,
+            ]);
+        }
+
+        let mut loot = TableBuilder::new("LootTable")
+            .column("itemid", ValueType::Integer)
+            .column("LootTableIndex", ValueType::Integer);
+        loot.row(vec![Field::Integer(1001), Field::Integer(10)]);
+
+        let mut custom = TableBuilder::new("MyShopItems")
+            .column("name", ValueType::Text)
+            .column("lot", ValueType::Integer);
+        custom.row(vec![Field::Text("a".into()), Field::Integer(1000)]);
+        custom.row(vec![Field::Text("b".into()), Field::Integer(5)]);
+
+        build(vec![objects, registry, matrix, loot, custom])
+    }
+
+    #[test]
+    fn test_check() {
+        let buf = database();
+        let tables = Database::new(&buf).tables().unwrap();
+        let keys =
+            ForeignKeys::cdclient().with(ForeignKey::new("MyShopItems", "lot", "Objects", "id"));
+        let report = keys.check(tables).unwrap();
+
+        let broken: Vec<_> = report
+            .broken
+            .iter()
+            .map(|b| {
+                (
+                    &b.table[..],
+                    b.primary_key.clone(),
+                    &b.column[..],
+                    b.value.clone(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            broken,
+            [
+                (
+                    "ComponentsRegistry",
+                    Key::Integer(1002),
+                    "id",
+                    Key::Integer(1002)
+                ),
+                (
+                    "ComponentsRegistry",
+                    Key::Integer(1002),
+                    "id",
+                    Key::Integer(1002)
+                ),
+                (
+                    "LootMatrix",
+                    Key::Integer(2),
+                    "LootTableIndex",
+                    Key::Integer(11)
+                ),
+                ("MyShopItems", Key::Text("b".into()), "lot", Key::Integer(5)),
+            ]
+        );
+        assert_eq!(report.broken[2].target_table, "LootTable");
+        assert_eq!(report.broken[2].target_column, "LootTableIndex");
+
+        // `RarityTable` does not exist, and neither do most other tables
+        assert!(report
+            .skipped
+            .iter()
+            .any(|k| k.target_table == "RarityTable"));
+        assert!(!report
+            .skipped
+            .iter()
+            .any(|k| k.table == "ComponentsRegistry"));
+        assert_eq!(report.skipped.len(), CDCLIENT_FOREIGN_KEYS.len() - 3);
+    }
+}
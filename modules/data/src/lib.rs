@@ -2,5 +2,13 @@
 #![doc(html_logo_url = "https://assembly.lu-dev.net/rust-logo-lu-256.png")]
 #![doc(html_favicon_url = "https://assembly.lu-dev.net/rust-logo-lu-256.png")]
 
+pub mod behavior;
+pub mod foreign_keys;
+pub mod loot;
+pub mod object;
+pub mod precondition;
+
+mod util;
+
 pub use assembly_fdb as fdb;
 pub use assembly_xml as xml;
@@ -0,0 +1,778 @@
+//! # Loot drop probabilities
+//!
+//! When an enemy is smashed, every row of its loot matrix in `LootMatrix`
+//! is rolled separately:
+//!
+//! 1. The row drops with the chance in `percent`
+//! 2. If it does, a number of items between `minToDrop` and `maxToDrop` is
+//!    picked uniformly
+//! 3. For every item, a rarity is picked from the `RarityTable` of the row.
+//!    Its rows are sorted by `randmax`, and a random number in `[0, 1)` picks
+//!    the first row with a larger `randmax`
+//! 4. The item is picked uniformly from the items of the `LootTable` of the
+//!    row, that have that rarity in their `ItemComponent`. If there are none,
+//!    nothing drops
+//!
+//! Coins are dropped from the row in `CurrencyTable` with the largest
+//! `npcminlevel` that is not above the level of the enemy, uniformly between
+//! `minvalue` and `maxvalue`.
+//!
+//! [`LootData::drops`] computes the expected drops from these rules, and with
+//! the `simulate` feature, the [`Simulator`] rolls them with a seeded random
+//! number generator.
+//!
+//! ```
+//! use assembly_data::{
+//!     fdb::{mem::Database, typed::LoadError},
+//!     loot::{LootData, LootOptions},
+//! };
+//!
+//! fn print_drops(db: Database, loot_matrix_index: i32) -> Result<(), LoadError> {
+//!     let loot = LootData::load(db.tables()?)?;
+//!     let drops = loot.drops(loot_matrix_index, &LootOptions::new());
+//!     for item in &drops.items {
+//!         println!("{}: {:.2}%", item.lot, item.probability * 100.0);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use std::collections::BTreeMap;
+
+use assembly_fdb::{
+    mem::{Row, Table, Tables},
+    typed::{column_index, field_at, FromField, LoadError},
+};
+
+use crate::{
+    object::DestructibleData,
+    util::{rows_by_id, table},
+};
+
+/// The component type of the `ItemComponent`
+const ITEM_COMPONENT: i32 = 11;
+
+/// A row of `LootMatrix`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct MatrixEntry {
+    /// The `LootTableIndex` of the items
+    pub loot_table_index: i32,
+    /// The `RarityTableIndex` of the rarities
+    pub rarity_table_index: i32,
+    /// The chance that this entry drops anything
+    pub percent: f32,
+    /// The smallest number of items
+    pub min_to_drop: i32,
+    /// The largest number of items
+    pub max_to_drop: i32,
+}
+
+impl MatrixEntry {
+    /// The possible numbers of items
+    fn counts(&self) -> std::ops::RangeInclusive<i32> {
+        let min = self.min_to_drop.max(0);
+        min..=self.max_to_drop.max(min)
+    }
+}
+
+/// A row of `LootTable`
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct LootItem {
+    /// The LOT of the item
+    pub lot: i32,
+    /// The rarity from the `ItemComponent` of the item
+    pub rarity: Option<i32>,
+    /// Whether the item only drops for players on a mission
+    pub mission_drop: bool,
+}
+
+/// A row of `RarityTable`
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct RarityEntry {
+    /// The upper bound of the random number for this rarity
+    pub randmax: f32,
+    /// The rarity
+    pub rarity: i32,
+}
+
+/// A row of `CurrencyTable`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct CurrencyEntry {
+    /// The smallest level of the enemy
+    pub npc_min_level: i32,
+    /// The smallest number of coins
+    pub min_value: i32,
+    /// The largest number of coins
+    pub max_value: i32,
+}
+
+/// Options for computing and simulating drops
+#[derive(Debug, Clone, Default)]
+pub struct LootOptions {
+    mission_drops: bool,
+}
+
+impl LootOptions {
+    /// Create the default options, which exclude mission drops
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Include items with `MissionDrop`, as for a player on the mission
+    pub fn mission_drops(mut self, mission_drops: bool) -> Self {
+        self.mission_drops = mission_drops;
+        self
+    }
+}
+
+/// The expected drops of a single item
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct ItemDrop {
+    /// The LOT of the item
+    pub lot: i32,
+    /// The average number of items per roll of the loot matrix
+    pub expected_count: f64,
+    /// The chance that at least one item drops
+    pub probability: f64,
+}
+
+/// The expected drops of a loot matrix
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct DropDistribution {
+    /// The items that can drop, sorted by LOT
+    pub items: Vec<ItemDrop>,
+    /// The average number of items per roll
+    pub expected_total: f64,
+}
+
+impl DropDistribution {
+    /// Get the drops of the item `lot`
+    pub fn item(&self, lot: i32) -> Option<&ItemDrop> {
+        self.items
+            .binary_search_by_key(&lot, |item| item.lot)
+            .ok()
+            .map(|index| &self.items[index])
+    }
+}
+
+/// The coins that can drop
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct CurrencyRange {
+    /// The smallest number of coins
+    pub min: i32,
+    /// The largest number of coins
+    pub max: i32,
+    /// The average number of coins
+    pub expected: f64,
+}
+
+/// The expected drops of a destructible component
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct LootDistribution {
+    /// The items
+    pub items: DropDistribution,
+    /// The coins
+    pub currency: Option<CurrencyRange>,
+}
+
+/// The loot tables of a database
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LootData {
+    matrices: BTreeMap<i32, Vec<MatrixEntry>>,
+    loot_tables: BTreeMap<i32, Vec<LootItem>>,
+    rarity_tables: BTreeMap<i32, Vec<RarityEntry>>,
+    currency_tables: BTreeMap<i32, Vec<CurrencyEntry>>,
+}
+
+impl LootData {
+    /// Load the `LootMatrix`, `LootTable`, `RarityTable` and `CurrencyTable`
+    /// tables, and the rarities of the items from `ItemComponent`
+    pub fn load(tables: Tables<'_>) -> Result<Self, LoadError> {
+        let mut data = Self::default();
+
+        let t = table(tables, "LootMatrix")?;
+        let cols = Columns::new(t)
+            .col::<i32>("LootMatrixIndex")?
+            .col::<i32>("LootTableIndex")?
+            .col::<i32>("RarityTableIndex")?
+            .col::<f32>("percent")?
+            .col::<i32>("minToDrop")?
+            .col::<i32>("maxToDrop")?;
+        for row in t.row_iter() {
+            let entry = MatrixEntry {
+                loot_table_index: cols.get(row, 1)?,
+                rarity_table_index: cols.get(row, 2)?,
+                percent: cols.get(row, 3)?,
+                min_to_drop: cols.get(row, 4)?,
+                max_to_drop: cols.get(row, 5)?,
+            };
+            data.matrices
+                .entry(cols.get(row, 0)?)
+                .or_default()
+                .push(entry);
+        }
+
+        let rarities = ItemRarities::new(tables)?;
+        let t = table(tables, "LootTable")?;
+        let cols = Columns::new(t)
+            .col::<i32>("LootTableIndex")?
+            .col::<i32>("itemid")?
+            .col::<Option<bool>>("MissionDrop")?;
+        for row in t.row_iter() {
+            let lot = cols.get(row, 1)?;
+            let mission_drop: Option<bool> = cols.get(row, 2)?;
+            let item = LootItem {
+                lot,
+                rarity: rarities.get(lot)?,
+                mission_drop: mission_drop.unwrap_or(false),
+            };
+            data.loot_tables
+                .entry(cols.get(row, 0)?)
+                .or_default()
+                .push(item);
+        }
+
+        let t = table(tables, "RarityTable")?;
+        let cols = Columns::new(t)
+            .col::<i32>("RarityTableIndex")?
+            .col::<f32>("randmax")?
+            .col::<i32>("rarity")?;
+        for row in t.row_iter() {
+            let entry = RarityEntry {
+                randmax: cols.get(row, 1)?,
+                rarity: cols.get(row, 2)?,
+            };
+            data.rarity_tables
+                .entry(cols.get(row, 0)?)
+                .or_default()
+                .push(entry);
+        }
+        for entries in data.rarity_tables.values_mut() {
+            entries.sort_by(|a, b| a.randmax.total_cmp(&b.randmax));
+        }
+
+        let t = table(tables, "CurrencyTable")?;
+        let cols = Columns::new(t)
+            .col::<i32>("currencyIndex")?
+            .col::<i32>("npcminlevel")?
+            .col::<i32>("minvalue")?
+            .col::<i32>("maxvalue")?;
+        for row in t.row_iter() {
+            let entry = CurrencyEntry {
+                npc_min_level: cols.get(row, 1)?,
+                min_value: cols.get(row, 2)?,
+                max_value: cols.get(row, 3)?,
+            };
+            data.currency_tables
+                .entry(cols.get(row, 0)?)
+                .or_default()
+                .push(entry);
+        }
+
+        Ok(data)
+    }
+
+    /// Get the rows of the loot matrix `index`
+    pub fn matrix(&self, index: i32) -> &[MatrixEntry] {
+        self.matrices.get(&index).map_or(&[], Vec::as_slice)
+    }
+
+    /// Get the items of the loot table `index`
+    pub fn loot_table(&self, index: i32) -> &[LootItem] {
+        self.loot_tables.get(&index).map_or(&[], Vec::as_slice)
+    }
+
+    /// Get the rows of the rarity table `index`, sorted by `randmax`
+    pub fn rarity_table(&self, index: i32) -> &[RarityEntry] {
+        self.rarity_tables.get(&index).map_or(&[], Vec::as_slice)
+    }
+
+    /// Get the chance of every row of the rarity table `index`
+    pub fn rarity_chances(&self, index: i32) -> Vec<(i32, f64)> {
+        let mut last = 0.0;
+        let mut chances = Vec::new();
+        for entry in self.rarity_table(index) {
+            let randmax = f64::from(entry.randmax).clamp(0.0, 1.0);
+            chances.push((entry.rarity, (randmax - last).max(0.0)));
+            last = last.max(randmax);
+        }
+        chances
+    }
+
+    /// Get the row of the currency table `index` for an enemy of level `level`
+    pub fn currency_entry(&self, index: i32, level: i32) -> Option<&CurrencyEntry> {
+        self.currency_tables
+            .get(&index)?
+            .iter()
+            .filter(|entry| entry.npc_min_level <= level)
+            .max_by_key(|entry| entry.npc_min_level)
+    }
+
+    /// Get the coins that can drop from the currency table `index` for an
+    /// enemy of level `level`
+    pub fn currency(&self, index: i32, level: i32) -> Option<CurrencyRange> {
+        let entry = self.currency_entry(index, level)?;
+        let max = entry.max_value.max(entry.min_value);
+        Some(CurrencyRange {
+            min: entry.min_value,
+            max,
+            expected: (f64::from(entry.min_value) + f64::from(max)) / 2.0,
+        })
+    }
+
+    /// Get the items of the loot table `index` with the rarity `rarity`
+    fn pool(&self, index: i32, rarity: i32, options: &LootOptions) -> Vec<i32> {
+        self.loot_table(index)
+            .iter()
+            .filter(|item| item.rarity == Some(rarity))
+            .filter(|item| options.mission_drops || !item.mission_drop)
+            .map(|item| item.lot)
+            .collect()
+    }
+
+    /// Compute the expected drops of the loot matrix `index`
+    pub fn drops(&self, index: i32, options: &LootOptions) -> DropDistribution {
+        // For every item, the expected count and the chance of no drop
+        let mut items: BTreeMap<i32, (f64, f64)> = BTreeMap::new();
+        for entry in self.matrix(index) {
+            let percent = f64::from(entry.percent).clamp(0.0, 1.0);
+            let counts = entry.counts();
+            let count_chance = 1.0 / counts.clone().count() as f64;
+            let expected_rolls = f64::from(counts.start() + counts.end()) / 2.0;
+
+            // The chance per roll for every item of this entry
+            let mut chances: BTreeMap<i32, f64> = BTreeMap::new();
+            for (rarity, chance) in self.rarity_chances(entry.rarity_table_index) {
+                let pool = self.pool(entry.loot_table_index, rarity, options);
+                for &lot in &pool {
+                    *chances.entry(lot).or_default() += chance / pool.len() as f64;
+                }
+            }
+
+            for (lot, chance) in chances {
+                let none_per_drop: f64 = counts
+                    .clone()
+                    .map(|k| (1.0 - chance).powi(k) * count_chance)
+                    .sum();
+                let item = items.entry(lot).or_insert((0.0, 1.0));
+                item.0 += percent * expected_rolls * chance;
+                item.1 *= 1.0 - percent * (1.0 - none_per_drop);
+            }
+        }
+
+        let items: Vec<ItemDrop> = items
+            .into_iter()
+            .map(|(lot, (expected_count, none))| ItemDrop {
+                lot,
+                expected_count,
+                probability: 1.0 - none,
+            })
+            .collect();
+        DropDistribution {
+            expected_total: items.iter().map(|item| item.expected_count).sum(),
+            items,
+        }
+    }
+
+    /// Compute the expected drops of a destructible component
+    ///
+    /// The level defaults to `1` if it is not set.
+    pub fn for_destructible(
+        &self,
+        destructible: &DestructibleData,
+        options: &LootOptions,
+    ) -> LootDistribution {
+        let level = destructible.level.unwrap_or(1);
+        LootDistribution {
+            items: destructible
+                .loot_matrix_index
+                .map(|index| self.drops(index, options))
+                .unwrap_or_default(),
+            currency: destructible
+                .currency_index
+                .and_then(|index| self.currency(index, level)),
+        }
+    }
+}
+
+/// Column indices of a table, by position
+struct Columns<'a> {
+    table: Table<'a>,
+    indices: Vec<(usize, &'static str)>,
+}
+
+impl<'a> Columns<'a> {
+    fn new(table: Table<'a>) -> Self {
+        Self {
+            table,
+            indices: Vec::new(),
+        }
+    }
+
+    fn col<T: FromField<'a>>(mut self, name: &'static str) -> Result<Self, LoadError> {
+        let index = column_index::<T>(self.table, name)?;
+        self.indices.push((index, name));
+        Ok(self)
+    }
+
+    fn get<T: FromField<'a>>(&self, row: Row<'a>, i: usize) -> Result<T, LoadError> {
+        let (index, name) = self.indices[i];
+        field_at(self.table, row, index, name)
+    }
+}
+
+/// Finds the rarity of items with `ComponentsRegistry` and `ItemComponent`
+struct ItemRarities<'a> {
+    registry: Columns<'a>,
+    items: Columns<'a>,
+}
+
+impl<'a> ItemRarities<'a> {
+    fn new(tables: Tables<'a>) -> Result<Self, LoadError> {
+        Ok(Self {
+            registry: Columns::new(table(tables, "ComponentsRegistry")?)
+                .col::<i32>("component_type")?
+                .col::<i32>("component_id")?,
+            items: Columns::new(table(tables, "ItemComponent")?).col::<Option<i32>>("rarity")?,
+        })
+    }
+
+    fn get(&self, lot: i32) -> Result<Option<i32>, LoadError> {
+        for entry in rows_by_id(self.registry.table, lot) {
+            let component_type: i32 = self.registry.get(entry, 0)?;
+            if component_type != ITEM_COMPONENT {
+                continue;
+            }
+            let component_id = self.registry.get(entry, 1)?;
+            if let Some(row) = rows_by_id(self.items.table, component_id).next() {
+                return self.items.get(row, 0);
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(feature = "simulate")]
+pub use simulate::Simulator;
+
+#[cfg(feature = "simulate")]
+mod simulate {
+    use std::collections::BTreeMap;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::{DropDistribution, ItemDrop, LootData, LootOptions};
+
+    /// Rolls loot with a random number generator
+    ///
+    /// ```
+    /// use assembly_data::loot::{LootData, LootOptions, Simulator};
+    ///
+    /// let data = LootData::default();
+    /// let mut simulator = Simulator::seeded(&data, LootOptions::new(), 42);
+    /// let drops = simulator.simulate(1, 10_000);
+    /// assert!(drops.items.is_empty());
+    /// ```
+    pub struct Simulator<'d, R> {
+        data: &'d LootData,
+        options: LootOptions,
+        rng: R,
+    }
+
+    impl<'d> Simulator<'d, StdRng> {
+        /// Create a simulator with a [`StdRng`] seeded with `seed`
+        pub fn seeded(data: &'d LootData, options: LootOptions, seed: u64) -> Self {
+            Self::new(data, options, StdRng::seed_from_u64(seed))
+        }
+    }
+
+    impl<'d, R: Rng> Simulator<'d, R> {
+        /// Create a simulator with a random number generator
+        pub fn new(data: &'d LootData, options: LootOptions, rng: R) -> Self {
+            Self { data, options, rng }
+        }
+
+        /// Roll the loot matrix `index` once, and return the number of every item
+        pub fn roll(&mut self, index: i32) -> BTreeMap<i32, u32> {
+            let mut drops = BTreeMap::new();
+            for entry in self.data.matrix(index) {
+                if self.rng.gen::<f32>() >= entry.percent {
+                    continue;
+                }
+                let count = self.rng.gen_range(entry.counts());
+                for _ in 0..count {
+                    let roll = self.rng.gen::<f32>();
+                    let rarity = match self
+                        .data
+                        .rarity_table(entry.rarity_table_index)
+                        .iter()
+                        .find(|r| roll < r.randmax)
+                    {
+                        Some(r) => r.rarity,
+                        None => continue,
+                    };
+                    let pool = self
+                        .data
+                        .pool(entry.loot_table_index, rarity, &self.options);
+                    if pool.is_empty() {
+                        continue;
+                    }
+                    let lot = pool[self.rng.gen_range(0..pool.len())];
+                    *drops.entry(lot).or_default() += 1;
+                }
+            }
+            drops
+        }
+
+        /// Roll the number of coins from the currency table `index` for an
+        /// enemy of level `level`
+        pub fn roll_currency(&mut self, index: i32, level: i32) -> Option<i32> {
+            let range = self.data.currency(index, level)?;
+            Some(self.rng.gen_range(range.min..=range.max))
+        }
+
+        /// Roll the loot matrix `index` `trials` times, and return the
+        /// average drops
+        pub fn simulate(&mut self, index: i32, trials: u32) -> DropDistribution {
+            let mut items: BTreeMap<i32, (u64, u64)> = BTreeMap::new();
+            for _ in 0..trials {
+                for (lot, count) in self.roll(index) {
+                    let item = items.entry(lot).or_default();
+                    item.0 += u64::from(count);
+                    item.1 += 1;
+                }
+            }
+            let trials = f64::from(trials.max(1));
+            let items: Vec<ItemDrop> = items
+                .into_iter()
+                .map(|(lot, (count, hits))| ItemDrop {
+                    lot,
+                    expected_count: count as f64 / trials,
+                    probability: hits as f64 / trials,
+                })
+                .collect();
+            DropDistribution {
+                expected_total: items.iter().map(|item| item.expected_count).sum(),
+                items,
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "fdb-store"))]
+mod tests {
+    use super::*;
+    use crate::util::tests::{build, TableBuilder};
+    use assembly_fdb::{
+        mem::Database,
+        value::{owned::Field, ValueType},
+    };
+
+    fn database() -> Vec<u8> {
+        let mut registry = TableBuilder::new("ComponentsRegistry")
+            .column("id", ValueType::Integer)
+            .column("component_type", ValueType::Integer)
+            .column("component_id", ValueType::Integer);
+        let mut item_component = TableBuilder::new("ItemComponent")
+            .column("id", ValueType::Integer)
+            .column("rarity", ValueType::Integer);
+        for (lot, rarity) in [(100, 1), (101, 1), (102, 2), (103, 3), (105, 1)] {
+            registry.row(vec![
+                Field::Integer(lot),
+                Field::Integer(2),
+                Field::Integer(1),
+            ]);
+            registry.row(vec![
+                Field::Integer(lot),
+                Field::Integer(ITEM_COMPONENT),
+                Field::Integer(lot * 10),
+            ]);
+            item_component.row(vec![Field::Integer(lot * 10), Field::Integer(rarity)]);
+        }
+
+        let mut loot_table = TableBuilder::new("LootTable")
+            .column("itemid", ValueType::Integer)
+            .column("LootTableIndex", ValueType::Integer)
+            .column("MissionDrop", ValueType::Boolean);
+        for (lot, index, mission_drop) in [
+            (100, 10, false),
+            (101, 10, false),
+            (102, 10, false),
+            (103, 10, true),
+            (104, 10, false),
+            (105, 11, false),
+        ] {
+            loot_table.row(vec![
+                Field::Integer(lot),
+                Field::Integer(index),
+                Field::Boolean(mission_drop),
+            ]);
+        }
+
+        let mut rarity_table = TableBuilder::new("RarityTable")
+            .column("id", ValueType::Integer)
+            .column("randmax", ValueType::Float)
+            .column("rarity", ValueType::Integer)
+            .column("RarityTableIndex", ValueType::Integer);
+        for (id, randmax, rarity) in [(1, 1.0, 3), (2, 0.6, 1), (3, 0.9, 2)] {
+            rarity_table.row(vec![
+                Field::Integer(id),
+                Field::Float(randmax),
+                Field::Integer(rarity),
+                Field::Integer(5),
+            ]);
+        }
+
+        let mut matrix = TableBuilder::new("LootMatrix")
+            .column("LootMatrixIndex", ValueType::Integer)
+            .column("LootTableIndex", ValueType::Integer)
+            .column("RarityTableIndex", ValueType::Integer)
+            .column("percent", ValueType::Float)
+            .column("minToDrop", ValueType::Integer)
+            .column("maxToDrop", ValueType::Integer);
+        for (table, percent, min, max) in [(10, 0.5, 1, 3), (11, 1.0, 1, 1)] {
+            matrix.row(vec![
+                Field::Integer(1),
+                Field::Integer(table),
+                Field::Integer(5),
+                Field::Float(percent),
+                Field::Integer(min),
+                Field::Integer(max),
+            ]);
+        }
+
+        let mut currency = TableBuilder::new("CurrencyTable")
+            .column("currencyIndex", ValueType::Integer)
+            .column("npcminlevel", ValueType::Integer)
+            .column("minvalue", ValueType::Integer)
+            .column("maxvalue", ValueType::Integer);
+        currency.row(vec![
+            Field::Integer(3),
+            Field::Integer(1),
+            Field::Integer(10),
+            Field::Integer(20),
+        ]);
+        currency.row(vec![
+            Field::Integer(3),
+            Field::Integer(5),
+            Field::Integer(50),
+            Field::Integer(100),
+        ]);
+
+        build(vec![
+            registry,
+            item_component,
+            loot_table,
+            rarity_table,
+            matrix,
+            currency,
+        ])
+    }
+
+    fn assert_close(a: f64, b: f64, epsilon: f64) {
+        assert!((a - b).abs() < epsilon, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn test_drops() {
+        let buf = database();
+        let data = LootData::load(Database::new(&buf).tables().unwrap()).unwrap();
+        let chances = data.rarity_chances(5);
+        assert_eq!(chances.len(), 3);
+        assert_close(chances[0].1, 0.6, 1e-6);
+        assert_close(chances[1].1, 0.3, 1e-6);
+        assert_close(chances[2].1, 0.1, 1e-6);
+        let item = data.loot_table(10).iter().find(|item| item.lot == 104);
+        assert_eq!(item.unwrap().rarity, None);
+
+        let drops = data.drops(1, &LootOptions::new());
+        let lots: Vec<_> = drops.items.iter().map(|item| item.lot).collect();
+        assert_eq!(lots, [100, 101, 102, 105]);
+        // 50% * 2 items on average * 60% for rarity 1 / 2 items of rarity 1
+        let item = drops.item(100).unwrap();
+        assert_close(item.expected_count, 0.3, 1e-6);
+        assert_close(
+            item.probability,
+            0.5 * (1.0 - (0.7 + 0.49 + 0.343) / 3.0),
+            1e-6,
+        );
+        assert_close(drops.item(102).unwrap().expected_count, 0.3, 1e-6);
+        let item = drops.item(105).unwrap();
+        assert_close(item.expected_count, 0.6, 1e-6);
+        assert_close(item.probability, 0.6, 1e-6);
+        assert_close(drops.expected_total, 1.5, 1e-6);
+
+        let drops = data.drops(1, &LootOptions::new().mission_drops(true));
+        assert_close(drops.item(103).unwrap().expected_count, 0.1, 1e-6);
+        assert_eq!(
+            data.drops(2, &LootOptions::new()),
+            DropDistribution::default()
+        );
+    }
+
+    #[test]
+    fn test_currency() {
+        let buf = database();
+        let data = LootData::load(Database::new(&buf).tables().unwrap()).unwrap();
+        assert_eq!(data.currency(3, 0), None);
+        assert_eq!(
+            data.currency(3, 6),
+            Some(CurrencyRange {
+                min: 50,
+                max: 100,
+                expected: 75.0
+            })
+        );
+
+        let destructible = DestructibleData {
+            component_id: 1,
+            faction: None,
+            life: None,
+            armor: None,
+            imagination: None,
+            level: Some(4),
+            loot_matrix_index: Some(1),
+            currency_index: Some(3),
+            is_smashable: None,
+        };
+        let loot = data.for_destructible(&destructible, &LootOptions::new());
+        assert_eq!(loot.items, data.drops(1, &LootOptions::new()));
+        assert_eq!(loot.currency.unwrap().expected, 15.0);
+    }
+
+    #[test]
+    #[cfg(feature = "simulate")]
+    fn test_simulate() {
+        let buf = database();
+        let data = LootData::load(Database::new(&buf).tables().unwrap()).unwrap();
+        for options in [LootOptions::new(), LootOptions::new().mission_drops(true)] {
+            let expected = data.drops(1, &options);
+            let mut simulator = Simulator::seeded(&data, options, 1234);
+            let simulated = simulator.simulate(1, 100_000);
+            assert_eq!(simulated.items.len(), expected.items.len());
+            for (a, b) in simulated.items.iter().zip(&expected.items) {
+                assert_eq!(a.lot, b.lot);
+                assert_close(a.expected_count, b.expected_count, 0.01);
+                assert_close(a.probability, b.probability, 0.01);
+            }
+            assert_close(simulated.expected_total, expected.expected_total, 0.02);
+        }
+
+        let mut simulator = Simulator::seeded(&data, LootOptions::new(), 1);
+        let mut sum = 0;
+        for _ in 0..10_000 {
+            let coins = simulator.roll_currency(3, 6).unwrap();
+            assert!((50..=100).contains(&coins));
+            sum += coins;
+        }
+        assert_close(f64::from(sum) / 10_000.0, 75.0, 1.0);
+    }
+}
@@ -0,0 +1,621 @@
+//! # Objects and their components
+//!
+//! An object template (LOT) is a row in `Objects`. The components of the
+//! object are listed in `ComponentsRegistry`, as pairs of a component type and
+//! the ID of a row in the table of that component type, e.g. the
+//! `RenderComponent` for type `2`.
+//!
+//! The [`ObjectResolver`] joins these tables for a single LOT:
+//!
+//! ```
+//! use assembly_data::{fdb::{mem::Database, typed::LoadError}, object::ObjectResolver};
+//!
+//! fn print_object(db: Database, lot: i32) -> Result<(), LoadError> {
+//!     let resolver = ObjectResolver::new(db.tables()?)?;
+//!     if let Some(object) = resolver.resolve(lot)? {
+//!         println!("{:?}: {:?}", object.name, object.components.keys());
+//!         if let Some(script) = &object.script {
+//!             println!("script: {:?}", script.script_name);
+//!         }
+//!         for warning in &object.warnings {
+//!             println!("warning: {}", warning);
+//!         }
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Missing component rows, tables and columns don't fail the lookup, but are
+//! reported as a [`Warning`] on the [`Object`].
+
+use std::collections::BTreeMap;
+
+use assembly_fdb::{
+    mem::{MemToOwned, Row, Table, Tables},
+    typed::{column_index, field_at, FromValue, LoadError},
+    value::{
+        owned::{Field, OwnedContext},
+        ValueType,
+    },
+};
+use displaydoc::Display;
+
+use crate::util::{rows_by_id, table};
+
+/// The component type of the `RenderComponent`
+pub const RENDER_COMPONENT: i32 = 2;
+/// The component type of the `ScriptComponent`
+pub const SCRIPT_COMPONENT: i32 = 5;
+/// The component type of the `DestructibleComponent`
+pub const DESTRUCTIBLE_COMPONENT: i32 = 7;
+/// The component types that use the `PhysicsComponent` table
+///
+/// These are the simple (`3`), rigid body phantom (`20`) and phantom (`40`)
+/// physics components.
+pub const PHYSICS_COMPONENTS: &[i32] = &[3, 20, 40];
+
+/// The tables of the component types that are known by default
+pub const COMPONENT_TABLES: &[(i32, &str)] = &[
+    (RENDER_COMPONENT, "RenderComponent"),
+    (3, "PhysicsComponent"),
+    (SCRIPT_COMPONENT, "ScriptComponent"),
+    (DESTRUCTIBLE_COMPONENT, "DestructibleComponent"),
+    (11, "ItemComponent"),
+    (16, "VendorComponent"),
+    (17, "InventoryComponent"),
+    (20, "PhysicsComponent"),
+    (23, "CollectibleComponent"),
+    (25, "MovingPlatforms"),
+    (26, "PetComponent"),
+    (31, "MovementAIComponent"),
+    (35, "MinifigComponent"),
+    (40, "PhysicsComponent"),
+    (48, "RebuildComponent"),
+    (53, "PackageComponent"),
+    (60, "BaseCombatAIComponent"),
+    (67, "RocketLaunchpadControlComponent"),
+    (73, "MissionNPCComponent"),
+    (78, "ProximityMonitorComponent"),
+    (104, "RailActivatorComponent"),
+];
+
+/// A row as a map from column names to values
+pub type Record = BTreeMap<String, Field>;
+
+/// A problem that was found while resolving an object
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub enum Warning {
+    /// Component type {component_type} is registered more than once, ignoring component {component_id}
+    DuplicateComponent {
+        /// The type of the component
+        component_type: i32,
+        /// The ID of the ignored component
+        component_id: i32,
+    },
+    /// Table {table} of component type {component_type} does not exist
+    MissingTable {
+        /// The type of the component
+        component_type: i32,
+        /// The name of the table
+        table: String,
+    },
+    /// Table {table} has no row for component {component_id}
+    MissingRow {
+        /// The type of the component
+        component_type: i32,
+        /// The name of the table
+        table: String,
+        /// The ID of the component
+        component_id: i32,
+    },
+    /// Column {table}.{column} does not exist
+    MissingColumn {
+        /// The name of the table
+        table: String,
+        /// The name of the column
+        column: &'static str,
+    },
+    /// Field {table}.{column} has type {actual}, expected {expected}
+    FieldType {
+        /// The name of the table
+        table: String,
+        /// The name of the column
+        column: &'static str,
+        /// The type that was expected
+        expected: ValueType,
+        /// The type of the field
+        actual: ValueType,
+    },
+}
+
+/// A component of an object
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct Component {
+    /// The type of the component
+    pub component_type: i32,
+    /// The ID of the component in its table
+    pub component_id: i32,
+    /// The table of the component type, if it is known
+    pub table: Option<String>,
+    /// The rows of that table with the ID of the component
+    ///
+    /// Most tables have one row per component, but some, like the
+    /// `InventoryComponent`, have one row per entry.
+    pub rows: Vec<Record>,
+}
+
+/// The linked `RenderComponent` of an object
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct RenderData {
+    /// The ID of the component
+    pub component_id: i32,
+    /// The path of the model
+    pub render_asset: Option<String>,
+    /// The path of the icon
+    pub icon_asset: Option<String>,
+}
+
+/// The linked `PhysicsComponent` of an object
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct PhysicsData {
+    /// The type of the component, one of [`PHYSICS_COMPONENTS`]
+    pub component_type: i32,
+    /// The ID of the component
+    pub component_id: i32,
+    /// The path of the collision mesh
+    pub physics_asset: Option<String>,
+    /// The collision group
+    pub collision_group: Option<i32>,
+}
+
+/// The linked `ScriptComponent` of an object
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct ScriptData {
+    /// The ID of the component
+    pub component_id: i32,
+    /// The path of the server script
+    pub script_name: Option<String>,
+    /// The path of the client script
+    pub client_script_name: Option<String>,
+}
+
+/// The linked `DestructibleComponent` of an object
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct DestructibleData {
+    /// The ID of the component
+    pub component_id: i32,
+    /// The faction
+    pub faction: Option<i32>,
+    /// The health
+    pub life: Option<i32>,
+    /// The armor
+    pub armor: Option<f32>,
+    /// The imagination
+    pub imagination: Option<i32>,
+    /// The level
+    pub level: Option<i32>,
+    /// The ID of the loot matrix in `LootMatrix`
+    pub loot_matrix_index: Option<i32>,
+    /// The ID of the currency drops in `CurrencyTable`
+    pub currency_index: Option<i32>,
+    /// Whether the object can be smashed
+    pub is_smashable: Option<bool>,
+}
+
+/// An object template with all of its components
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct Object {
+    /// The ID of the object template (LOT)
+    pub lot: i32,
+    /// The internal name
+    pub name: Option<String>,
+    /// The type, e.g. `Enemies` or `Loot`
+    pub object_type: Option<String>,
+    /// The row in `Objects`
+    pub row: Record,
+    /// The components by component type
+    pub components: BTreeMap<i32, Component>,
+    /// The render component
+    pub render: Option<RenderData>,
+    /// The first physics component
+    pub physics: Option<PhysicsData>,
+    /// The script component
+    pub script: Option<ScriptData>,
+    /// The destructible component
+    pub destructible: Option<DestructibleData>,
+    /// The problems that were found
+    pub warnings: Vec<Warning>,
+}
+
+impl Object {
+    /// Get the first row of the component with type `component_type`
+    pub fn component_row(&self, component_type: i32) -> Option<&Record> {
+        self.components
+            .get(&component_type)
+            .and_then(|c| c.rows.first())
+    }
+}
+
+/// Resolves the components of objects
+pub struct ObjectResolver<'a> {
+    tables: Tables<'a>,
+    objects: Table<'a>,
+    registry: Table<'a>,
+    col_component_type: usize,
+    col_component_id: usize,
+    component_tables: BTreeMap<i32, String>,
+}
+
+impl<'a> ObjectResolver<'a> {
+    /// Create a resolver with the tables in [`COMPONENT_TABLES`]
+    ///
+    /// This fails if the `Objects` or `ComponentsRegistry` tables don't exist.
+    pub fn new(tables: Tables<'a>) -> Result<Self, LoadError> {
+        let objects = table(tables, "Objects")?;
+        let registry = table(tables, "ComponentsRegistry")?;
+        Ok(Self {
+            tables,
+            objects,
+            registry,
+            col_component_type: column_index::<i32>(registry, "component_type")?,
+            col_component_id: column_index::<i32>(registry, "component_id")?,
+            component_tables: COMPONENT_TABLES
+                .iter()
+                .map(|&(component_type, name)| (component_type, name.to_owned()))
+                .collect(),
+        })
+    }
+
+    /// Set the table for the component type `component_type`
+    pub fn component_table(mut self, component_type: i32, name: &str) -> Self {
+        self.component_tables
+            .insert(component_type, name.to_owned());
+        self
+    }
+
+    /// Resolve the object `lot`, or return `None` if it is not in `Objects`
+    pub fn resolve(&self, lot: i32) -> Result<Option<Object>, LoadError> {
+        let row = match rows_by_id(self.objects, lot).next() {
+            Some(row) => record(self.objects, row),
+            None => return Ok(None),
+        };
+        let mut warnings = Vec::new();
+        let name = get(&row, "Objects", "name", &mut warnings);
+        let object_type = get(&row, "Objects", "type", &mut warnings);
+
+        let mut components = BTreeMap::new();
+        let registry = self.registry;
+        for entry in rows_by_id(registry, lot) {
+            let component_type: i32 =
+                field_at(registry, entry, self.col_component_type, "component_type")?;
+            let component_id: i32 =
+                field_at(registry, entry, self.col_component_id, "component_id")?;
+            if components.contains_key(&component_type) {
+                warnings.push(Warning::DuplicateComponent {
+                    component_type,
+                    component_id,
+                });
+                continue;
+            }
+            let component = self.component(component_type, component_id, &mut warnings)?;
+            components.insert(component_type, component);
+        }
+
+        let mut object = Object {
+            lot,
+            name,
+            object_type,
+            row,
+            components,
+            render: None,
+            physics: None,
+            script: None,
+            destructible: None,
+            warnings,
+        };
+        link(&mut object);
+        Ok(Some(object))
+    }
+
+    fn component(
+        &self,
+        component_type: i32,
+        component_id: i32,
+        warnings: &mut Vec<Warning>,
+    ) -> Result<Component, LoadError> {
+        let mut component = Component {
+            component_type,
+            component_id,
+            table: self.component_tables.get(&component_type).cloned(),
+            rows: Vec::new(),
+        };
+        let name = match &component.table {
+            Some(name) => name,
+            None => return Ok(component),
+        };
+        let t = match self.tables.by_name(name) {
+            Some(t) => t?,
+            None => {
+                warnings.push(Warning::MissingTable {
+                    component_type,
+                    table: name.clone(),
+                });
+                return Ok(component);
+            }
+        };
+        component.rows = rows_by_id(t, component_id)
+            .map(|row| record(t, row))
+            .collect();
+        if component.rows.is_empty() {
+            warnings.push(Warning::MissingRow {
+                component_type,
+                table: name.clone(),
+                component_id,
+            });
+        }
+        Ok(component)
+    }
+}
+
+/// Fill in the typed data of the linked components
+fn link(object: &mut Object) {
+    let warnings = &mut object.warnings;
+    let components = &object.components;
+    let first = |component_type: i32| {
+        let component = components.get(&component_type)?;
+        Some((component, component.rows.first()?))
+    };
+
+    if let Some((c, row)) = first(RENDER_COMPONENT) {
+        let table = "RenderComponent";
+        object.render = Some(RenderData {
+            component_id: c.component_id,
+            render_asset: get(row, table, "render_asset", warnings),
+            icon_asset: get(row, table, "icon_asset", warnings),
+        });
+    }
+    if let Some((c, row)) = PHYSICS_COMPONENTS.iter().find_map(|&t| first(t)) {
+        let table = "PhysicsComponent";
+        object.physics = Some(PhysicsData {
+            component_type: c.component_type,
+            component_id: c.component_id,
+            physics_asset: get(row, table, "physics_asset", warnings),
+            collision_group: get(row, table, "collisionGroup", warnings),
+        });
+    }
+    if let Some((c, row)) = first(SCRIPT_COMPONENT) {
+        let table = "ScriptComponent";
+        object.script = Some(ScriptData {
+            component_id: c.component_id,
+            script_name: get(row, table, "script_name", warnings),
+            client_script_name: get(row, table, "client_script_name", warnings),
+        });
+    }
+    if let Some((c, row)) = first(DESTRUCTIBLE_COMPONENT) {
+        let table = "DestructibleComponent";
+        object.destructible = Some(DestructibleData {
+            component_id: c.component_id,
+            faction: get(row, table, "faction", warnings),
+            life: get(row, table, "life", warnings),
+            armor: get(row, table, "armor", warnings),
+            imagination: get(row, table, "imagination", warnings),
+            level: get(row, table, "level", warnings),
+            loot_matrix_index: get(row, table, "LootMatrixIndex", warnings),
+            currency_index: get(row, table, "CurrencyIndex", warnings),
+            is_smashable: get(row, table, "isSmashable", warnings),
+        });
+    }
+}
+
+/// Copy all fields of a row
+fn record(table: Table<'_>, row: Row<'_>) -> Record {
+    table
+        .column_iter()
+        .zip(row.field_iter())
+        .map(|(column, field)| (column.name().into_owned(), field.map(&mut MemToOwned)))
+        .collect()
+}
+
+/// Get a field of a record, or `None` with a warning if it is missing or has the wrong type
+fn get<T: FromValue<OwnedContext>>(
+    record: &Record,
+    table: &str,
+    column: &'static str,
+    warnings: &mut Vec<Warning>,
+) -> Option<T> {
+    match record.get(column) {
+        None => {
+            warnings.push(Warning::MissingColumn {
+                table: table.to_owned(),
+                column,
+            });
+            None
+        }
+        Some(Field::Nothing) => None,
+        Some(field) => match T::from_value(field.clone()) {
+            Ok(value) => Some(value),
+            Err(actual) => {
+                warnings.push(Warning::FieldType {
+                    table: table.to_owned(),
+                    column,
+                    expected: T::VALUE_TYPE,
+                    actual,
+                });
+                None
+            }
+        },
+    }
+}
+
+#[cfg(all(test, feature = "fdb-store"))]
+mod tests {
+    use super::*;
+    use crate::util::tests::{build, TableBuilder};
+    use assembly_fdb::mem::Database;
+
+    fn database() -> Vec<u8> {
+        let text = |s: &str| Field::Text(s.to_owned());
+
+        let mut objects = TableBuilder::new("Objects")
+            .column("id", ValueType::Integer)
+            .column("name", ValueType::Text)
+            .column("type", ValueType::Text);
+        objects.row(vec![
+            Field::Integer(1000),
+            text("Crate"),
+            text("Environmental"),
+        ]);
+        objects.row(vec![Field::Integer(1001), text("Spider"), text("Enemies")]);
+
+        let mut registry = TableBuilder::new("ComponentsRegistry")
+            .column("id", ValueType::Integer)
+            .column("component_type", ValueType::Integer)
+            .column("component_id", ValueType::Integer);
+        for (lot, component_type, component_id) in [
+            (1000, 2, 10),
+            (1000, 3, 20),
+            (1000, 5, 30),
+            (1000, 7, 40),
+            (1000, 999, 1),
+            (1001, 2, 11),
+            (1001, 2, 12),
+            (1001, 7, 41),
+            (1001, 26, 1),
+        ] {
+            registry.row(vec![
+                Field::Integer(lot),
+                Field::Integer(component_type),
+                Field::Integer(component_id),
+            ]);
+        }
+
+        let mut render = TableBuilder::new("RenderComponent")
+            .column("id", ValueType::Integer)
+            .column("render_asset", ValueType::Text)
+            .column("icon_asset", ValueType::Text);
+        render.row(vec![Field::Integer(10), text("crate.nif"), Field::Nothing]);
+        render.row(vec![
+            Field::Integer(11),
+            text("spider.nif"),
+            text("spider.dds"),
+        ]);
+
+        let mut physics = TableBuilder::new("PhysicsComponent")
+            .column("id", ValueType::Integer)
+            .column("physics_asset", ValueType::Text)
+            .column("collisionGroup", ValueType::Integer);
+        physics.row(vec![
+            Field::Integer(20),
+            text("crate.hkx"),
+            Field::Integer(3),
+        ]);
+
+        let mut script = TableBuilder::new("ScriptComponent")
+            .column("id", ValueType::Integer)
+            .column("script_name", ValueType::Text)
+            .column("client_script_name", ValueType::Text);
+        script.row(vec![Field::Integer(30), text("crate.lua"), Field::Nothing]);
+
+        let mut destructible = TableBuilder::new("DestructibleComponent")
+            .column("id", ValueType::Integer)
+            .column("life", ValueType::Integer)
+            .column("LootMatrixIndex", ValueType::Integer);
+        destructible.row(vec![
+            Field::Integer(40),
+            Field::Integer(1),
+            Field::Integer(5),
+        ]);
+
+        build(vec![
+            objects,
+            registry,
+            render,
+            physics,
+            script,
+            destructible,
+        ])
+    }
+
+    #[test]
+    fn test_resolve() {
+        let buf = database();
+        let resolver = ObjectResolver::new(Database::new(&buf).tables().unwrap()).unwrap();
+        assert_eq!(resolver.resolve(1002).unwrap(), None);
+
+        let object = resolver.resolve(1000).unwrap().unwrap();
+        assert_eq!(object.name.as_deref(), Some("Crate"));
+        assert_eq!(object.object_type.as_deref(), Some("Environmental"));
+        assert_eq!(object.row["id"], Field::Integer(1000));
+        assert_eq!(
+            object.components.keys().copied().collect::<Vec<_>>(),
+            [2, 3, 5, 7, 999]
+        );
+        assert_eq!(object.components[&999].table, None);
+        assert_eq!(
+            object.component_row(2).unwrap()["render_asset"],
+            Field::Text("crate.nif".to_owned())
+        );
+
+        let render = object.render.unwrap();
+        assert_eq!(render.render_asset.as_deref(), Some("crate.nif"));
+        assert_eq!(render.icon_asset, None);
+        let physics = object.physics.unwrap();
+        assert_eq!(physics.component_type, 3);
+        assert_eq!(physics.collision_group, Some(3));
+        let script = object.script.unwrap();
+        assert_eq!(script.script_name.as_deref(), Some("crate.lua"));
+        let destructible = object.destructible.unwrap();
+        assert_eq!(destructible.life, Some(1));
+        assert_eq!(destructible.loot_matrix_index, Some(5));
+
+        // The test table only has some of the columns
+        assert!(object.warnings.iter().all(|w| matches!(
+            w,
+            Warning::MissingColumn {
+                table,
+                ..
+            } if table == "DestructibleComponent"
+        )));
+    }
+
+    #[test]
+    fn test_resolve_warnings() {
+        let buf = database();
+        let resolver = ObjectResolver::new(Database::new(&buf).tables().unwrap())
+            .unwrap()
+            .component_table(999, "CustomComponent");
+        let object = resolver.resolve(1001).unwrap().unwrap();
+        assert_eq!(object.render.unwrap().component_id, 11);
+        assert_eq!(object.destructible, None);
+        assert_eq!(
+            object.warnings,
+            [
+                Warning::DuplicateComponent {
+                    component_type: 2,
+                    component_id: 12
+                },
+                Warning::MissingRow {
+                    component_type: 7,
+                    table: "DestructibleComponent".to_owned(),
+                    component_id: 41
+                },
+                Warning::MissingTable {
+                    component_type: 26,
+                    table: "PetComponent".to_owned()
+                },
+            ]
+        );
+        let object = resolver.resolve(1000).unwrap().unwrap();
+        assert!(object.warnings.contains(&Warning::MissingTable {
+            component_type: 999,
+            table: "CustomComponent".to_owned()
+        }));
+    }
+}
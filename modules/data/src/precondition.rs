@@ -0,0 +1,877 @@
+//! # Precondition expressions
+//!
+//! Many tables restrict when something is available with a string of IDs,
+//! for example `Missions.prereqMissionID`, which lists missions that need to
+//! be completed first, or `ItemComponent.reqPrecondition`, which lists rows of
+//! the `Preconditions` table.
+//!
+//! The IDs are combined with the following operators:
+//!
+//! - `a|b`: `a` or `b` holds
+//! - `a,b`, `a;b` and `a&b`: both `a` and `b` hold
+//! - `(a)`: grouping
+//!
+//! `|` binds stronger than the other operators, so `1|2,3` means that `1` or
+//! `2` and also `3` need to hold.
+//!
+//! ```
+//! use assembly_data::precondition::{Expr, PlayerState};
+//!
+//! struct Player;
+//!
+//! impl PlayerState for Player {
+//!     fn has_completed_mission(&self, mission_id: i32) -> bool {
+//!         mission_id == 2
+//!     }
+//!     fn is_on_mission(&self, _mission_id: i32) -> bool { false }
+//!     fn item_count(&self, _lot: i32) -> u32 { 0 }
+//!     fn is_equipped(&self, _lot: i32) -> bool { false }
+//!     fn has_flag(&self, _flag_id: i32) -> bool { false }
+//!     fn level(&self) -> i32 { 1 }
+//! }
+//!
+//! let expr = Expr::parse("(1|2);3")?;
+//! assert_eq!(expr, Expr::All(vec![Expr::Any(vec![Expr::Id(1), Expr::Id(2)]), Expr::Id(3)]));
+//! assert!(!expr.eval_missions(&Player));
+//! assert!(Expr::parse("1|2")?.eval_missions(&Player));
+//! # Ok::<(), assembly_data::precondition::ParseError>(())
+//! ```
+
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    convert::Infallible,
+    fmt,
+};
+
+use assembly_fdb::{
+    mem::Tables,
+    typed::{column_index, field_at, LoadError},
+};
+use displaydoc::Display;
+use thiserror::Error;
+
+use crate::util::table;
+
+/// A parsed precondition expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub enum Expr {
+    /// A single ID
+    Id(i32),
+    /// All of the expressions need to hold
+    All(Vec<Expr>),
+    /// At least one of the expressions needs to hold
+    Any(Vec<Expr>),
+}
+
+/// Errors when parsing an [`Expr`]
+#[derive(Debug, Clone, PartialEq, Eq, Error, Display)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub enum ParseError {
+    /// The expression is empty
+    Empty,
+    /// Unexpected {found:?} at position {pos}
+    Unexpected {
+        /// The byte offset of the character
+        pos: usize,
+        /// The unexpected character
+        found: char,
+    },
+    /// Unexpected end of the expression
+    UnexpectedEnd,
+    /// The number at position {pos} is too large
+    Overflow {
+        /// The byte offset of the number
+        pos: usize,
+    },
+}
+
+impl Expr {
+    /// Parse an expression
+    ///
+    /// Whitespace between IDs and operators is ignored.
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        if input.trim().is_empty() {
+            return Err(ParseError::Empty);
+        }
+        let mut parser = Parser { input, pos: 0 };
+        let expr = parser.all()?;
+        match parser.peek() {
+            None => Ok(expr),
+            Some(found) => Err(ParseError::Unexpected {
+                pos: parser.pos,
+                found,
+            }),
+        }
+    }
+
+    /// Get all IDs in the expression
+    pub fn ids(&self) -> BTreeSet<i32> {
+        let mut ids = BTreeSet::new();
+        self.collect_ids(&mut ids);
+        ids
+    }
+
+    fn collect_ids(&self, ids: &mut BTreeSet<i32>) {
+        match self {
+            Expr::Id(id) => {
+                ids.insert(*id);
+            }
+            Expr::All(list) | Expr::Any(list) => list.iter().for_each(|e| e.collect_ids(ids)),
+        }
+    }
+
+    /// Evaluate the expression with `check` for the IDs
+    ///
+    /// The operands are checked from left to right, and `All` and `Any`
+    /// stop at the first operand that decides the result.
+    pub fn eval<E, F: FnMut(i32) -> Result<bool, E>>(&self, check: &mut F) -> Result<bool, E> {
+        match self {
+            Expr::Id(id) => check(*id),
+            Expr::All(list) => {
+                for expr in list {
+                    if !expr.eval(check)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            Expr::Any(list) => {
+                for expr in list {
+                    if expr.eval(check)? {
+                        return Ok(true);
+                    }
+                }
+                Ok(false)
+            }
+        }
+    }
+
+    /// Evaluate the expression with IDs of missions that need to be completed,
+    /// like in `Missions.prereqMissionID`
+    pub fn eval_missions<P: PlayerState + ?Sized>(&self, player: &P) -> bool {
+        self.eval::<Infallible, _>(&mut |id| Ok(player.has_completed_mission(id)))
+            .unwrap_or_else(|never| match never {})
+    }
+
+    /// Evaluate the expression with IDs of rows in `Preconditions`, like in
+    /// `ItemComponent.reqPrecondition`
+    pub fn eval_preconditions<P: PlayerState + ?Sized>(
+        &self,
+        preconditions: &Preconditions,
+        player: &P,
+    ) -> Result<bool, EvalError> {
+        self.eval(&mut |id| {
+            preconditions
+                .get(id)
+                .ok_or(EvalError::MissingPrecondition(id))?
+                .check(player)
+        })
+    }
+
+    fn fmt_prec(&self, f: &mut fmt::Formatter<'_>, in_any: bool) -> fmt::Result {
+        let (list, separator) = match self {
+            Expr::Id(id) => return write!(f, "{}", id),
+            Expr::All(list) => (list, ','),
+            Expr::Any(list) => (list, '|'),
+        };
+        let parens = in_any && separator == ',';
+        if parens {
+            write!(f, "(")?;
+        }
+        for (i, expr) in list.iter().enumerate() {
+            if i > 0 {
+                write!(f, "{}", separator)?;
+            }
+            expr.fmt_prec(f, separator == '|')?;
+        }
+        if parens {
+            write!(f, ")")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Expr {
+    /// Writes the expression with `,` and `|`, so that it parses to the same value
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_prec(f, false)
+    }
+}
+
+struct Parser<'s> {
+    input: &'s str,
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn skip_whitespace(&mut self) {
+        let rest = &self.input[self.pos..];
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.input[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) {
+        if let Some(c) = self.peek() {
+            self.pos += c.len_utf8();
+        }
+    }
+
+    /// `all := any (("," | ";" | "&") any)*`
+    fn all(&mut self) -> Result<Expr, ParseError> {
+        let mut list = vec![self.any()?];
+        while let Some(',' | ';' | '&') = self.peek() {
+            self.bump();
+            list.push(self.any()?);
+        }
+        Ok(flatten(list, true))
+    }
+
+    /// `any := term ("|" term)*`
+    fn any(&mut self) -> Result<Expr, ParseError> {
+        let mut list = vec![self.term()?];
+        while let Some('|') = self.peek() {
+            self.bump();
+            list.push(self.term()?);
+        }
+        Ok(flatten(list, false))
+    }
+
+    /// `term := id | "(" all ")"`
+    fn term(&mut self) -> Result<Expr, ParseError> {
+        match self.peek() {
+            Some('(') => {
+                self.bump();
+                let expr = self.all()?;
+                match self.peek() {
+                    Some(')') => {
+                        self.bump();
+                        Ok(expr)
+                    }
+                    Some(found) => Err(ParseError::Unexpected {
+                        pos: self.pos,
+                        found,
+                    }),
+                    None => Err(ParseError::UnexpectedEnd),
+                }
+            }
+            Some(c) if c.is_ascii_digit() => {
+                let start = self.pos;
+                let rest = &self.input[start..];
+                let len = rest
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len());
+                self.pos += len;
+                rest[..len]
+                    .parse()
+                    .map(Expr::Id)
+                    .map_err(|_| ParseError::Overflow { pos: start })
+            }
+            Some(found) => Err(ParseError::Unexpected {
+                pos: self.pos,
+                found,
+            }),
+            None => Err(ParseError::UnexpectedEnd),
+        }
+    }
+}
+
+/// Merge nested lists of the same kind, and unwrap lists with one element
+fn flatten(list: Vec<Expr>, is_all: bool) -> Expr {
+    if list.len() == 1 {
+        return list.into_iter().next().unwrap();
+    }
+    let mut flat = Vec::with_capacity(list.len());
+    for expr in list {
+        match expr {
+            Expr::All(inner) if is_all => flat.extend(inner),
+            Expr::Any(inner) if !is_all => flat.extend(inner),
+            expr => flat.push(expr),
+        }
+    }
+    if is_all {
+        Expr::All(flat)
+    } else {
+        Expr::Any(flat)
+    }
+}
+
+/// The state of a player that preconditions are checked against
+pub trait PlayerState {
+    /// Whether the player has completed the mission
+    fn has_completed_mission(&self, mission_id: i32) -> bool;
+    /// Whether the player has accepted, but not completed the mission
+    fn is_on_mission(&self, mission_id: i32) -> bool;
+    /// The number of items with this LOT in the inventory
+    fn item_count(&self, lot: i32) -> u32;
+    /// Whether an item with this LOT is equipped
+    fn is_equipped(&self, lot: i32) -> bool;
+    /// Whether the player flag is set
+    fn has_flag(&self, flag_id: i32) -> bool;
+    /// The level of the player
+    fn level(&self) -> i32;
+}
+
+/// The type of a row in `Preconditions`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub enum PreconditionKind {
+    /// One of the targets is equipped
+    ItemEquipped,
+    /// None of the targets are equipped
+    ItemNotEquipped,
+    /// The inventory contains `count` of one of the targets
+    HasItem,
+    /// The inventory doesn't contain `count` of any of the targets
+    DoesNotHaveItem,
+    /// One of the target achievements is completed
+    HasAchievement,
+    /// One of the target missions is accepted
+    OnMission,
+    /// One of the target missions is completed
+    MissionComplete,
+    /// One of the target flags is set
+    HasFlag,
+    /// The level is at least the first target
+    HasLevel,
+    /// Any other type, which can't be evaluated
+    Other(i32),
+}
+
+impl From<i32> for PreconditionKind {
+    fn from(value: i32) -> Self {
+        match value {
+            0 => Self::ItemEquipped,
+            1 => Self::ItemNotEquipped,
+            2 => Self::HasItem,
+            3 => Self::DoesNotHaveItem,
+            4 => Self::HasAchievement,
+            6 => Self::OnMission,
+            7 => Self::MissionComplete,
+            9 => Self::HasFlag,
+            22 => Self::HasLevel,
+            other => Self::Other(other),
+        }
+    }
+}
+
+impl PreconditionKind {
+    /// Whether the targets are object templates (LOTs)
+    pub fn targets_objects(self) -> bool {
+        matches!(
+            self,
+            Self::ItemEquipped | Self::ItemNotEquipped | Self::HasItem | Self::DoesNotHaveItem
+        )
+    }
+
+    /// Whether the targets are missions
+    pub fn targets_missions(self) -> bool {
+        matches!(
+            self,
+            Self::HasAchievement | Self::OnMission | Self::MissionComplete
+        )
+    }
+}
+
+/// Errors when evaluating preconditions
+#[derive(Debug, Clone, PartialEq, Eq, Error, Display)]
+pub enum EvalError {
+    /// Precondition {0} does not exist
+    MissingPrecondition(i32),
+    /// Precondition {id} has the unsupported type {kind}
+    Unsupported {
+        /// The ID of the precondition
+        id: i32,
+        /// The type of the precondition
+        kind: i32,
+    },
+    /// Precondition {0} has no type
+    MissingType(i32),
+}
+
+/// A row of the `Preconditions` table
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct Precondition {
+    /// The ID of the precondition
+    pub id: i32,
+    /// The type from the `type` column
+    pub kind: Option<PreconditionKind>,
+    /// The IDs in the `targetLOT` column
+    pub targets: Vec<i32>,
+    /// The `targetCount` column
+    pub target_count: Option<i32>,
+}
+
+impl Precondition {
+    /// Check the precondition against a player
+    pub fn check<P: PlayerState + ?Sized>(&self, player: &P) -> Result<bool, EvalError> {
+        let count = self.target_count.unwrap_or(1).max(1) as u32;
+        let mut targets = self.targets.iter().copied();
+        let result = match self.kind.ok_or(EvalError::MissingType(self.id))? {
+            PreconditionKind::ItemEquipped => targets.any(|lot| player.is_equipped(lot)),
+            PreconditionKind::ItemNotEquipped => !targets.any(|lot| player.is_equipped(lot)),
+            PreconditionKind::HasItem => targets.any(|lot| player.item_count(lot) >= count),
+            PreconditionKind::DoesNotHaveItem => {
+                !targets.any(|lot| player.item_count(lot) >= count)
+            }
+            PreconditionKind::HasAchievement | PreconditionKind::MissionComplete => {
+                targets.any(|id| player.has_completed_mission(id))
+            }
+            PreconditionKind::OnMission => targets.any(|id| player.is_on_mission(id)),
+            PreconditionKind::HasFlag => targets.any(|id| player.has_flag(id)),
+            PreconditionKind::HasLevel => match self.targets.first() {
+                Some(&level) => player.level() >= level,
+                None => true,
+            },
+            PreconditionKind::Other(kind) => {
+                return Err(EvalError::Unsupported { id: self.id, kind })
+            }
+        };
+        Ok(result)
+    }
+}
+
+/// The rows of the `Preconditions` table
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Preconditions {
+    rows: BTreeMap<i32, Precondition>,
+}
+
+impl Preconditions {
+    /// Load the `Preconditions` table
+    pub fn load(tables: Tables<'_>) -> Result<Self, LoadError> {
+        let t = table(tables, "Preconditions")?;
+        let col_id = column_index::<i32>(t, "id")?;
+        let col_type = column_index::<Option<i32>>(t, "type")?;
+        let col_target_lot = column_index::<Option<String>>(t, "targetLOT")?;
+        let col_target_count = column_index::<Option<i32>>(t, "targetCount")?;
+        let mut rows = BTreeMap::new();
+        for row in t.row_iter() {
+            let id = field_at(t, row, col_id, "id")?;
+            let kind: Option<i32> = field_at(t, row, col_type, "type")?;
+            let target_lot: Option<String> = field_at(t, row, col_target_lot, "targetLOT")?;
+            let precondition = Precondition {
+                id,
+                kind: kind.map(PreconditionKind::from),
+                targets: target_lot.as_deref().map(parse_targets).unwrap_or_default(),
+                target_count: field_at(t, row, col_target_count, "targetCount")?,
+            };
+            rows.insert(id, precondition);
+        }
+        Ok(Self { rows })
+    }
+
+    /// Get the precondition `id`
+    pub fn get(&self, id: i32) -> Option<&Precondition> {
+        self.rows.get(&id)
+    }
+
+    /// Add or replace a precondition
+    pub fn insert(&mut self, precondition: Precondition) {
+        self.rows.insert(precondition.id, precondition);
+    }
+
+    /// Iterate over all preconditions, ordered by ID
+    pub fn iter(&self) -> impl Iterator<Item = &Precondition> {
+        self.rows.values()
+    }
+}
+
+/// Parse the comma separated IDs of `targetLOT`, skipping anything else
+fn parse_targets(s: &str) -> Vec<i32> {
+    s.split([',', ';', '|'])
+        .filter_map(|part| part.trim().parse().ok())
+        .collect()
+}
+
+/// A problem found by [`validate`]
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub enum IssueKind {
+    /// invalid expression: {0}
+    Parse(ParseError),
+    /// mission {0} does not exist
+    MissingMission(i32),
+    /// precondition {0} does not exist
+    MissingPrecondition(i32),
+    /// object {0} does not exist
+    MissingObject(i32),
+}
+
+/// A reference to something that doesn't exist, or an invalid expression
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct Issue {
+    /// The table with the expression
+    pub table: &'static str,
+    /// The ID of the row
+    pub id: i32,
+    /// The column with the expression
+    pub column: &'static str,
+    /// The expression
+    pub value: String,
+    /// What is wrong
+    pub kind: IssueKind,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {} {:?}: {}",
+            self.table, self.id, self.column, self.value, self.kind
+        )
+    }
+}
+
+/// Check the precondition expressions of a database
+///
+/// This checks that
+///
+/// - `Missions.prereqMissionID` parses and references existing missions
+/// - `ItemComponent.reqPrecondition` parses and references existing preconditions
+/// - the targets of `Preconditions` of mission and item types exist
+///
+/// Tables that don't exist are skipped.
+pub fn validate(tables: Tables<'_>) -> Result<Vec<Issue>, LoadError> {
+    let missions = ids(tables, "Missions", "id")?;
+    let objects = ids(tables, "Objects", "id")?;
+    let preconditions = match tables.by_name("Preconditions") {
+        Some(_) => Some(Preconditions::load(tables)?),
+        None => None,
+    };
+    let mut issues = Vec::new();
+
+    if let Some(missions) = &missions {
+        check_expressions(tables, "Missions", "prereqMissionID", &mut issues, |id| {
+            (!missions.contains(&id)).then_some(IssueKind::MissingMission(id))
+        })?;
+    }
+    if let Some(preconditions) = &preconditions {
+        check_expressions(
+            tables,
+            "ItemComponent",
+            "reqPrecondition",
+            &mut issues,
+            |id| {
+                preconditions
+                    .get(id)
+                    .is_none()
+                    .then_some(IssueKind::MissingPrecondition(id))
+            },
+        )?;
+
+        for precondition in preconditions.iter() {
+            let kind = match precondition.kind {
+                Some(kind) => kind,
+                None => continue,
+            };
+            let (known, missing): (_, fn(i32) -> IssueKind) = if kind.targets_missions() {
+                (&missions, IssueKind::MissingMission)
+            } else if kind.targets_objects() {
+                (&objects, IssueKind::MissingObject)
+            } else {
+                continue;
+            };
+            let known = match known {
+                Some(known) => known,
+                None => continue,
+            };
+            for &target in &precondition.targets {
+                if !known.contains(&target) {
+                    issues.push(Issue {
+                        table: "Preconditions",
+                        id: precondition.id,
+                        column: "targetLOT",
+                        value: target.to_string(),
+                        kind: missing(target),
+                    });
+                }
+            }
+        }
+    }
+    Ok(issues)
+}
+
+/// Collect the integer values of a column, or `None` if the table doesn't exist
+fn ids(
+    tables: Tables<'_>,
+    name: &'static str,
+    column: &'static str,
+) -> Result<Option<BTreeSet<i32>>, LoadError> {
+    if tables.by_name(name).is_none() {
+        return Ok(None);
+    }
+    let t = table(tables, name)?;
+    let index = column_index::<i32>(t, column)?;
+    let mut ids = BTreeSet::new();
+    for row in t.row_iter() {
+        ids.insert(field_at(t, row, index, column)?);
+    }
+    Ok(Some(ids))
+}
+
+/// Parse the expressions in a column, and check their IDs with `check`
+fn check_expressions<F: Fn(i32) -> Option<IssueKind>>(
+    tables: Tables<'_>,
+    name: &'static str,
+    column: &'static str,
+    issues: &mut Vec<Issue>,
+    check: F,
+) -> Result<(), LoadError> {
+    if tables.by_name(name).is_none() {
+        return Ok(());
+    }
+    let t = table(tables, name)?;
+    let col_id = column_index::<i32>(t, "id")?;
+    let col_value = column_index::<Option<String>>(t, column)?;
+    for row in t.row_iter() {
+        let value: Option<String> = field_at(t, row, col_value, column)?;
+        let value = match value {
+            Some(value) if !value.trim().is_empty() => value,
+            _ => continue,
+        };
+        let id = field_at(t, row, col_id, "id")?;
+        let issue = |kind| Issue {
+            table: name,
+            id,
+            column,
+            value: value.clone(),
+            kind,
+        };
+        match Expr::parse(&value) {
+            Ok(expr) => {
+                for ref_id in expr.ids() {
+                    if let Some(kind) = check(ref_id) {
+                        issues.push(issue(kind));
+                    }
+                }
+            }
+            Err(e) => issues.push(issue(IssueKind::Parse(e))),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Player {
+        missions: Vec<i32>,
+        items: Vec<(i32, u32)>,
+        level: i32,
+    }
+
+    impl PlayerState for Player {
+        fn has_completed_mission(&self, mission_id: i32) -> bool {
+            self.missions.contains(&mission_id)
+        }
+
+        fn is_on_mission(&self, _mission_id: i32) -> bool {
+            false
+        }
+
+        fn item_count(&self, lot: i32) -> u32 {
+            self.items
+                .iter()
+                .filter(|(l, _)| *l == lot)
+                .map(|(_, count)| count)
+                .sum()
+        }
+
+        fn is_equipped(&self, _lot: i32) -> bool {
+            false
+        }
+
+        fn has_flag(&self, flag_id: i32) -> bool {
+            flag_id == 7
+        }
+
+        fn level(&self) -> i32 {
+            self.level
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        use Expr::*;
+        assert_eq!(Expr::parse(" 42 "), Ok(Id(42)));
+        assert_eq!(
+            Expr::parse("1,2;3&4"),
+            Ok(All(vec![Id(1), Id(2), Id(3), Id(4)]))
+        );
+        assert_eq!(
+            Expr::parse("1|2,3"),
+            Ok(All(vec![Any(vec![Id(1), Id(2)]), Id(3)]))
+        );
+        assert_eq!(
+            Expr::parse("1|(2,(3,4))"),
+            Ok(Any(vec![Id(1), All(vec![Id(2), Id(3), Id(4)])]))
+        );
+        assert_eq!(Expr::parse(""), Err(ParseError::Empty));
+        assert_eq!(Expr::parse("1,"), Err(ParseError::UnexpectedEnd));
+        assert_eq!(Expr::parse("(1"), Err(ParseError::UnexpectedEnd));
+        assert_eq!(
+            Expr::parse("1 2"),
+            Err(ParseError::Unexpected { pos: 2, found: '2' })
+        );
+        assert_eq!(
+            Expr::parse("1|x"),
+            Err(ParseError::Unexpected { pos: 2, found: 'x' })
+        );
+        assert_eq!(
+            Expr::parse("99999999999"),
+            Err(ParseError::Overflow { pos: 0 })
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        for (input, output) in [
+            ("1", "1"),
+            ("1;2&3", "1,2,3"),
+            ("1|2,3", "1|2,3"),
+            ("1|(2,3)", "1|(2,3)"),
+            ("(1,2)|(3,4|5)", "(1,2)|(3,4|5)"),
+        ] {
+            let expr = Expr::parse(input).unwrap();
+            assert_eq!(expr.to_string(), output);
+            assert_eq!(Expr::parse(output).unwrap(), expr);
+        }
+    }
+
+    #[test]
+    fn test_eval() {
+        let player = Player {
+            missions: vec![1, 3],
+            items: vec![(100, 2), (101, 1)],
+            level: 10,
+        };
+        assert!(Expr::parse("1,3").unwrap().eval_missions(&player));
+        assert!(!Expr::parse("1,2").unwrap().eval_missions(&player));
+        assert!(Expr::parse("2|3").unwrap().eval_missions(&player));
+
+        let mut preconditions = Preconditions::default();
+        let mut add = |id, kind, targets: &[i32], target_count| {
+            preconditions.insert(Precondition {
+                id,
+                kind: Some(PreconditionKind::from(kind)),
+                targets: targets.to_vec(),
+                target_count,
+            })
+        };
+        add(10, 2, &[100], Some(2));
+        add(11, 2, &[101], Some(2));
+        add(12, 3, &[102], None);
+        add(13, 9, &[7], None);
+        add(14, 22, &[15], None);
+        add(15, 7, &[2, 3], None);
+        add(16, 10, &[], None);
+
+        let eval = |s: &str| {
+            Expr::parse(s)
+                .unwrap()
+                .eval_preconditions(&preconditions, &player)
+        };
+        assert_eq!(eval("10"), Ok(true));
+        assert_eq!(eval("11"), Ok(false));
+        assert_eq!(eval("10;12;13;15"), Ok(true));
+        assert_eq!(eval("14"), Ok(false));
+        assert_eq!(eval("14|13"), Ok(true));
+        // Evaluation stops at the first operand that decides the result
+        assert_eq!(eval("13|16"), Ok(true));
+        assert_eq!(
+            eval("16|13"),
+            Err(EvalError::Unsupported { id: 16, kind: 10 })
+        );
+        assert_eq!(eval("17"), Err(EvalError::MissingPrecondition(17)));
+    }
+
+    #[test]
+    fn test_parse_targets() {
+        assert_eq!(parse_targets("1727, 1728,"), [1727, 1728]);
+        assert_eq!(parse_targets(""), Vec::<i32>::new());
+    }
+
+    #[test]
+    #[cfg(feature = "fdb-store")]
+    fn test_validate() {
+        use crate::util::tests::{build, TableBuilder};
+        use assembly_fdb::{
+            mem::Database,
+            value::{owned::Field, ValueType},
+        };
+        let text = |s: &str| Field::Text(s.to_owned());
+
+        let mut missions = TableBuilder::new("Missions")
+            .column("id", ValueType::Integer)
+            .column("prereqMissionID", ValueType::Text);
+        missions.row(vec![Field::Integer(1), Field::Nothing]);
+        missions.row(vec![Field::Integer(2), text("1")]);
+        missions.row(vec![Field::Integer(3), text("1|5,2")]);
+        missions.row(vec![Field::Integer(4), text("1||2")]);
+
+        let mut objects = TableBuilder::new("Objects").column("id", ValueType::Integer);
+        objects.row(vec![Field::Integer(1000)]);
+
+        let mut preconditions = TableBuilder::new("Preconditions")
+            .column("id", ValueType::Integer)
+            .column("type", ValueType::Integer)
+            .column("targetLOT", ValueType::Text)
+            .column("targetCount", ValueType::Integer);
+        preconditions.row(vec![
+            Field::Integer(10),
+            Field::Integer(2),
+            text("1000,1001"),
+            Field::Integer(1),
+        ]);
+        preconditions.row(vec![
+            Field::Integer(11),
+            Field::Integer(7),
+            text("3;6"),
+            Field::Nothing,
+        ]);
+        preconditions.row(vec![
+            Field::Integer(12),
+            Field::Integer(9),
+            text("99"),
+            Field::Nothing,
+        ]);
+
+        let mut items = TableBuilder::new("ItemComponent")
+            .column("id", ValueType::Integer)
+            .column("reqPrecondition", ValueType::Text);
+        items.row(vec![Field::Integer(20), text("10;12")]);
+        items.row(vec![Field::Integer(21), text("11|13")]);
+        items.row(vec![Field::Integer(22), text(" ")]);
+
+        let buf = build(vec![missions, objects, preconditions, items]);
+        let issues = validate(Database::new(&buf).tables().unwrap()).unwrap();
+        let issues: Vec<_> = issues
+            .iter()
+            .map(|i| (i.table, i.id, i.kind.clone()))
+            .collect();
+        assert_eq!(
+            issues,
+            [
+                // Rows are checked in the order of the buckets
+                (
+                    "Missions",
+                    4,
+                    IssueKind::Parse(ParseError::Unexpected { pos: 2, found: '|' })
+                ),
+                ("Missions", 3, IssueKind::MissingMission(5)),
+                ("ItemComponent", 21, IssueKind::MissingPrecondition(13)),
+                ("Preconditions", 10, IssueKind::MissingObject(1001)),
+                ("Preconditions", 11, IssueKind::MissingMission(6)),
+            ]
+        );
+    }
+}
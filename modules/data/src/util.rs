@@ -0,0 +1,66 @@
+use assembly_fdb::{
+    mem::{Row, Table, Tables},
+    typed::LoadError,
+};
+
+/// Get the table `name`, or fail with [`LoadError::MissingTable`]
+pub(crate) fn table<'a>(tables: Tables<'a>, name: &'static str) -> Result<Table<'a>, LoadError> {
+    Ok(tables
+        .by_name(name)
+        .ok_or(LoadError::MissingTable(name))??)
+}
+
+/// Get the rows of `table` with the integer primary key `id`
+pub(crate) fn rows_by_id<'a>(table: Table<'a>, id: i32) -> impl Iterator<Item = Row<'a>> {
+    let has_buckets = table.bucket_count() > 0;
+    has_buckets
+        .then(|| table.index_iter(id as u32))
+        .into_iter()
+        .flatten()
+}
+
+#[cfg(all(test, feature = "fdb-store"))]
+pub(crate) mod tests {
+    use assembly_fdb::{
+        store,
+        value::{owned::Field, ValueType},
+    };
+    use latin1str::Latin1String;
+
+    /// A table for a test database
+    pub(crate) struct TableBuilder {
+        name: &'static str,
+        table: store::Table,
+    }
+
+    impl TableBuilder {
+        pub(crate) fn new(name: &'static str) -> Self {
+            Self {
+                name,
+                table: store::Table::new(0),
+            }
+        }
+
+        pub(crate) fn column(mut self, name: &str, value_type: ValueType) -> Self {
+            self.table
+                .push_column(Latin1String::encode(name), value_type);
+            self
+        }
+
+        pub(crate) fn row(&mut self, fields: Vec<Field>) {
+            self.table.push_row_hashed(&fields);
+        }
+    }
+
+    /// Write the tables into a database file
+    pub(crate) fn build(tables: Vec<TableBuilder>) -> Vec<u8> {
+        let mut db = store::Database::new();
+        for mut t in tables {
+            t.table.auto_resize_buckets();
+            db.push_table(Latin1String::encode(t.name), t.table);
+        }
+        let mut buf = Vec::new();
+        db.write(&mut buf).unwrap();
+        buf
+    }
+}
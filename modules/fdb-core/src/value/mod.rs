@@ -222,7 +222,7 @@ impl<T: Context> From<&Value<T>> for ValueType {
 
 /// Value datatypes used in the database
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ValueType {
     /// The NULL value
     Nothing,
@@ -254,6 +254,30 @@ impl ValueType {
         }
     }
 
+    /// Get the type for a name returned by [`ValueType::static_name`]
+    ///
+    /// The name is compared case-insensitively.
+    ///
+    /// ```
+    /// # use assembly_fdb_core::value::ValueType;
+    /// assert_eq!(ValueType::from_static_name("BIGINT"), Some(ValueType::BigInt));
+    /// assert_eq!(ValueType::from_static_name("varchar"), Some(ValueType::VarChar));
+    /// assert_eq!(ValueType::from_static_name("TEXT4"), None);
+    /// ```
+    pub fn from_static_name(name: &str) -> Option<Self> {
+        [
+            ValueType::Nothing,
+            ValueType::Integer,
+            ValueType::Float,
+            ValueType::Text,
+            ValueType::Boolean,
+            ValueType::BigInt,
+            ValueType::VarChar,
+        ]
+        .into_iter()
+        .find(|value_type| value_type.static_name().eq_ignore_ascii_case(name))
+    }
+
     /// Get the canonical SQLite name of this data type
     pub fn to_sqlite_type(self) -> &'static str {
         match self {
@@ -0,0 +1,159 @@
+#![doc(html_logo_url = "https://assembly.lu-dev.net/rust-logo-lu-256.png")]
+#![doc(html_favicon_url = "https://assembly.lu-dev.net/rust-logo-lu-256.png")]
+#![warn(missing_docs)]
+//! # fdb-derive
+//!
+//! This crate implements `#[derive(FdbRow)]` for the `assembly_fdb::typed` module.
+//! Please refer to the documentation of that module for usage information.
+
+use proc_macro::TokenStream;
+use proc_macro2::Span;
+use quote::quote;
+use syn::{
+    parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Fields, GenericParam, Lifetime,
+    LitStr, Result,
+};
+
+/// Implement `assembly_fdb::typed::FdbRow` for a struct with named fields
+///
+/// The struct needs a `#[fdb(table = "...")]` attribute. Every field is loaded
+/// from the column with the same name, unless it has a `#[fdb(column = "...")]`
+/// attribute.
+#[proc_macro_derive(FdbRow, attributes(fdb))]
+pub fn derive_fdb_row(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: DeriveInput) -> Result<proc_macro2::TokenStream> {
+    let ident = &input.ident;
+    let table = table_name(&input)?;
+
+    let fields = match &input.data {
+        Data::Struct(s) => match &s.fields {
+            Fields::Named(named) => &named.named,
+            _ => {
+                return Err(Error::new(
+                    s.fields.span(),
+                    "FdbRow can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                input.span(),
+                "FdbRow can only be derived for structs",
+            ))
+        }
+    };
+
+    let mut lifetimes = input.generics.lifetimes();
+    let lifetime = match (lifetimes.next(), lifetimes.next()) {
+        (None, _) => Lifetime::new("'__fdb", Span::call_site()),
+        (Some(def), None) => def.lifetime.clone(),
+        (Some(_), Some(second)) => {
+            return Err(Error::new(
+                second.span(),
+                "FdbRow supports at most one lifetime parameter",
+            ))
+        }
+    };
+    if let Some(param) = input
+        .generics
+        .params
+        .iter()
+        .find(|p| !matches!(p, GenericParam::Lifetime(_)))
+    {
+        return Err(Error::new(
+            param.span(),
+            "FdbRow does not support type or const parameters",
+        ));
+    }
+    let struct_generics = if input.generics.params.is_empty() {
+        quote!()
+    } else {
+        quote!(<#lifetime>)
+    };
+
+    let count = fields.len();
+    let mut resolve = Vec::with_capacity(count);
+    let mut load = Vec::with_capacity(count);
+    for (index, field) in fields.iter().enumerate() {
+        let field_ident = field.ident.as_ref().unwrap();
+        let ty = &field.ty;
+        let column = column_name(field)?;
+        resolve.push(quote! {
+            ::assembly_fdb::typed::column_index::<#ty>(table, #column)?
+        });
+        load.push(quote! {
+            #field_ident: ::assembly_fdb::typed::field_at(table, row, columns[#index], #column)?
+        });
+    }
+
+    Ok(quote! {
+        impl<#lifetime> ::assembly_fdb::typed::FdbRow<#lifetime> for #ident #struct_generics {
+            const TABLE: &'static str = #table;
+            type Columns = [usize; #count];
+
+            fn resolve_columns(
+                table: ::assembly_fdb::mem::Table<#lifetime>,
+            ) -> ::std::result::Result<Self::Columns, ::assembly_fdb::typed::LoadError> {
+                ::std::result::Result::Ok([#(#resolve),*])
+            }
+
+            fn load_row(
+                table: ::assembly_fdb::mem::Table<#lifetime>,
+                columns: &Self::Columns,
+                row: ::assembly_fdb::mem::Row<#lifetime>,
+            ) -> ::std::result::Result<Self, ::assembly_fdb::typed::LoadError> {
+                ::std::result::Result::Ok(Self {
+                    #(#load),*
+                })
+            }
+        }
+    })
+}
+
+/// Get the value of `#[fdb(table = "...")]`
+fn table_name(input: &DeriveInput) -> Result<LitStr> {
+    let mut table = None;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("fdb")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("table") {
+                table = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown attribute, expected `table`"))
+            }
+        })?;
+    }
+    table.ok_or_else(|| {
+        Error::new(
+            input.ident.span(),
+            "missing table name, add `#[fdb(table = \"...\")]`",
+        )
+    })
+}
+
+/// Get the value of `#[fdb(column = "...")]` or the name of the field
+fn column_name(field: &syn::Field) -> Result<LitStr> {
+    let mut column = None;
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("fdb")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("column") {
+                column = Some(meta.value()?.parse::<LitStr>()?);
+                Ok(())
+            } else {
+                Err(meta.error("unknown attribute, expected `column`"))
+            }
+        })?;
+    }
+    Ok(column.unwrap_or_else(|| {
+        let ident = field.ident.as_ref().unwrap();
+        let name = ident.to_string();
+        let name = name.strip_prefix("r#").unwrap_or(&name);
+        LitStr::new(name, ident.span())
+    }))
+}
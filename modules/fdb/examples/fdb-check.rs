@@ -0,0 +1,42 @@
+use std::{fs::File, path::PathBuf, time::Instant};
+
+use argh::FromArgs;
+use assembly_fdb::validate::validate_buffer;
+use mapr::Mmap;
+
+use color_eyre::eyre::{self, eyre, WrapErr};
+
+#[derive(FromArgs)]
+/// Checks the structure of an FDB file
+struct Options {
+    /// the FDB file
+    #[argh(positional)]
+    file: PathBuf,
+}
+
+fn main() -> eyre::Result<()> {
+    color_eyre::install()?;
+    let opts: Options = argh::from_env();
+    let start = Instant::now();
+
+    let file = File::open(&opts.file)
+        .wrap_err_with(|| format!("Failed to open input file '{}'", opts.file.display()))?;
+    let mmap = unsafe { Mmap::map(&file)? };
+    let buffer: &[u8] = &mmap;
+
+    let report = validate_buffer(buffer);
+    print!("{}", report);
+
+    let duration = start.elapsed();
+    println!(
+        "Finished in {}.{}s",
+        duration.as_secs(),
+        duration.subsec_millis()
+    );
+
+    if report.is_valid() {
+        Ok(())
+    } else {
+        Err(eyre!("Found {} problems", report.issues.len()))
+    }
+}
@@ -0,0 +1,46 @@
+use std::{fs::File, path::PathBuf};
+
+use argh::FromArgs;
+use assembly_fdb::{
+    codegen::{generate, CodegenOptions},
+    mem::Database,
+};
+use color_eyre::eyre::{self, WrapErr};
+use mapr::Mmap;
+
+#[derive(FromArgs)]
+/// Generates Rust structs for the tables of an FDB file
+struct Options {
+    /// the FDB file, or a template created by `template-fdb`
+    #[argh(positional)]
+    src: PathBuf,
+    /// the Rust file to create
+    #[argh(positional)]
+    dest: PathBuf,
+    /// only generate code for these tables
+    #[argh(option)]
+    table: Vec<String>,
+    /// use `Option<T>` for all columns
+    #[argh(switch)]
+    all_nullable: bool,
+}
+
+fn main() -> eyre::Result<()> {
+    color_eyre::install()?;
+    let opts: Options = argh::from_env();
+
+    let src_file = File::open(&opts.src)
+        .wrap_err_with(|| format!("Failed to open input file '{}'", opts.src.display()))?;
+    let mmap = unsafe { Mmap::map(&src_file)? };
+    let buffer: &[u8] = &mmap;
+
+    let tables = &opts.table;
+    let mut options = CodegenOptions::new().all_nullable(opts.all_nullable);
+    if !tables.is_empty() {
+        options = options.filter(|name| tables.iter().any(|t| t == name));
+    }
+    let code = generate(Database::new(buffer), &options)?;
+    std::fs::write(&opts.dest, code)
+        .wrap_err_with(|| format!("Failed to write '{}'", opts.dest.display()))?;
+    Ok(())
+}
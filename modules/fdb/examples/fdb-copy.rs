@@ -1,7 +1,7 @@
-use std::{fs::File, io::BufWriter, path::PathBuf, time::Instant};
+use std::{convert::TryFrom, fs::File, io::BufWriter, path::PathBuf, time::Instant};
 
 use argh::FromArgs;
-use assembly_fdb::{mem, store, value::owned::Field};
+use assembly_fdb::{mem, store};
 use mapr::Mmap;
 
 use color_eyre::eyre::{self, WrapErr};
@@ -15,6 +15,15 @@ struct Options {
     /// the FDB file to create
     #[argh(positional)]
     dest: PathBuf,
+    /// store identical strings and i64 values only once per table
+    #[argh(switch)]
+    intern: bool,
+    /// store identical strings and i64 values only once in the whole file
+    #[argh(switch)]
+    intern_all: bool,
+    /// reproduce the layout of the source file byte by byte
+    #[argh(switch)]
+    exact: bool,
 }
 
 fn main() -> eyre::Result<()> {
@@ -34,35 +43,36 @@ fn main() -> eyre::Result<()> {
     println!("Copying file, this may take a few seconds...");
 
     let src_db = mem::Database::new(buffer);
-    let mut dest_db = store::Database::new();
-
-    for src_table in src_db.tables()?.iter() {
-        let src_table = src_table?;
-
-        let mut dest_table = store::Table::new(src_table.bucket_count());
-
-        for src_column in src_table.column_iter() {
-            dest_table.push_column(src_column.name_raw(), src_column.value_type());
-        }
-
-        let mut row_buffer: Vec<Field> = Vec::with_capacity(src_table.column_count());
-
-        for (pk, src_bucket) in src_table.bucket_iter().enumerate() {
-            for src_row in src_bucket.row_iter() {
-                for field in src_row.field_iter() {
-                    row_buffer.push(field.map(&mut mem::MemToOwned));
-                }
-                dest_table.push_row(pk, &row_buffer[..]);
-                row_buffer.clear();
-            }
+    let layout = if opts.exact {
+        if opts.intern || opts.intern_all {
+            eyre::bail!("--exact can't be combined with --intern or --intern-all");
         }
-
-        dest_db.push_table(src_table.name_raw(), dest_table);
+        Some(store::Layout::capture(src_db)?)
+    } else {
+        None
+    };
+    let mut dest_db = store::Database::try_from(src_db)?;
+    if opts.intern_all {
+        dest_db.set_interning(store::Interning::Database);
+    } else if opts.intern {
+        dest_db.set_interning(store::Interning::Table);
     }
 
-    dest_db
-        .write(&mut dest_out)
-        .wrap_err("Failed to write copied database")?;
+    if let Some(layout) = &layout {
+        dest_db
+            .write_with_layout(layout, &mut dest_out)
+            .wrap_err("Failed to write copied database")?;
+    } else {
+        dest_db
+            .write(&mut dest_out)
+            .wrap_err("Failed to write copied database")?;
+
+        println!(
+            "Wrote {} bytes (input: {} bytes)",
+            dest_db.compute_size(),
+            buffer.len()
+        );
+    }
 
     let duration = start.elapsed();
     println!(
@@ -1,10 +1,10 @@
 use std::{fs::File, path::PathBuf, time::Instant};
 
 use argh::FromArgs;
-use assembly_fdb::mem;
+use assembly_fdb::{diff, mem, validate::validate_buffer};
 use mapr::Mmap;
 
-use color_eyre::eyre::{self, WrapErr};
+use color_eyre::eyre::{self, eyre, WrapErr};
 
 #[derive(FromArgs)]
 /// Finds differences in FDB files
@@ -15,6 +15,9 @@ struct Options {
     /// the 'right' FDB file
     #[argh(positional)]
     right: PathBuf,
+    /// print the changes as JSON
+    #[argh(switch)]
+    json: bool,
 }
 
 fn main() -> eyre::Result<()> {
@@ -34,15 +37,24 @@ fn main() -> eyre::Result<()> {
     let right_mmap = unsafe { Mmap::map(&right_file)? };
     let right_buffer: &[u8] = &right_mmap;
 
+    for (path, buffer) in [(&opts.left, left_buffer), (&opts.right, right_buffer)] {
+        let report = validate_buffer(buffer);
+        if !report.is_valid() {
+            eprint!("{}", report);
+            return Err(eyre!("'{}' is not a valid FDB file", path.display()));
+        }
+    }
+
     let left_db = mem::Database::new(left_buffer);
     let right_db = mem::Database::new(right_buffer);
 
-    // FIXME: check whether the DB is sorted correctly
-
-    let mut _left_table_iter = left_db.tables()?.iter();
-    let mut _right_table_iter = right_db.tables()?.iter();
-
-    // FIXME: write diff tool
+    let changes = diff::diff_databases(left_db, right_db)?;
+    if opts.json {
+        serde_json::to_writer_pretty(std::io::stdout(), &changes)?;
+        println!();
+    } else {
+        print!("{}", changes);
+    }
 
     let duration = start.elapsed();
     println!(
@@ -0,0 +1,102 @@
+use std::{fs::File, io::BufWriter, path::PathBuf, time::Instant};
+
+use argh::FromArgs;
+use assembly_fdb::{
+    mem,
+    merge::{ConflictPolicy, MergeOptions, Merger, SchemaPolicy},
+};
+use color_eyre::eyre::{self, eyre, WrapErr};
+use mapr::Mmap;
+
+#[derive(FromArgs)]
+/// Combines a base FDB file with one or more overlays
+struct Options {
+    /// the base FDB file, followed by the overlays
+    #[argh(positional)]
+    layers: Vec<PathBuf>,
+    /// the FDB file to create
+    #[argh(option, short = 'o')]
+    out: PathBuf,
+    /// what to do with existing keys: overlay, base, error or append (default: overlay)
+    #[argh(option, default = "String::from(\"overlay\")")]
+    policy: String,
+    /// the policy for a single table, as `<table>=<policy>`
+    #[argh(option)]
+    table: Vec<String>,
+    /// fail if an overlay has different columns
+    #[argh(switch)]
+    strict: bool,
+    /// write a JSON report of where every row came from
+    #[argh(option)]
+    report: Option<PathBuf>,
+}
+
+fn parse_policy(policy: &str) -> eyre::Result<ConflictPolicy> {
+    match policy {
+        "overlay" => Ok(ConflictPolicy::OverlayWins),
+        "base" => Ok(ConflictPolicy::BaseWins),
+        "error" => Ok(ConflictPolicy::Error),
+        "append" => Ok(ConflictPolicy::Append),
+        _ => Err(eyre!("Unknown policy '{}'", policy)),
+    }
+}
+
+fn main() -> eyre::Result<()> {
+    color_eyre::install()?;
+    let opts: Options = argh::from_env();
+    let start = Instant::now();
+
+    let mut options = MergeOptions::new().conflicts(parse_policy(&opts.policy)?);
+    for entry in &opts.table {
+        let (name, policy) = entry
+            .split_once('=')
+            .ok_or_else(|| eyre!("Expected `<table>=<policy>`, found '{}'", entry))?;
+        options = options.table(name, parse_policy(policy)?);
+    }
+    if opts.strict {
+        options = options.schema(SchemaPolicy::Strict);
+    }
+
+    let mut merger = Merger::new(options);
+    for path in &opts.layers {
+        let file = File::open(path)
+            .wrap_err_with(|| format!("Failed to open input file '{}'", path.display()))?;
+        let mmap = unsafe { Mmap::map(&file)? };
+        let name = path.display().to_string();
+        merger.add(&name, mem::Database::new(&mmap))?;
+    }
+    let (db, report) = merger.finish();
+
+    let out = File::create(&opts.out)
+        .wrap_err_with(|| format!("Failed to create output file '{}'", opts.out.display()))?;
+    db.write(&mut BufWriter::new(out))
+        .wrap_err("Failed to write merged database")?;
+
+    for table in &report.tables {
+        let counts: Vec<String> = (0..report.layers.len())
+            .map(|layer| table.rows_from(layer).to_string())
+            .collect();
+        println!(
+            "{}: {} rows ({}), {} replaced, {} skipped, {} columns added",
+            table.name,
+            table.rows.len(),
+            counts.join(" + "),
+            table.replaced.len(),
+            table.skipped.len(),
+            table.added_columns.len()
+        );
+    }
+    if let Some(path) = &opts.report {
+        let file = File::create(path)
+            .wrap_err_with(|| format!("Failed to create report '{}'", path.display()))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &report)?;
+    }
+
+    let duration = start.elapsed();
+    println!(
+        "Finished in {}.{}s",
+        duration.as_secs(),
+        duration.subsec_millis()
+    );
+    Ok(())
+}
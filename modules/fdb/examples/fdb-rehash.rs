@@ -0,0 +1,88 @@
+use std::{fs::File, io::BufWriter, num::NonZeroUsize, path::PathBuf, time::Instant};
+
+use argh::FromArgs;
+use assembly_fdb::{
+    mem,
+    rehash::{rehash_database, same_lookups, BucketCount, RehashOptions},
+};
+use color_eyre::eyre::{self, eyre, WrapErr};
+use mapr::Mmap;
+
+#[derive(FromArgs)]
+/// Rewrites an FDB file with a new number of buckets in every table
+struct Options {
+    /// the FDB file to read
+    #[argh(positional)]
+    src: PathBuf,
+    /// the FDB file to create
+    #[argh(positional)]
+    dest: PathBuf,
+    /// the number of buckets for all tables, default: the next power of two of the number of keys
+    #[argh(option)]
+    buckets: Option<NonZeroUsize>,
+    /// the number of buckets for a single table, as `<table>=<count>`
+    #[argh(option)]
+    table: Vec<String>,
+    /// check that all lookups give the same rows in the new file
+    #[argh(switch)]
+    verify: bool,
+}
+
+fn main() -> eyre::Result<()> {
+    color_eyre::install()?;
+    let opts: Options = argh::from_env();
+    let start = Instant::now();
+
+    let mut options = RehashOptions::new();
+    if let Some(count) = opts.buckets {
+        options = options.bucket_count(BucketCount::Fixed(count.get()));
+    }
+    for entry in &opts.table {
+        let (name, count) = entry
+            .split_once('=')
+            .ok_or_else(|| eyre!("Expected `<table>=<count>`, found '{}'", entry))?;
+        let count: NonZeroUsize = count
+            .parse()
+            .wrap_err_with(|| format!("Invalid bucket count for '{}'", name))?;
+        options = options.table(name, BucketCount::Fixed(count.get()));
+    }
+
+    let src_file = File::open(&opts.src)
+        .wrap_err_with(|| format!("Failed to open input file '{}'", opts.src.display()))?;
+    let mmap = unsafe { Mmap::map(&src_file)? };
+    let buffer: &[u8] = &mmap;
+
+    let src_db = mem::Database::new(buffer);
+    let dest_db = rehash_database(src_db, &options)?;
+
+    let mut out = Vec::new();
+    dest_db
+        .write(&mut out)
+        .wrap_err("Failed to write database")?;
+
+    if opts.verify {
+        let dest_tables = mem::Database::new(&out).tables()?;
+        for table in src_db.tables()?.iter() {
+            let table = table?;
+            let copy = dest_tables
+                .by_name(&table.name())
+                .ok_or_else(|| eyre!("Missing table '{}'", table.name()))??;
+            if !same_lookups(table, copy) {
+                eyre::bail!("Lookups in table '{}' changed", table.name());
+            }
+        }
+        println!("Verified all lookups");
+    }
+
+    let dest_file = File::create(&opts.dest)
+        .wrap_err_with(|| format!("Failed to create output file '{}'", opts.dest.display()))?;
+    std::io::copy(&mut &out[..], &mut BufWriter::new(dest_file))?;
+
+    let duration = start.elapsed();
+    println!(
+        "Finished in {}.{}s",
+        duration.as_secs(),
+        duration.subsec_millis()
+    );
+    Ok(())
+}
@@ -1,5 +1,9 @@
 use argh::FromArgs;
-use assembly_fdb::mem::{Database, Tables};
+use assembly_fdb::{
+    index::Key,
+    mem::{Database, Tables},
+    profile::{profile_database, ColumnProfile},
+};
 use assembly_fdb_core::value::{Value, ValueType};
 use mapr::Mmap;
 use std::{fs::File, path::PathBuf, time::Instant};
@@ -10,6 +14,38 @@ struct Options {
     /// the FDB file
     #[argh(positional)]
     file: PathBuf,
+    /// print a profile of every table and column
+    #[argh(switch)]
+    columns: bool,
+    /// print the profile as JSON
+    #[argh(switch)]
+    json: bool,
+}
+
+fn number(key: &Key) -> String {
+    match key {
+        Key::Integer(i) => i.to_string(),
+        Key::Float(f) => f.to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+fn print_column(column: &ColumnProfile) {
+    let distinct = if column.distinct_exact { "" } else { "~" };
+    print!(
+        "  {} ({}): {} null, {}{} distinct",
+        column.name, column.value_type, column.null_count, distinct, column.distinct_count
+    );
+    if let (Some(min), Some(max)) = (&column.min, &column.max) {
+        print!(", range {}..={}", number(min), number(max));
+    }
+    if let Some(len) = column.max_string_len {
+        print!(", longest string {}", len);
+    }
+    if column.type_mismatches > 0 {
+        print!(", {} fields with the wrong type", column.type_mismatches);
+    }
+    println!();
 }
 
 fn main() -> color_eyre::Result<()> {
@@ -23,6 +59,27 @@ fn main() -> color_eyre::Result<()> {
     println!("Scanning database, this may take a while...");
 
     let db = Database::new(buffer);
+
+    if opts.json || opts.columns {
+        let profile = profile_database(db)?;
+        if opts.json {
+            println!("{}", serde_json::to_string_pretty(&profile)?);
+            return Ok(());
+        }
+        for table in &profile.tables {
+            println!();
+            println!("# {}", table.name);
+            println!(
+                "Rows: {}, buckets: {} ({} empty, longest chain {})",
+                table.row_count,
+                table.buckets.bucket_count,
+                table.buckets.empty,
+                table.buckets.longest_chain
+            );
+            table.columns.iter().for_each(print_column);
+        }
+    }
+
     let tables: Tables<'_> = db.tables()?;
 
     let table_count = tables.len();
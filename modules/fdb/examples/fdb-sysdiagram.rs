@@ -1,9 +1,7 @@
-use assembly_fdb::{
-    mem::{Database, Table},
-    value::Value,
-};
+use assembly_fdb::mem::{Database, Table};
 use base64::{engine::general_purpose::STANDARD, read::DecoderReader};
 use color_eyre::eyre::{eyre, Context};
+use latin1str::Latin1Str;
 use mapr::Mmap;
 use std::{fs::File, io::Cursor};
 use std::{io::BufWriter, path::PathBuf};
@@ -37,18 +35,14 @@ fn load_database(opts: &Options) -> color_eyre::Result<()> {
     let table: Table = table.context("Failed to load table 'sysdiagrams'")?;
 
     if let Some(row) = table.row_iter().next() {
-        match row.field_at(4) {
-            Some(Value::Text(text)) => {
-                let mut wrapped_reader = Cursor::new(text.as_bytes());
-                let mut decoder = DecoderReader::new(&mut wrapped_reader, &STANDARD);
-                let file = File::create(&opts.output)?;
-                let mut writer = BufWriter::new(file);
-                print!("Writing {} ...", opts.output.display());
-                std::io::copy(&mut decoder, &mut writer)?;
-                println!("Done!");
-            }
-            data => println!("Wrong data: {:?}", data),
-        }
+        let text: &Latin1Str = row.get_by_name(table, "definition")?;
+        let mut wrapped_reader = Cursor::new(text.as_bytes());
+        let mut decoder = DecoderReader::new(&mut wrapped_reader, &STANDARD);
+        let file = File::create(&opts.output)?;
+        let mut writer = BufWriter::new(file);
+        print!("Writing {} ...", opts.output.display());
+        std::io::copy(&mut decoder, &mut writer)?;
+        println!("Done!");
     } else {
         eprintln!("No sysdiagram found in table");
     }
@@ -0,0 +1,113 @@
+use std::{
+    convert::TryFrom,
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::{Path, PathBuf},
+};
+
+use argh::FromArgs;
+use assembly_fdb::{mem, store, text};
+use color_eyre::eyre::{self, eyre, WrapErr};
+use latin1str::Latin1String;
+use mapr::Mmap;
+
+#[derive(FromArgs)]
+/// Convert a single table to and from CSV or NDJSON, depending on the file extension
+struct Options {
+    #[argh(subcommand)]
+    command: Command,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand)]
+enum Command {
+    Export(Export),
+    Import(Import),
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "export")]
+/// Write a table to a `.csv` or `.ndjson` file
+struct Export {
+    /// the FDB file
+    #[argh(positional)]
+    fdb: PathBuf,
+    /// the name of the table
+    #[argh(positional)]
+    table: String,
+    /// the file to create
+    #[argh(positional)]
+    dest: PathBuf,
+}
+
+#[derive(FromArgs)]
+#[argh(subcommand, name = "import")]
+/// Replace a table with the content of a `.csv` or `.ndjson` file
+struct Import {
+    /// the FDB file
+    #[argh(positional)]
+    fdb: PathBuf,
+    /// the name of the table
+    #[argh(positional)]
+    table: String,
+    /// the file to read
+    #[argh(positional)]
+    src: PathBuf,
+    /// the FDB file to create
+    #[argh(positional)]
+    dest: PathBuf,
+}
+
+fn is_csv(path: &Path) -> eyre::Result<bool> {
+    match path.extension().and_then(|e| e.to_str()) {
+        Some("csv") => Ok(true),
+        Some("ndjson") | Some("jsonl") => Ok(false),
+        _ => Err(eyre!("Unknown file type '{}'", path.display())),
+    }
+}
+
+fn map(path: &Path) -> eyre::Result<Mmap> {
+    let file = File::open(path)
+        .wrap_err_with(|| format!("Failed to open input file '{}'", path.display()))?;
+    Ok(unsafe { Mmap::map(&file)? })
+}
+
+fn main() -> eyre::Result<()> {
+    color_eyre::install()?;
+    let opts: Options = argh::from_env();
+
+    match opts.command {
+        Command::Export(opts) => {
+            let mmap = map(&opts.fdb)?;
+            let db = mem::Database::new(&mmap);
+            let table = db
+                .tables()?
+                .by_name(&opts.table)
+                .ok_or_else(|| eyre!("Table '{}' not found", opts.table))??;
+
+            let out = BufWriter::new(File::create(&opts.dest)?);
+            if is_csv(&opts.dest)? {
+                text::csv::write_table(table, out)?;
+            } else {
+                text::ndjson::write_table(table, out)?;
+            }
+        }
+        Command::Import(opts) => {
+            let mmap = map(&opts.fdb)?;
+            let mut db = store::Database::try_from(mem::Database::new(&mmap))?;
+
+            let input = BufReader::new(File::open(&opts.src)?);
+            let table = if is_csv(&opts.src)? {
+                text::csv::read_table(input)
+            } else {
+                text::ndjson::read_table(input)
+            }
+            .wrap_err_with(|| format!("Failed to read '{}'", opts.src.display()))?;
+            db.push_table(Latin1String::encode(&opts.table), table);
+
+            let mut out = BufWriter::new(File::create(&opts.dest)?);
+            db.write(&mut out)?;
+        }
+    }
+    Ok(())
+}
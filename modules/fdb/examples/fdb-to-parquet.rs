@@ -0,0 +1,40 @@
+use std::{fs::File, path::PathBuf, time::Instant};
+
+use argh::FromArgs;
+use assembly_fdb::{arrow::write_parquet_dir, mem::Database};
+use color_eyre::eyre::WrapErr;
+use mapr::Mmap;
+
+#[derive(FromArgs)]
+/// Write every table of an FDB file to a Parquet file in a directory
+struct Options {
+    /// the FDB source file
+    #[argh(positional)]
+    src: PathBuf,
+    /// the destination directory
+    #[argh(positional)]
+    dest: PathBuf,
+}
+
+fn main() -> color_eyre::Result<()> {
+    color_eyre::install()?;
+    let opts: Options = argh::from_env();
+    let start = Instant::now();
+
+    let src_file = File::open(&opts.src)
+        .wrap_err_with(|| format!("Failed to open input file '{}'", opts.src.display()))?;
+    let mmap = unsafe { Mmap::map(&src_file)? };
+    let buffer: &[u8] = &mmap;
+
+    println!("Writing tables, this may take a few seconds...");
+    write_parquet_dir(Database::new(buffer), &opts.dest)
+        .wrap_err("Failed to export database to parquet")?;
+
+    let duration = start.elapsed();
+    println!(
+        "Finished in {}.{:03}s",
+        duration.as_secs(),
+        duration.subsec_millis()
+    );
+    Ok(())
+}
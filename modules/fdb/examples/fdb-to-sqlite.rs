@@ -1,7 +1,10 @@
 use std::{fs::File, path::PathBuf, time::Instant};
 
 use argh::FromArgs;
-use assembly_fdb::{mem::Database, sqlite::try_export_db};
+use assembly_fdb::{
+    mem::Database,
+    sqlite::{try_export_db_with, ExportOptions},
+};
 use color_eyre::eyre::WrapErr;
 use mapr::Mmap;
 use rusqlite::Connection;
@@ -18,6 +21,9 @@ struct Options {
     /// optional SQL schema to be used instead of FDB schema
     #[argh(option)]
     schema: Option<PathBuf>,
+    /// record column types and bucket counts and index the primary keys
+    #[argh(switch)]
+    lossless: bool,
 }
 
 fn main() -> color_eyre::Result<()> {
@@ -41,7 +47,12 @@ fn main() -> color_eyre::Result<()> {
     }
 
     conn.execute_batch("PRAGMA foreign_keys = off")?;
-    try_export_db(&mut conn, db).wrap_err("Failed to export database to sqlite")?;
+    let options = if opts.lossless {
+        ExportOptions::lossless()
+    } else {
+        ExportOptions::new()
+    };
+    try_export_db_with(&mut conn, db, &options).wrap_err("Failed to export database to sqlite")?;
     conn.execute_batch("PRAGMA foreign_keys = on")?;
 
     let duration = start.elapsed();
@@ -68,26 +68,10 @@ fn main() -> color_eyre::Result<()> {
     let src_file = File::open(&opts.src)
         .wrap_err_with(|| format!("Failed to open input file '{}'", opts.src.display()))?;
     let mmap = unsafe { Mmap::map(&src_file)? };
-    let mmap: &'static Mmap = Box::leak(Box::new(mmap));
-    let buffer: &'static [u8] = mmap;
 
-    let db = mem::Database::new(buffer);
+    let db = mem::ArcDatabase::new(mmap);
     let conn = Connection::open_in_memory()?;
-    sqlite::load_module(&conn, db)?;
-
-    for table in db.tables()?.iter() {
-        let table = table?;
-        let name = table.name();
-
-        if name == "DBExclude" {
-            println!("Skipping {:?}", name); // FIXME
-            continue;
-        }
-
-        let sql = format!("CREATE VIRTUAL TABLE {} USING fdb;", name);
-        //println!("Running: {}", sql);
-        conn.execute(&sql, [])?;
-    }
+    sqlite::load_all_tables(&conn, db)?;
 
     let mut rl = rustyline::Editor::<()>::new();
 
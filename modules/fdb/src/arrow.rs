@@ -0,0 +1,354 @@
+//! # Apache Arrow and Parquet
+//!
+//! This module converts tables to Arrow [`RecordBatch`]es, so that they can be
+//! used with dataframe libraries without going through SQLite. The column types
+//! are mapped as follows:
+//!
+//! | [`ValueType`] | [`DataType`] |
+//! |---------------|--------------|
+//! | `Nothing`     | `Null`       |
+//! | `Integer`     | `Int32`      |
+//! | `Float`       | `Float32`    |
+//! | `Text`        | `Utf8`       |
+//! | `Boolean`     | `Boolean`    |
+//! | `BigInt`      | `Int64`      |
+//! | `VarChar`     | `Utf8`       |
+//!
+//! Strings are decoded from Latin-1 and `NULL` fields become nulls. The original
+//! type of every column is kept in the field metadata under [`VALUE_TYPE_KEY`].
+
+use std::{collections::HashMap, fs::File, io, path::Path, sync::Arc};
+
+use arrow_array::{
+    builder::{BooleanBuilder, Float32Builder, Int32Builder, Int64Builder, StringBuilder},
+    ArrayRef, NullArray, RecordBatch, RecordBatchOptions,
+};
+use arrow_schema::{ArrowError, DataType, Field as ArrowField, Schema};
+use assembly_core::buffer::CastError;
+use assembly_fdb_core::value::ValueType;
+use displaydoc::Display;
+use parquet::{arrow::ArrowWriter, errors::ParquetError};
+use thiserror::Error;
+
+use crate::mem::{Database, Field, Table};
+
+/// The key of the field metadata that contains the name of the [`ValueType`]
+pub const VALUE_TYPE_KEY: &str = "fdb:value_type";
+
+/// Error when converting a table to Arrow or Parquet
+#[derive(Debug, Display, Error)]
+pub enum ArrowExportError {
+    /// Failed to load the database: {0}
+    Cast(#[from] CastError),
+    /// Arrow error: {0}
+    Arrow(#[from] ArrowError),
+    /// Parquet error: {0}
+    Parquet(#[from] ParquetError),
+    /// I/O error: {0}
+    Io(#[from] io::Error),
+    /// Table {table:?}, column {column:?}: field {row} has type {found}, expected {expected}
+    Mismatch {
+        /// The name of the table
+        table: String,
+        /// The name of the column
+        column: String,
+        /// The index of the row, in the order of [`Table::row_iter`]
+        row: usize,
+        /// The type of the column
+        expected: ValueType,
+        /// The type of the field
+        found: ValueType,
+    },
+    /// Table {table:?}: row {row} has {actual} fields, expected {expected}
+    FieldCount {
+        /// The name of the table
+        table: String,
+        /// The index of the row, in the order of [`Table::row_iter`]
+        row: usize,
+        /// The number of columns
+        expected: usize,
+        /// The number of fields in the row
+        actual: usize,
+    },
+}
+
+/// Get the Arrow type for a [`ValueType`]
+pub fn data_type(value_type: ValueType) -> DataType {
+    match value_type {
+        ValueType::Nothing => DataType::Null,
+        ValueType::Integer => DataType::Int32,
+        ValueType::Float => DataType::Float32,
+        ValueType::Text | ValueType::VarChar => DataType::Utf8,
+        ValueType::Boolean => DataType::Boolean,
+        ValueType::BigInt => DataType::Int64,
+    }
+}
+
+/// Get the Arrow schema of a table
+///
+/// All fields are nullable.
+pub fn table_schema(table: Table<'_>) -> Schema {
+    let fields: Vec<ArrowField> = table
+        .column_iter()
+        .map(|column| {
+            let value_type = column.value_type();
+            let metadata = HashMap::from([(
+                VALUE_TYPE_KEY.to_owned(),
+                value_type.static_name().to_owned(),
+            )]);
+            ArrowField::new(column.name(), data_type(value_type), true).with_metadata(metadata)
+        })
+        .collect();
+    Schema::new(fields)
+}
+
+/// Collects the fields of one column
+enum ColumnBuilder {
+    Null(usize),
+    Int32(Int32Builder),
+    Float32(Float32Builder),
+    Utf8(StringBuilder),
+    Boolean(BooleanBuilder),
+    Int64(Int64Builder),
+}
+
+impl ColumnBuilder {
+    fn new(value_type: ValueType) -> Self {
+        match value_type {
+            ValueType::Nothing => Self::Null(0),
+            ValueType::Integer => Self::Int32(Int32Builder::new()),
+            ValueType::Float => Self::Float32(Float32Builder::new()),
+            ValueType::Text | ValueType::VarChar => Self::Utf8(StringBuilder::new()),
+            ValueType::Boolean => Self::Boolean(BooleanBuilder::new()),
+            ValueType::BigInt => Self::Int64(Int64Builder::new()),
+        }
+    }
+
+    /// Append a field, returns `false` if it has the wrong type
+    fn append(&mut self, field: Field<'_>) -> bool {
+        match (self, field) {
+            (Self::Null(len), Field::Nothing) => *len += 1,
+            (Self::Null(_), _) => return false,
+            (Self::Int32(b), Field::Nothing) => b.append_null(),
+            (Self::Int32(b), Field::Integer(i)) => b.append_value(i),
+            (Self::Float32(b), Field::Nothing) => b.append_null(),
+            (Self::Float32(b), Field::Float(f)) => b.append_value(f),
+            (Self::Utf8(b), Field::Nothing) => b.append_null(),
+            (Self::Utf8(b), Field::Text(s) | Field::VarChar(s)) => b.append_value(s.decode()),
+            (Self::Boolean(b), Field::Nothing) => b.append_null(),
+            (Self::Boolean(b), Field::Boolean(v)) => b.append_value(v),
+            (Self::Int64(b), Field::Nothing) => b.append_null(),
+            (Self::Int64(b), Field::BigInt(i)) => b.append_value(i),
+            _ => return false,
+        }
+        true
+    }
+
+    fn finish(self) -> ArrayRef {
+        match self {
+            Self::Null(len) => Arc::new(NullArray::new(len)),
+            Self::Int32(mut b) => Arc::new(b.finish()),
+            Self::Float32(mut b) => Arc::new(b.finish()),
+            Self::Utf8(mut b) => Arc::new(b.finish()),
+            Self::Boolean(mut b) => Arc::new(b.finish()),
+            Self::Int64(mut b) => Arc::new(b.finish()),
+        }
+    }
+}
+
+/// Convert a table to a record batch
+///
+/// The rows are in the order of [`Table::row_iter`]. This fails if a row
+/// doesn't have one field per column, or a field is neither `NULL` nor of the
+/// type of its column.
+pub fn to_record_batch(table: Table<'_>) -> Result<RecordBatch, ArrowExportError> {
+    let mut builders: Vec<ColumnBuilder> = table
+        .column_iter()
+        .map(|column| ColumnBuilder::new(column.value_type()))
+        .collect();
+
+    let mut row_count = 0;
+    for (index, row) in table.row_iter().enumerate() {
+        if row.field_count() != table.column_count() {
+            return Err(ArrowExportError::FieldCount {
+                table: table.name().into_owned(),
+                row: index,
+                expected: table.column_count(),
+                actual: row.field_count(),
+            });
+        }
+        for ((builder, field), column) in builders
+            .iter_mut()
+            .zip(row.field_iter())
+            .zip(table.column_iter())
+        {
+            if !builder.append(field) {
+                return Err(ArrowExportError::Mismatch {
+                    table: table.name().into_owned(),
+                    column: column.name().into_owned(),
+                    row: index,
+                    expected: column.value_type(),
+                    found: ValueType::from(&field),
+                });
+            }
+        }
+        row_count += 1;
+    }
+
+    let schema = Arc::new(table_schema(table));
+    let columns = builders.into_iter().map(ColumnBuilder::finish).collect();
+    let options = RecordBatchOptions::new().with_row_count(Some(row_count));
+    Ok(RecordBatch::try_new_with_options(
+        schema, columns, &options,
+    )?)
+}
+
+/// Write every table of a database to `<dir>/<table>.parquet`
+///
+/// The directory is created if it doesn't exist. Tables without columns are
+/// skipped, because Parquet files need at least one column.
+pub fn write_parquet_dir(db: Database<'_>, dir: &Path) -> Result<(), ArrowExportError> {
+    std::fs::create_dir_all(dir)?;
+    for table in db.tables()?.iter() {
+        let table = table?;
+        if table.column_count() == 0 {
+            continue;
+        }
+        let batch = to_record_batch(table)?;
+        let file = File::create(dir.join(format!("{}.parquet", table.name())))?;
+        let mut writer = ArrowWriter::try_new(file, batch.schema(), None)?;
+        writer.write(&batch)?;
+        writer.close()?;
+    }
+    Ok(())
+}
+
+#[cfg(all(test, feature = "store"))]
+mod tests {
+    use super::*;
+    use crate::{
+        util::tests::{build, TableBuilder},
+        value::owned,
+    };
+    use arrow_array::{Array, BooleanArray, Float32Array, Int32Array, Int64Array, StringArray};
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    fn database() -> Vec<u8> {
+        let mut table = TableBuilder::new("Items").buckets(1);
+        for (name, value_type) in [
+            ("id", ValueType::Integer),
+            ("name", ValueType::Text),
+            ("chance", ValueType::Float),
+            ("flag", ValueType::Boolean),
+            ("big", ValueType::BigInt),
+            ("xml", ValueType::VarChar),
+            ("none", ValueType::Nothing),
+        ] {
+            table = table.column(name, value_type);
+        }
+        table.row(vec![
+            owned::Field::Integer(1),
+            owned::Field::Text(String::from("Grüße")),
+            owned::Field::Float(0.5),
+            owned::Field::Boolean(true),
+            owned::Field::BigInt(1 << 40),
+            owned::Field::Nothing,
+            owned::Field::Nothing,
+        ]);
+        table.row(vec![
+            owned::Field::Integer(2),
+            owned::Field::Nothing,
+            owned::Field::Nothing,
+            owned::Field::Boolean(false),
+            owned::Field::Nothing,
+            owned::Field::VarChar(String::from("<a/>")),
+            owned::Field::Nothing,
+        ]);
+        build(vec![table, TableBuilder::new("Empty")])
+    }
+
+    fn check(batch: &RecordBatch) {
+        assert_eq!(batch.num_rows(), 2);
+        let column = |i: usize| batch.column(i).as_any();
+        let ids = column(0).downcast_ref::<Int32Array>().unwrap();
+        assert_eq!(ids.values(), &[1, 2]);
+        let names = column(1).downcast_ref::<StringArray>().unwrap();
+        assert_eq!(names.value(0), "Grüße");
+        assert!(names.is_null(1));
+        let chances = column(2).downcast_ref::<Float32Array>().unwrap();
+        assert_eq!(chances.value(0), 0.5);
+        assert!(chances.is_null(1));
+        let flags = column(3).downcast_ref::<BooleanArray>().unwrap();
+        assert!(flags.value(0) && !flags.value(1));
+        let bigs = column(4).downcast_ref::<Int64Array>().unwrap();
+        assert_eq!(bigs.value(0), 1 << 40);
+        let xml = column(5).downcast_ref::<StringArray>().unwrap();
+        assert_eq!(xml.value(1), "<a/>");
+        assert_eq!(batch.column(6).len(), 2);
+        assert_eq!(batch.column(6).data_type(), &DataType::Null);
+    }
+
+    #[test]
+    fn test_record_batch() {
+        let buf = database();
+        let table = Database::new(&buf)
+            .tables()
+            .unwrap()
+            .by_name("Items")
+            .unwrap()
+            .unwrap();
+        let batch = to_record_batch(table).unwrap();
+        check(&batch);
+        let schema = batch.schema();
+        let xml = schema.field(5);
+        assert_eq!(xml.data_type(), &DataType::Utf8);
+        assert_eq!(
+            xml.metadata().get(VALUE_TYPE_KEY).map(String::as_str),
+            Some("VARCHAR")
+        );
+    }
+
+    #[test]
+    fn test_short_row() {
+        let mut table = TableBuilder::new("Short")
+            .column("id", ValueType::Integer)
+            .column("name", ValueType::Text);
+        table.row(vec![owned::Field::Integer(1)]);
+        let buf = build(vec![table]);
+
+        let table = Database::new(&buf)
+            .tables()
+            .unwrap()
+            .by_name("Short")
+            .unwrap()
+            .unwrap();
+        assert!(matches!(
+            to_record_batch(table),
+            Err(ArrowExportError::FieldCount {
+                row: 0,
+                expected: 2,
+                actual: 1,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_parquet_dir() {
+        let buf = database();
+        let dir = std::env::temp_dir().join(format!("assembly-fdb-parquet-{}", std::process::id()));
+        write_parquet_dir(Database::new(&buf), &dir).unwrap();
+        assert!(!dir.join("Empty.parquet").exists());
+
+        let file = File::open(dir.join("Items.parquet")).unwrap();
+        let batches = ParquetRecordBatchReaderBuilder::try_new(file)
+            .unwrap()
+            .build()
+            .unwrap()
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(batches.len(), 1);
+        check(&batches[0]);
+    }
+}
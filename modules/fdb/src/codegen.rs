@@ -0,0 +1,524 @@
+//! # Rust code for tables
+//!
+//! This module generates Rust code that mirrors the tables of a database, so
+//! that the structs don't need to be maintained by hand. For every table, the
+//! output contains:
+//!
+//! - a struct with one field per column, which implements [`FdbRow`](crate::typed::FdbRow)
+//! - `load_all`, which loads all rows of the table
+//! - `find`, which loads the rows with a given primary key
+//!
+//! Columns that contain `NULL` become `Option<T>`. Because a template file
+//! has no rows, all of its columns are treated as nullable. Column names are
+//! converted to `snake_case` and table names to `CamelCase`, so `behavior 1`
+//! becomes `behavior_1`.
+//!
+//! The generator is meant to be called from a build script:
+//!
+//! ```no_run
+//! // build.rs
+//! use assembly_fdb::{codegen::{generate, CodegenOptions}, mem::Database};
+//!
+//! let buf = std::fs::read("cdclient.fdb").unwrap();
+//! let options = CodegenOptions::new().filter(|table| table.starts_with("Loot"));
+//! let code = generate(Database::new(&buf), &options).unwrap();
+//! let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("tables.rs");
+//! std::fs::write(out, code).unwrap();
+//! ```
+//!
+//! ```ignore
+//! // src/lib.rs
+//! include!(concat!(env!("OUT_DIR"), "/tables.rs"));
+//! ```
+
+use std::{collections::HashSet, fmt::Write};
+
+use assembly_core::buffer::CastError;
+use assembly_fdb_core::value::ValueType;
+
+use crate::mem::{Database, Field, Table};
+
+type TableFilter<'a> = Box<dyn Fn(&str) -> bool + 'a>;
+
+/// Options for [`generate`]
+#[derive(Default)]
+pub struct CodegenOptions<'a> {
+    filter: Option<TableFilter<'a>>,
+    all_nullable: bool,
+}
+
+impl<'a> CodegenOptions<'a> {
+    /// Create the default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only generate code for the tables for which `filter` returns `true`
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&str) -> bool + 'a,
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    /// Use `Option<T>` for all columns, even if they contain no `NULL`
+    pub fn all_nullable(mut self, all_nullable: bool) -> Self {
+        self.all_nullable = all_nullable;
+        self
+    }
+
+    fn includes(&self, table: &str) -> bool {
+        match &self.filter {
+            Some(filter) => filter(table),
+            None => true,
+        }
+    }
+}
+
+/// Generate the code for all tables of a database
+pub fn generate(db: Database<'_>, options: &CodegenOptions) -> Result<String, CastError> {
+    let mut out = String::from("// Generated by assembly_fdb::codegen, do not edit\n");
+    let mut idents = HashSet::new();
+    for table in db.tables()?.iter() {
+        let table = table?;
+        if options.includes(&table.name()) {
+            let ident = unique(&mut idents, camel_case(&table.name()), "");
+            out.push('\n');
+            out.push_str(&table_code(table, &ident, options));
+        }
+    }
+    Ok(out)
+}
+
+/// A column of the generated struct
+struct Column {
+    name: String,
+    ident: String,
+    value_type: ValueType,
+    nullable: bool,
+}
+
+impl Column {
+    /// The Rust type of the field
+    fn field_type(&self) -> String {
+        let ty = match self.value_type {
+            ValueType::Integer => "i32",
+            ValueType::Float => "f32",
+            ValueType::Text | ValueType::VarChar => "::std::string::String",
+            ValueType::Boolean => "bool",
+            ValueType::BigInt => "i64",
+            ValueType::Nothing => unreachable!(),
+        };
+        if self.nullable {
+            format!("::std::option::Option<{}>", ty)
+        } else {
+            ty.to_owned()
+        }
+    }
+
+    /// The type of the key argument of `find`
+    fn key_type(&self) -> &'static str {
+        match self.value_type {
+            ValueType::Integer => "i32",
+            ValueType::Float => "f32",
+            ValueType::Text | ValueType::VarChar => "&str",
+            ValueType::Boolean => "bool",
+            ValueType::BigInt => "i64",
+            ValueType::Nothing => unreachable!(),
+        }
+    }
+
+    fn is_text(&self) -> bool {
+        matches!(self.value_type, ValueType::Text | ValueType::VarChar)
+    }
+}
+
+/// Generate the code for a single table
+pub fn generate_table(table: Table<'_>, options: &CodegenOptions) -> String {
+    table_code(table, &camel_case(&table.name()), options)
+}
+
+/// Generate the code for a table with the struct `ty`
+fn table_code(table: Table<'_>, ty: &str, options: &CodegenOptions) -> String {
+    let empty = table.row_iter().next().is_none();
+    let mut nullable = vec![options.all_nullable || empty; table.column_count()];
+    for row in table.row_iter() {
+        for (nullable, field) in nullable.iter_mut().zip(row.field_iter()) {
+            *nullable |= matches!(field, Field::Nothing);
+        }
+    }
+
+    let mut idents = HashSet::new();
+    let mut skipped = Vec::new();
+    let mut columns = Vec::new();
+    for (column, nullable) in table.column_iter().zip(nullable) {
+        let name = column.name().into_owned();
+        if column.value_type() == ValueType::Nothing {
+            skipped.push(name);
+            continue;
+        }
+        let ident = unique(&mut idents, snake_case(&name), "_");
+        columns.push(Column {
+            name,
+            ident,
+            value_type: column.value_type(),
+            nullable,
+        });
+    }
+
+    let table_name = table.name();
+    let mut out = String::new();
+    let o = &mut out;
+    writeln!(o, "/// A row of the table `{}`", table_name).unwrap();
+    for name in &skipped {
+        writeln!(
+            o,
+            "///\n/// The column `{}` has no type and is skipped.",
+            name
+        )
+        .unwrap();
+    }
+    writeln!(o, "#[derive(Debug, Clone, PartialEq)]").unwrap();
+    writeln!(o, "pub struct {} {{", ty).unwrap();
+    for column in &columns {
+        writeln!(o, "    /// The column `{}`", column.name).unwrap();
+        writeln!(o, "    pub {}: {},", column.ident, column.field_type()).unwrap();
+    }
+    writeln!(o, "}}\n").unwrap();
+
+    writeln!(
+        o,
+        "impl<'a> ::assembly_fdb::typed::FdbRow<'a> for {} {{",
+        ty
+    )
+    .unwrap();
+    writeln!(o, "    const TABLE: &'static str = {:?};", table_name).unwrap();
+    writeln!(o, "    type Columns = [usize; {}];\n", columns.len()).unwrap();
+    writeln!(o, "    fn resolve_columns(").unwrap();
+    writeln!(o, "        table: ::assembly_fdb::mem::Table<'a>,").unwrap();
+    writeln!(
+        o,
+        "    ) -> ::std::result::Result<Self::Columns, ::assembly_fdb::typed::LoadError> {{"
+    )
+    .unwrap();
+    writeln!(o, "        ::std::result::Result::Ok([").unwrap();
+    for column in &columns {
+        writeln!(
+            o,
+            "            ::assembly_fdb::typed::column_index::<{}>(table, {:?})?,",
+            column.field_type(),
+            column.name
+        )
+        .unwrap();
+    }
+    writeln!(o, "        ])").unwrap();
+    writeln!(o, "    }}\n").unwrap();
+    writeln!(o, "    fn load_row(").unwrap();
+    writeln!(o, "        table: ::assembly_fdb::mem::Table<'a>,").unwrap();
+    writeln!(o, "        columns: &Self::Columns,").unwrap();
+    writeln!(o, "        row: ::assembly_fdb::mem::Row<'a>,").unwrap();
+    writeln!(
+        o,
+        "    ) -> ::std::result::Result<Self, ::assembly_fdb::typed::LoadError> {{"
+    )
+    .unwrap();
+    writeln!(o, "        ::std::result::Result::Ok(Self {{").unwrap();
+    for (index, column) in columns.iter().enumerate() {
+        writeln!(
+            o,
+            "            {}: ::assembly_fdb::typed::field_at(table, row, columns[{}], {:?})?,",
+            column.ident, index, column.name
+        )
+        .unwrap();
+    }
+    writeln!(o, "        }})").unwrap();
+    writeln!(o, "    }}").unwrap();
+    writeln!(o, "}}\n").unwrap();
+
+    writeln!(o, "impl {} {{", ty).unwrap();
+    writeln!(o, "    /// Load all rows of the table `{}`", table_name).unwrap();
+    writeln!(o, "    pub fn load_all(").unwrap();
+    writeln!(o, "        tables: ::assembly_fdb::mem::Tables<'_>,").unwrap();
+    writeln!(o, "    ) -> ::std::result::Result<::std::vec::Vec<Self>, ::assembly_fdb::typed::LoadError> {{").unwrap();
+    writeln!(
+        o,
+        "        ::assembly_fdb::typed::Loader::<Self>::from_tables(tables)?"
+    )
+    .unwrap();
+    writeln!(o, "            .iter()").unwrap();
+    writeln!(o, "            .collect()").unwrap();
+    writeln!(o, "    }}").unwrap();
+
+    let pk = match table.column_iter().next() {
+        Some(pk) if pk.value_type() != ValueType::Nothing => Some(&columns[0]),
+        _ => None,
+    };
+    if let Some(pk) = pk {
+        let (pattern, guard) = match pk.value_type {
+            ValueType::Text | ValueType::VarChar => (
+                "Text(v) | ::assembly_fdb::mem::Field::VarChar(v)",
+                "v.decode() == key",
+            ),
+            ValueType::Integer => ("Integer(v)", "v == key"),
+            ValueType::Float => ("Float(v)", "v == key"),
+            ValueType::Boolean => ("Boolean(v)", "v == key"),
+            ValueType::BigInt => ("BigInt(v)", "v == key"),
+            ValueType::Nothing => unreachable!(),
+        };
+        let key = if pk.is_text() { "key" } else { "&key" };
+        writeln!(o).unwrap();
+        writeln!(o, "    /// Find all rows where `{}` is `key`", pk.name).unwrap();
+        writeln!(o, "    pub fn find(").unwrap();
+        writeln!(o, "        tables: ::assembly_fdb::mem::Tables<'_>,").unwrap();
+        writeln!(o, "        key: {},", pk.key_type()).unwrap();
+        writeln!(o, "    ) -> ::std::result::Result<::std::vec::Vec<Self>, ::assembly_fdb::typed::LoadError> {{").unwrap();
+        writeln!(
+            o,
+            "        let loader = ::assembly_fdb::typed::Loader::<Self>::from_tables(tables)?;"
+        )
+        .unwrap();
+        writeln!(o, "        let table = loader.table();").unwrap();
+        writeln!(o, "        let mut rows = ::std::vec::Vec::new();").unwrap();
+        writeln!(o, "        if table.bucket_count() == 0 {{").unwrap();
+        writeln!(o, "            return ::std::result::Result::Ok(rows);").unwrap();
+        writeln!(o, "        }}").unwrap();
+        writeln!(
+            o,
+            "        let hash = ::assembly_fdb::FdbHash::hash({});",
+            key
+        )
+        .unwrap();
+        writeln!(
+            o,
+            "        for row in table.bucket_for_hash(hash).row_iter() {{"
+        )
+        .unwrap();
+        writeln!(o, "            // Only load the rows with the right key").unwrap();
+        writeln!(o, "            match row.field_at(0) {{").unwrap();
+        writeln!(
+            o,
+            "                ::std::option::Option::Some(::assembly_fdb::mem::Field::{}) if {} => {{",
+            pattern, guard
+        )
+        .unwrap();
+        writeln!(o, "                    rows.push(loader.load(row)?);").unwrap();
+        writeln!(o, "                }}").unwrap();
+        writeln!(o, "                _ => {{}}").unwrap();
+        writeln!(o, "            }}").unwrap();
+        writeln!(o, "        }}").unwrap();
+        writeln!(o, "        ::std::result::Result::Ok(rows)").unwrap();
+        writeln!(o, "    }}").unwrap();
+    }
+    writeln!(o, "}}").unwrap();
+    out
+}
+
+/// Split a name into words at non-alphanumeric characters and case changes
+fn words(name: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    for part in name.split(|c: char| !c.is_ascii_alphanumeric()) {
+        let chars: Vec<char> = part.chars().collect();
+        let mut word = String::new();
+        for (i, &c) in chars.iter().enumerate() {
+            let prev = i.checked_sub(1).map(|i| chars[i]);
+            let next = chars.get(i + 1);
+            let boundary = c.is_ascii_uppercase()
+                && match prev {
+                    Some(p) if p.is_ascii_lowercase() || p.is_ascii_digit() => true,
+                    Some(p) if p.is_ascii_uppercase() => {
+                        next.filter(|n| n.is_ascii_lowercase()).is_some()
+                    }
+                    _ => false,
+                };
+            if boundary && !word.is_empty() {
+                words.push(std::mem::take(&mut word));
+            }
+            word.push(c);
+        }
+        if !word.is_empty() {
+            words.push(word);
+        }
+    }
+    words
+}
+
+/// Convert a column name to a field name
+pub fn snake_case(name: &str) -> String {
+    let ident = words(name)
+        .iter()
+        .map(|w| w.to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join("_");
+    if ident.is_empty() {
+        String::from("column")
+    } else if ident.starts_with(|c: char| c.is_ascii_digit()) {
+        format!("column_{}", ident)
+    } else if matches!(ident.as_str(), "self" | "super" | "crate") {
+        format!("{}_", ident)
+    } else if KEYWORDS.contains(&ident.as_str()) {
+        format!("r#{}", ident)
+    } else {
+        ident
+    }
+}
+
+/// Convert a table name to a type name
+pub fn camel_case(name: &str) -> String {
+    let ident: String = words(name)
+        .iter()
+        .map(|w| {
+            let lower = w.to_ascii_lowercase();
+            let mut chars = lower.chars();
+            chars
+                .next()
+                .map(|c| c.to_ascii_uppercase())
+                .into_iter()
+                .chain(chars)
+                .collect::<String>()
+        })
+        .collect();
+    if ident.is_empty() {
+        String::from("Table")
+    } else if ident.starts_with(|c: char| c.is_ascii_digit()) || ident == "Self" {
+        format!("Table{}", ident)
+    } else {
+        ident
+    }
+}
+
+/// Append a number to `ident` until it is not in `idents`
+fn unique(idents: &mut HashSet<String>, ident: String, separator: &str) -> String {
+    let mut candidate = ident.clone();
+    let mut n = 2;
+    while !idents.insert(candidate.clone()) {
+        candidate = format!("{}{}{}", ident.trim_start_matches("r#"), separator, n);
+        n += 1;
+    }
+    candidate
+}
+
+/// The keywords that can be used as raw identifiers
+const KEYWORDS: &[&str] = &[
+    "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "do", "dyn",
+    "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl", "in", "let",
+    "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub", "ref", "return",
+    "static", "struct", "trait", "true", "try", "type", "typeof", "unsafe", "unsized", "use",
+    "virtual", "where", "while", "yield",
+];
+
+/// The output of `generate` for the database of `tests::database`
+#[cfg(all(test, feature = "store"))]
+#[rustfmt::skip]
+#[allow(clippy::all)]
+mod generated;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_idents() {
+        assert_eq!(snake_case("behavior 1"), "behavior_1");
+        assert_eq!(snake_case("targetLOT"), "target_lot");
+        assert_eq!(snake_case("LOTTable"), "lot_table");
+        assert_eq!(snake_case("skillID2"), "skill_id2");
+        assert_eq!(snake_case("type"), "r#type");
+        assert_eq!(snake_case("self"), "self_");
+        assert_eq!(snake_case("1st"), "column_1st");
+        assert_eq!(snake_case("?"), "column");
+        assert_eq!(camel_case("AICombatRoles"), "AiCombatRoles");
+        assert_eq!(camel_case("mapShaders"), "MapShaders");
+        assert_eq!(camel_case("ZoneLoadingTips"), "ZoneLoadingTips");
+        assert_eq!(camel_case("self"), "TableSelf");
+
+        let mut idents = HashSet::new();
+        assert_eq!(unique(&mut idents, snake_case("Type"), "_"), "r#type");
+        assert_eq!(unique(&mut idents, snake_case("type"), "_"), "type_2");
+    }
+
+    /// A database with a text key, an integer key with collisions and an empty table
+    #[cfg(feature = "store")]
+    fn database() -> Vec<u8> {
+        use crate::{
+            util::tests::{build, TableBuilder},
+            value::owned::Field,
+        };
+
+        let mut skills = TableBuilder::new("Skills")
+            .column("name", ValueType::Text)
+            .column("behavior 1", ValueType::Integer)
+            .column("unused", ValueType::Nothing);
+        for (name, behavior) in [("a", Some(1)), ("b", None), ("a", Some(3))] {
+            skills.row(vec![
+                Field::Text(String::from(name)),
+                behavior.map_or(Field::Nothing, Field::Integer),
+                Field::Nothing,
+            ]);
+        }
+
+        // Put several keys into every bucket
+        let mut items = TableBuilder::new("Items")
+            .column("id", ValueType::Integer)
+            .column("price", ValueType::BigInt)
+            .column("weight", ValueType::Float)
+            .buckets(2);
+        for id in 1..=5 {
+            items.row(vec![
+                Field::Integer(id),
+                Field::BigInt(i64::from(id) << 32),
+                Field::Float(id as f32 / 2.0),
+            ]);
+        }
+
+        build(vec![skills, items, TableBuilder::new("Empty")])
+    }
+
+    #[test]
+    #[cfg(feature = "store")]
+    fn test_generate() {
+        let buf = database();
+        let options = CodegenOptions::new().filter(|t| t != "Empty");
+        let code = generate(Database::new(&buf), &options).unwrap();
+        assert!(!code.contains("pub struct Empty"));
+        assert!(code.contains("pub struct Skills {"));
+        assert!(code.contains("pub name: ::std::string::String,"));
+        assert!(code.contains("pub behavior_1: ::std::option::Option<i32>,"));
+        assert!(code.contains("The column `unused` has no type and is skipped."));
+        assert!(code.contains("type Columns = [usize; 2];"));
+        assert!(code.contains("key: &str,"));
+
+        let code = generate(Database::new(&buf), &options.all_nullable(true)).unwrap();
+        assert!(code.contains("pub name: ::std::option::Option<::std::string::String>,"));
+    }
+
+    /// Compile the generated code and use it on the same database
+    #[test]
+    #[cfg(feature = "store")]
+    fn test_generated() {
+        use super::generated::{Items, Skills};
+
+        let buf = database();
+        let options = CodegenOptions::new().filter(|t| t != "Empty");
+        let code = generate(Database::new(&buf), &options).unwrap();
+        assert_eq!(code, include_str!("codegen/generated.rs"));
+
+        let tables = Database::new(&buf).tables().unwrap();
+        let skills = Skills::find(tables, "a").unwrap();
+        let behaviors: Vec<_> = skills.iter().map(|s| s.behavior_1).collect();
+        assert_eq!(behaviors, [Some(1), Some(3)]);
+        assert!(Skills::find(tables, "c").unwrap().is_empty());
+        assert_eq!(Skills::load_all(tables).unwrap().len(), 3);
+
+        let items = Items::find(tables, 3).unwrap();
+        assert_eq!(
+            items,
+            [Items {
+                id: 3,
+                price: 3 << 32,
+                weight: 1.5
+            }]
+        );
+        assert_eq!(Items::load_all(tables).unwrap().len(), 5);
+    }
+}
@@ -0,0 +1,145 @@
+// Generated by assembly_fdb::codegen, do not edit
+
+/// A row of the table `Items`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Items {
+    /// The column `id`
+    pub id: i32,
+    /// The column `price`
+    pub price: i64,
+    /// The column `weight`
+    pub weight: f32,
+}
+
+impl<'a> ::assembly_fdb::typed::FdbRow<'a> for Items {
+    const TABLE: &'static str = "Items";
+    type Columns = [usize; 3];
+
+    fn resolve_columns(
+        table: ::assembly_fdb::mem::Table<'a>,
+    ) -> ::std::result::Result<Self::Columns, ::assembly_fdb::typed::LoadError> {
+        ::std::result::Result::Ok([
+            ::assembly_fdb::typed::column_index::<i32>(table, "id")?,
+            ::assembly_fdb::typed::column_index::<i64>(table, "price")?,
+            ::assembly_fdb::typed::column_index::<f32>(table, "weight")?,
+        ])
+    }
+
+    fn load_row(
+        table: ::assembly_fdb::mem::Table<'a>,
+        columns: &Self::Columns,
+        row: ::assembly_fdb::mem::Row<'a>,
+    ) -> ::std::result::Result<Self, ::assembly_fdb::typed::LoadError> {
+        ::std::result::Result::Ok(Self {
+            id: ::assembly_fdb::typed::field_at(table, row, columns[0], "id")?,
+            price: ::assembly_fdb::typed::field_at(table, row, columns[1], "price")?,
+            weight: ::assembly_fdb::typed::field_at(table, row, columns[2], "weight")?,
+        })
+    }
+}
+
+impl Items {
+    /// Load all rows of the table `Items`
+    pub fn load_all(
+        tables: ::assembly_fdb::mem::Tables<'_>,
+    ) -> ::std::result::Result<::std::vec::Vec<Self>, ::assembly_fdb::typed::LoadError> {
+        ::assembly_fdb::typed::Loader::<Self>::from_tables(tables)?
+            .iter()
+            .collect()
+    }
+
+    /// Find all rows where `id` is `key`
+    pub fn find(
+        tables: ::assembly_fdb::mem::Tables<'_>,
+        key: i32,
+    ) -> ::std::result::Result<::std::vec::Vec<Self>, ::assembly_fdb::typed::LoadError> {
+        let loader = ::assembly_fdb::typed::Loader::<Self>::from_tables(tables)?;
+        let table = loader.table();
+        let mut rows = ::std::vec::Vec::new();
+        if table.bucket_count() == 0 {
+            return ::std::result::Result::Ok(rows);
+        }
+        let hash = ::assembly_fdb::FdbHash::hash(&key);
+        for row in table.bucket_for_hash(hash).row_iter() {
+            // Only load the rows with the right key
+            match row.field_at(0) {
+                ::std::option::Option::Some(::assembly_fdb::mem::Field::Integer(v)) if v == key => {
+                    rows.push(loader.load(row)?);
+                }
+                _ => {}
+            }
+        }
+        ::std::result::Result::Ok(rows)
+    }
+}
+
+/// A row of the table `Skills`
+///
+/// The column `unused` has no type and is skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Skills {
+    /// The column `name`
+    pub name: ::std::string::String,
+    /// The column `behavior 1`
+    pub behavior_1: ::std::option::Option<i32>,
+}
+
+impl<'a> ::assembly_fdb::typed::FdbRow<'a> for Skills {
+    const TABLE: &'static str = "Skills";
+    type Columns = [usize; 2];
+
+    fn resolve_columns(
+        table: ::assembly_fdb::mem::Table<'a>,
+    ) -> ::std::result::Result<Self::Columns, ::assembly_fdb::typed::LoadError> {
+        ::std::result::Result::Ok([
+            ::assembly_fdb::typed::column_index::<::std::string::String>(table, "name")?,
+            ::assembly_fdb::typed::column_index::<::std::option::Option<i32>>(table, "behavior 1")?,
+        ])
+    }
+
+    fn load_row(
+        table: ::assembly_fdb::mem::Table<'a>,
+        columns: &Self::Columns,
+        row: ::assembly_fdb::mem::Row<'a>,
+    ) -> ::std::result::Result<Self, ::assembly_fdb::typed::LoadError> {
+        ::std::result::Result::Ok(Self {
+            name: ::assembly_fdb::typed::field_at(table, row, columns[0], "name")?,
+            behavior_1: ::assembly_fdb::typed::field_at(table, row, columns[1], "behavior 1")?,
+        })
+    }
+}
+
+impl Skills {
+    /// Load all rows of the table `Skills`
+    pub fn load_all(
+        tables: ::assembly_fdb::mem::Tables<'_>,
+    ) -> ::std::result::Result<::std::vec::Vec<Self>, ::assembly_fdb::typed::LoadError> {
+        ::assembly_fdb::typed::Loader::<Self>::from_tables(tables)?
+            .iter()
+            .collect()
+    }
+
+    /// Find all rows where `name` is `key`
+    pub fn find(
+        tables: ::assembly_fdb::mem::Tables<'_>,
+        key: &str,
+    ) -> ::std::result::Result<::std::vec::Vec<Self>, ::assembly_fdb::typed::LoadError> {
+        let loader = ::assembly_fdb::typed::Loader::<Self>::from_tables(tables)?;
+        let table = loader.table();
+        let mut rows = ::std::vec::Vec::new();
+        if table.bucket_count() == 0 {
+            return ::std::result::Result::Ok(rows);
+        }
+        let hash = ::assembly_fdb::FdbHash::hash(key);
+        for row in table.bucket_for_hash(hash).row_iter() {
+            // Only load the rows with the right key
+            match row.field_at(0) {
+                ::std::option::Option::Some(::assembly_fdb::mem::Field::Text(v) | ::assembly_fdb::mem::Field::VarChar(v)) if v.decode() == key => {
+                    rows.push(loader.load(row)?);
+                }
+                _ => {}
+            }
+        }
+        ::std::result::Result::Ok(rows)
+    }
+}
@@ -15,17 +15,57 @@
 //!
 //! Each Table has a list of columns with the names and default data
 //! Types corresponding to the layout of each row.
+//!
+//! Unlike the other representations, this one can be edited. Rows are inserted,
+//! deleted and updated by the value of their primary key (the first field), and
+//! columns can be added or dropped. An edited schema can be written to a file by
+//! converting it to a `store::Database`.
 
 pub mod iter;
 #[cfg(feature = "core-loader")]
 pub mod loader;
 
+use assembly_core::buffer::CastError;
 pub use assembly_fdb_core::value::{
     mem::MemContext,
     owned::{Field, OwnedContext},
     Context, Value, ValueMapperMut, ValueType,
 };
-use std::collections::BTreeMap;
+use assembly_fdb_core::FdbHash;
+use displaydoc::Display;
+use std::{collections::BTreeMap, convert::TryFrom};
+use thiserror::Error;
+
+use crate::mem;
+
+/// Errors when editing a [`Table`]
+#[derive(Debug, Error, Display, Clone, PartialEq, Eq)]
+pub enum EditError {
+    /// Column {0:?} does not exist
+    UnknownColumn(String),
+    /// Column {0:?} already exists
+    DuplicateColumn(String),
+    /// The primary key column {0:?} can't be dropped
+    PrimaryKeyColumn(String),
+    /// The primary key column {0:?} can't be set to NULL
+    NullPrimaryKey(String),
+    /// Row has {actual} fields, but the table has {expected} columns
+    FieldCount {
+        /// The number of columns
+        expected: usize,
+        /// The number of fields in the row
+        actual: usize,
+    },
+    /// Column {column:?} has type {expected}, but the value has type {found}
+    FieldType {
+        /// The name of the column
+        column: String,
+        /// The type of the column
+        expected: ValueType,
+        /// The type of the value
+        found: ValueType,
+    },
+}
 
 /// A sequence of fields
 #[derive(Debug, Default)]
@@ -181,6 +221,205 @@ impl Table {
     pub fn name(&self) -> &str {
         self.definition.name.as_ref()
     }
+
+    /// Returns the index of the column with the given name
+    pub fn column_index(&self, name: &str) -> Option<usize> {
+        self.definition.columns.iter().position(|c| c.name == name)
+    }
+
+    fn bucket_index(&self, key: &Field) -> usize {
+        FdbHash::hash(key) as usize % self.data.buckets.len()
+    }
+
+    /// Returns an iterator over all rows with the given primary key
+    pub fn rows_by_key<'a>(&'a self, key: &'a Field) -> impl Iterator<Item = &'a Row> + 'a {
+        let bucket = match self.data.buckets.len() {
+            0 => None,
+            _ => Some(&self.data.buckets[self.bucket_index(key)]),
+        };
+        bucket
+            .into_iter()
+            .flat_map(|b| b.rows_ref().iter())
+            .filter(move |r| r.fields().first() == Some(key))
+    }
+
+    /// Returns an iterator over mutable references to all rows with the given primary key
+    ///
+    /// **Note**: Changing the primary key of a row this way does not move it to
+    /// another bucket. Use [`Table::update_field`] for that.
+    pub fn rows_by_key_mut<'a>(
+        &'a mut self,
+        key: &'a Field,
+    ) -> impl Iterator<Item = &'a mut Row> + 'a {
+        let bucket = match self.data.buckets.len() {
+            0 => None,
+            _ => {
+                let index = self.bucket_index(key);
+                Some(&mut self.data.buckets[index])
+            }
+        };
+        bucket
+            .into_iter()
+            .flat_map(|b| b.rows_mut().iter_mut())
+            .filter(move |r| r.fields().first() == Some(key))
+    }
+
+    /// Insert a row into the bucket for its primary key
+    ///
+    /// If the table has no buckets yet, a single bucket is created. Every field
+    /// must be `NULL` or have the type of its column.
+    pub fn insert_row(&mut self, row: Row) -> Result<(), EditError> {
+        let expected = self.definition.columns.len();
+        let actual = row.fields().len();
+        if expected != actual {
+            return Err(EditError::FieldCount { expected, actual });
+        }
+        for (column, field) in self.definition.columns.iter().zip(row.fields()) {
+            check_type(column, field)?;
+        }
+        if self.data.buckets.is_empty() {
+            self.data.buckets.push(Bucket::new());
+        }
+        let index = self.bucket_index(&row.fields()[0]);
+        self.data.buckets[index].rows_mut().push(row);
+        Ok(())
+    }
+
+    /// Delete all rows with the given primary key, returning them
+    pub fn delete_rows(&mut self, key: &Field) -> Vec<Row> {
+        if self.data.buckets.is_empty() {
+            return Vec::new();
+        }
+        let index = self.bucket_index(key);
+        let rows = self.data.buckets[index].rows_mut();
+        let (deleted, kept) = std::mem::take(rows)
+            .into_iter()
+            .partition(|r| r.fields().first() == Some(key));
+        *rows = kept;
+        deleted
+    }
+
+    /// Set the field `column` of all rows with the given primary key to `value`
+    ///
+    /// Returns the number of updated rows. If `column` is the primary key, the
+    /// rows are moved to the bucket for the new value, which can't be `NULL`.
+    /// If any of the rows doesn't have a field for every column, or `value`
+    /// doesn't have the type of the column, no row is changed.
+    pub fn update_field(
+        &mut self,
+        key: &Field,
+        column: &str,
+        value: Field,
+    ) -> Result<usize, EditError> {
+        let index = self
+            .column_index(column)
+            .ok_or_else(|| EditError::UnknownColumn(column.to_string()))?;
+        check_type(&self.definition.columns[index], &value)?;
+        if index == 0 && value == Field::Nothing {
+            return Err(EditError::NullPrimaryKey(column.to_string()));
+        }
+        let expected = self.definition.columns.len();
+        if let Some(row) = self
+            .rows_by_key(key)
+            .find(|row| row.fields().len() != expected)
+        {
+            let actual = row.fields().len();
+            return Err(EditError::FieldCount { expected, actual });
+        }
+
+        if index == 0 {
+            let rows = self.delete_rows(key);
+            let count = rows.len();
+            for mut row in rows {
+                row.fields_mut()[0] = value.clone();
+                self.insert_row(row)?;
+            }
+            Ok(count)
+        } else {
+            let mut count = 0;
+            for row in self.rows_by_key_mut(key) {
+                row.fields_mut()[index] = value.clone();
+                count += 1;
+            }
+            Ok(count)
+        }
+    }
+
+    /// Append a column, setting the new field in every existing row to `default`
+    pub fn add_column(&mut self, column: Column, default: Field) -> Result<(), EditError> {
+        if self.column_index(&column.name).is_some() {
+            return Err(EditError::DuplicateColumn(column.name));
+        }
+        check_type(&column, &default)?;
+        self.definition.columns.push(column);
+        for bucket in &mut self.data.buckets {
+            for row in bucket.rows_mut() {
+                row.fields_mut().push(default.clone());
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a column and the corresponding field from every row
+    ///
+    /// The first column is the primary key and can't be removed. If any row
+    /// doesn't have a field for every column, no row is changed.
+    pub fn drop_column(&mut self, name: &str) -> Result<Column, EditError> {
+        let index = self
+            .column_index(name)
+            .ok_or_else(|| EditError::UnknownColumn(name.to_string()))?;
+        if index == 0 {
+            return Err(EditError::PrimaryKeyColumn(name.to_string()));
+        }
+        let expected = self.definition.columns.len();
+        if let Some(row) = self
+            .data
+            .buckets
+            .iter()
+            .flat_map(Bucket::rows_ref)
+            .find(|row| row.fields().len() != expected)
+        {
+            let actual = row.fields().len();
+            return Err(EditError::FieldCount { expected, actual });
+        }
+        for bucket in &mut self.data.buckets {
+            for row in bucket.rows_mut() {
+                row.fields_mut().remove(index);
+            }
+        }
+        Ok(self.definition.columns.remove(index))
+    }
+}
+
+impl<'a> From<mem::Table<'a>> for Table {
+    fn from(table: mem::Table<'a>) -> Self {
+        let definition = TableDef {
+            name: table.name().into_owned(),
+            columns: table
+                .column_iter()
+                .map(|c| Column {
+                    name: c.name().into_owned(),
+                    field_type: c.value_type(),
+                })
+                .collect(),
+        };
+        let buckets = table
+            .bucket_iter()
+            .map(|b| {
+                Bucket(
+                    b.row_iter()
+                        .map(|r| {
+                            Row(r
+                                .field_iter()
+                                .map(|f| f.map(&mut mem::MemToOwned))
+                                .collect())
+                        })
+                        .collect(),
+                )
+            })
+            .collect();
+        Table::from(definition, TableData { buckets })
+    }
 }
 
 /// # An ordered map of tables
@@ -217,6 +456,19 @@ impl Schema {
     }
 }
 
+impl<'a> TryFrom<mem::Database<'a>> for Schema {
+    type Error = CastError;
+
+    fn try_from(db: mem::Database<'a>) -> Result<Self, Self::Error> {
+        let tables = db
+            .tables()?
+            .iter()
+            .map(|t| t.map(Into::into))
+            .collect::<Result<Vec<Table>, _>>()?;
+        Ok(Schema::from(tables))
+    }
+}
+
 impl From<Vec<Table>> for Schema {
     fn from(tables: Vec<Table>) -> Self {
         let mut tree = BTreeMap::new();
@@ -226,3 +478,158 @@ impl From<Vec<Table>> for Schema {
         Schema { tables: tree }
     }
 }
+
+/// Check that `value` is `NULL` or has the type of `column`
+fn check_type(column: &Column, value: &Field) -> Result<(), EditError> {
+    let found = ValueType::from(value);
+    if found != ValueType::Nothing && found != column.field_type {
+        return Err(EditError::FieldType {
+            column: column.name.clone(),
+            expected: column.field_type,
+            found,
+        });
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn items() -> Table {
+        let mut table = Table::new(TableDef {
+            name: String::from("Items"),
+            columns: vec![
+                Column::from(("id", ValueType::Integer)),
+                Column::from(("name", ValueType::Text)),
+            ],
+        });
+        table.buckets_mut().extend((0..4).map(|_| Bucket::new()));
+        for (id, name) in [(1, "Sword"), (2, "Shield"), (2, "Helmet"), (5, "Bow")] {
+            let row = vec![Field::Integer(id), Field::Text(String::from(name))];
+            table.insert_row(Row::from(row)).unwrap();
+        }
+        table
+    }
+
+    #[test]
+    fn test_insert_delete() {
+        let mut table = items();
+        assert_eq!(table.buckets()[1].rows_ref().len(), 2);
+        assert_eq!(table.rows_by_key(&Field::Integer(2)).count(), 2);
+
+        let deleted = table.delete_rows(&Field::Integer(2));
+        assert_eq!(deleted.len(), 2);
+        assert_eq!(table.rows_by_key(&Field::Integer(2)).count(), 0);
+        assert_eq!(table.rows_by_key(&Field::Integer(1)).count(), 1);
+
+        let err = table.insert_row(Row::from(vec![Field::Integer(3)]));
+        assert_eq!(
+            err,
+            Err(EditError::FieldCount {
+                expected: 2,
+                actual: 1
+            })
+        );
+        let err = table.insert_row(Row::from(vec![Field::Integer(3), Field::Float(1.0)]));
+        assert_eq!(
+            err,
+            Err(EditError::FieldType {
+                column: String::from("name"),
+                expected: ValueType::Text,
+                found: ValueType::Float,
+            })
+        );
+        assert_eq!(table.rows_by_key(&Field::Integer(3)).count(), 0);
+        table
+            .insert_row(Row::from(vec![Field::Integer(3), Field::Nothing]))
+            .unwrap();
+    }
+
+    #[test]
+    fn test_update() {
+        let mut table = items();
+        let name = Field::Text(String::from("Crossbow"));
+        assert_eq!(table.update_field(&Field::Integer(5), "name", name), Ok(1));
+        assert_eq!(
+            table.update_field(&Field::Integer(5), "id", Field::Integer(6)),
+            Ok(1)
+        );
+        assert_eq!(table.rows_by_key(&Field::Integer(5)).count(), 0);
+        let row = table.rows_by_key(&Field::Integer(6)).next().unwrap();
+        assert_eq!(row.fields()[1], Field::Text(String::from("Crossbow")));
+        assert_eq!(table.buckets()[1].rows_ref().len(), 1);
+
+        assert_eq!(
+            table.update_field(&Field::Integer(6), "name", Field::Integer(3)),
+            Err(EditError::FieldType {
+                column: String::from("name"),
+                expected: ValueType::Text,
+                found: ValueType::Integer,
+            })
+        );
+        assert_eq!(
+            table.update_field(&Field::Integer(6), "name", Field::Nothing),
+            Ok(1)
+        );
+        assert_eq!(
+            table.update_field(&Field::Integer(6), "id", Field::Nothing),
+            Err(EditError::NullPrimaryKey(String::from("id")))
+        );
+        assert_eq!(table.rows_by_key(&Field::Integer(6)).count(), 1);
+
+        // A short row is not lost when moving it fails
+        table.buckets_mut()[1]
+            .rows_mut()
+            .push(Row::from(vec![Field::Integer(1)]));
+        for (column, value) in [("id", Field::Integer(7)), ("name", Field::Nothing)] {
+            assert_eq!(
+                table.update_field(&Field::Integer(1), column, value),
+                Err(EditError::FieldCount {
+                    expected: 2,
+                    actual: 1
+                })
+            );
+        }
+        assert_eq!(table.rows_by_key(&Field::Integer(1)).count(), 2);
+    }
+
+    #[test]
+    fn test_columns() {
+        let mut table = items();
+        let column = Column::from(("price", ValueType::Integer));
+        let float = Column::from(("price", ValueType::Float));
+        let err = table.add_column(float, Field::Integer(10));
+        assert!(matches!(err, Err(EditError::FieldType { .. })));
+        table.add_column(column, Field::Integer(10)).unwrap();
+        assert!(table
+            .rows_by_key(&Field::Integer(2))
+            .all(|r| r.fields()[2] == Field::Integer(10)));
+
+        let dropped = table.drop_column("name").unwrap();
+        assert_eq!(dropped.name, "name");
+        assert_eq!(table.column_index("price"), Some(1));
+        assert!((&table).into_iter().all(|r| r.fields().len() == 2));
+
+        assert_eq!(
+            table.drop_column("id").err(),
+            Some(EditError::PrimaryKeyColumn(String::from("id")))
+        );
+
+        // A short row makes the table keep the column
+        table.buckets_mut()[0]
+            .rows_mut()
+            .push(Row::from(vec![Field::Integer(4)]));
+        assert_eq!(
+            table.drop_column("price").err(),
+            Some(EditError::FieldCount {
+                expected: 2,
+                actual: 1
+            })
+        );
+        assert_eq!(table.column_index("price"), Some(1));
+        assert!(table
+            .rows_by_key(&Field::Integer(2))
+            .all(|r| r.fields().len() == 2));
+    }
+}
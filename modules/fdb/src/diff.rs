@@ -0,0 +1,489 @@
+//! # Compare two databases
+//!
+//! This module computes a structured [`Changeset`] between two [`mem::Database`]s
+//! that lists tables that were added or removed, changes to the column definitions
+//! and rows that were inserted, deleted or updated.
+//!
+//! Rows are matched by the value of their first field (the primary key). Tables
+//! may contain more than one row for the same key, in which case identical rows are
+//! matched first and the remaining ones are paired up in bucket order.
+//!
+//! ```
+//! use assembly_fdb::{diff, mem::Database};
+//!
+//! let file: &[u8] = &[0,0,0,0,8,0,0,0];
+//! let left = Database::new(file);
+//! let right = Database::new(file);
+//! let changes = diff::diff_databases(left, right).unwrap();
+//!
+//! assert!(changes.is_empty());
+//! ```
+
+use std::{collections::BTreeMap, fmt};
+
+use assembly_core::buffer::CastError;
+use assembly_fdb_core::value::{owned::Field, ValueType};
+use latin1str::Latin1Str;
+
+use crate::mem::{self, Database, MemToOwned, Row, Table};
+
+/// Name and type of a column
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct ColumnDef {
+    /// The name of the column
+    pub name: String,
+    /// The declared type of the column
+    pub value_type: ValueType,
+}
+
+impl<'a> From<mem::Column<'a>> for ColumnDef {
+    fn from(column: mem::Column<'a>) -> Self {
+        Self {
+            name: column.name().into_owned(),
+            value_type: column.value_type(),
+        }
+    }
+}
+
+impl fmt::Display for ColumnDef {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "[{}] {}", self.name, self.value_type)
+    }
+}
+
+/// A change to the column at some index
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub enum ColumnChange {
+    /// The column only exists in the new table
+    Added {
+        /// The position of the column
+        index: usize,
+        /// The new column
+        column: ColumnDef,
+    },
+    /// The column only exists in the old table
+    Removed {
+        /// The position of the column
+        index: usize,
+        /// The old column
+        column: ColumnDef,
+    },
+    /// The name or type of the column changed
+    Changed {
+        /// The position of the column
+        index: usize,
+        /// The old column
+        old: ColumnDef,
+        /// The new column
+        new: ColumnDef,
+    },
+}
+
+/// A change to a single field of a row
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct FieldChange {
+    /// The position of the field
+    pub index: usize,
+    /// The name of the column in the new table (or the old one, if it was removed)
+    pub column: String,
+    /// The old value, if the old row had that field
+    pub old: Option<Field>,
+    /// The new value, if the new row has that field
+    pub new: Option<Field>,
+}
+
+/// A row that exists in both tables, but with different values
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct RowUpdate {
+    /// The primary key of the row
+    pub key: Field,
+    /// The fields that changed
+    pub fields: Vec<FieldChange>,
+}
+
+/// All changes to a table that exists in both databases
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct TableChangeset {
+    /// The name of the table
+    pub name: String,
+    /// Changes to the column definitions
+    pub columns: Vec<ColumnChange>,
+    /// Rows that only exist in the new table
+    pub inserted: Vec<Vec<Field>>,
+    /// Rows that only exist in the old table
+    pub deleted: Vec<Vec<Field>>,
+    /// Rows that exist in both tables with different values
+    pub updated: Vec<RowUpdate>,
+}
+
+impl TableChangeset {
+    /// Check whether there are no changes to this table
+    pub fn is_empty(&self) -> bool {
+        self.columns.is_empty()
+            && self.inserted.is_empty()
+            && self.deleted.is_empty()
+            && self.updated.is_empty()
+    }
+}
+
+/// All changes between two databases
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct Changeset {
+    /// Names of tables that only exist in the new database
+    pub tables_added: Vec<String>,
+    /// Names of tables that only exist in the old database
+    pub tables_removed: Vec<String>,
+    /// Changes to tables that exist in both databases
+    ///
+    /// Tables without changes are not included.
+    pub tables: Vec<TableChangeset>,
+}
+
+impl Changeset {
+    /// Check whether the databases are equivalent
+    pub fn is_empty(&self) -> bool {
+        self.tables_added.is_empty() && self.tables_removed.is_empty() && self.tables.is_empty()
+    }
+}
+
+/// Compare two databases
+pub fn diff_databases(old: Database, new: Database) -> Result<Changeset, CastError> {
+    let old_tables = table_map(old)?;
+    let mut new_tables = table_map(new)?;
+
+    let mut changeset = Changeset::default();
+    for (name, old_table) in old_tables {
+        if let Some(new_table) = new_tables.remove(name) {
+            let changes = diff_tables(old_table, new_table);
+            if !changes.is_empty() {
+                changeset.tables.push(changes);
+            }
+        } else {
+            changeset.tables_removed.push(name.decode().into_owned());
+        }
+    }
+    changeset.tables_added = new_tables
+        .keys()
+        .map(|name| name.decode().into_owned())
+        .collect();
+    Ok(changeset)
+}
+
+fn table_map(db: Database<'_>) -> Result<BTreeMap<&Latin1Str, Table<'_>>, CastError> {
+    let mut map = BTreeMap::new();
+    for table in db.tables()?.iter() {
+        let table = table?;
+        map.insert(table.name_raw(), table);
+    }
+    Ok(map)
+}
+
+/// Compare two tables
+pub fn diff_tables(old: Table, new: Table) -> TableChangeset {
+    let old_columns: Vec<ColumnDef> = old.column_iter().map(ColumnDef::from).collect();
+    let new_columns: Vec<ColumnDef> = new.column_iter().map(ColumnDef::from).collect();
+
+    let mut changes = TableChangeset {
+        name: new.name().into_owned(),
+        columns: diff_columns(&old_columns, &new_columns),
+        ..Default::default()
+    };
+
+    let old_rows = row_map(old);
+    let mut new_rows = row_map(new);
+
+    for (key, mut old_group) in old_rows {
+        let mut new_group = new_rows.remove(&key).unwrap_or_default();
+
+        // Drop all rows that are present in both groups
+        old_group.retain(|old_row| {
+            if let Some(pos) = new_group
+                .iter()
+                .position(|new_row| rows_eq(*old_row, *new_row))
+            {
+                new_group.remove(pos);
+                false
+            } else {
+                true
+            }
+        });
+
+        let mut old_iter = old_group.into_iter();
+        let mut new_iter = new_group.into_iter();
+        loop {
+            match (old_iter.next(), new_iter.next()) {
+                (Some(old_row), Some(new_row)) => {
+                    let fields = diff_fields(old_row, new_row, &old_columns, &new_columns);
+                    let key = old_row
+                        .field_at(0)
+                        .map_or(Field::Nothing, |f| f.map(&mut MemToOwned));
+                    changes.updated.push(RowUpdate { key, fields });
+                }
+                (Some(old_row), None) => changes.deleted.push(owned_fields(old_row)),
+                (None, Some(new_row)) => changes.inserted.push(owned_fields(new_row)),
+                (None, None) => break,
+            }
+        }
+    }
+
+    for new_group in new_rows.into_values() {
+        changes
+            .inserted
+            .extend(new_group.into_iter().map(owned_fields));
+    }
+
+    changes
+}
+
+fn diff_columns(old: &[ColumnDef], new: &[ColumnDef]) -> Vec<ColumnChange> {
+    let mut changes = Vec::new();
+    for index in 0..old.len().max(new.len()) {
+        match (old.get(index), new.get(index)) {
+            (Some(o), Some(n)) if o != n => changes.push(ColumnChange::Changed {
+                index,
+                old: o.clone(),
+                new: n.clone(),
+            }),
+            (Some(o), None) => changes.push(ColumnChange::Removed {
+                index,
+                column: o.clone(),
+            }),
+            (None, Some(n)) => changes.push(ColumnChange::Added {
+                index,
+                column: n.clone(),
+            }),
+            _ => {}
+        }
+    }
+    changes
+}
+
+fn diff_fields(
+    old: Row,
+    new: Row,
+    old_columns: &[ColumnDef],
+    new_columns: &[ColumnDef],
+) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+    for index in 0..old.field_count().max(new.field_count()) {
+        let old_field = old.field_at(index);
+        let new_field = new.field_at(index);
+        let same = match (old_field, new_field) {
+            (Some(o), Some(n)) => fields_eq(o, n),
+            (None, None) => true,
+            _ => false,
+        };
+        if !same {
+            let column = new_columns
+                .get(index)
+                .or_else(|| old_columns.get(index))
+                .map(|c| c.name.clone())
+                .unwrap_or_default();
+            changes.push(FieldChange {
+                index,
+                column,
+                old: old_field.map(|f| f.map(&mut MemToOwned)),
+                new: new_field.map(|f| f.map(&mut MemToOwned)),
+            });
+        }
+    }
+    changes
+}
+
+fn owned_fields(row: Row) -> Vec<Field> {
+    row.field_iter().map(|f| f.map(&mut MemToOwned)).collect()
+}
+
+/// Compare two fields, treating floats with the same bit pattern as equal
+fn fields_eq(a: mem::Field, b: mem::Field) -> bool {
+    match (a, b) {
+        (mem::Field::Float(x), mem::Field::Float(y)) => x.to_bits() == y.to_bits(),
+        _ => a == b,
+    }
+}
+
+fn rows_eq(a: Row, b: Row) -> bool {
+    a.field_count() == b.field_count()
+        && a.field_iter()
+            .zip(b.field_iter())
+            .all(|(x, y)| fields_eq(x, y))
+}
+
+/// A totally ordered version of a primary key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Key<'a> {
+    Nothing,
+    Integer(i32),
+    Float(u32),
+    Text(&'a Latin1Str),
+    Boolean(bool),
+    BigInt(i64),
+    VarChar(&'a Latin1Str),
+}
+
+impl<'a> From<mem::Field<'a>> for Key<'a> {
+    fn from(field: mem::Field<'a>) -> Self {
+        match field {
+            mem::Field::Nothing => Key::Nothing,
+            mem::Field::Integer(i) => Key::Integer(i),
+            mem::Field::Float(f) => Key::Float(f.to_bits()),
+            mem::Field::Text(s) => Key::Text(s),
+            mem::Field::Boolean(b) => Key::Boolean(b),
+            mem::Field::BigInt(i) => Key::BigInt(i),
+            mem::Field::VarChar(s) => Key::VarChar(s),
+        }
+    }
+}
+
+fn row_map(table: Table<'_>) -> BTreeMap<Key<'_>, Vec<Row<'_>>> {
+    let mut map: BTreeMap<Key, Vec<Row>> = BTreeMap::new();
+    for row in table.row_iter() {
+        let key = row.field_at(0).map(Key::from).unwrap_or(Key::Nothing);
+        map.entry(key).or_default().push(row);
+    }
+    map
+}
+
+fn fmt_row(f: &mut fmt::Formatter<'_>, fields: &[Field]) -> fmt::Result {
+    write!(f, "(")?;
+    for (index, field) in fields.iter().enumerate() {
+        if index > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", field)?;
+    }
+    write!(f, ")")
+}
+
+fn fmt_opt_field(f: &mut fmt::Formatter<'_>, field: &Option<Field>) -> fmt::Result {
+    match field {
+        Some(field) => write!(f, "{}", field),
+        None => write!(f, "<missing>"),
+    }
+}
+
+impl fmt::Display for TableChangeset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "~ table {}", self.name)?;
+        for change in &self.columns {
+            match change {
+                ColumnChange::Added { index, column } => {
+                    writeln!(f, "  + column #{}: {}", index, column)?
+                }
+                ColumnChange::Removed { index, column } => {
+                    writeln!(f, "  - column #{}: {}", index, column)?
+                }
+                ColumnChange::Changed { index, old, new } => {
+                    writeln!(f, "  ~ column #{}: {} -> {}", index, old, new)?
+                }
+            }
+        }
+        for row in &self.deleted {
+            write!(f, "  - row ")?;
+            fmt_row(f, row)?;
+            writeln!(f)?;
+        }
+        for row in &self.inserted {
+            write!(f, "  + row ")?;
+            fmt_row(f, row)?;
+            writeln!(f)?;
+        }
+        for update in &self.updated {
+            writeln!(f, "  ~ row {}", update.key)?;
+            for field in &update.fields {
+                write!(f, "      [{}]: ", field.column)?;
+                fmt_opt_field(f, &field.old)?;
+                write!(f, " -> ")?;
+                fmt_opt_field(f, &field.new)?;
+                writeln!(f)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for Changeset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for name in &self.tables_removed {
+            writeln!(f, "- table {}", name)?;
+        }
+        for name in &self.tables_added {
+            writeln!(f, "+ table {}", name)?;
+        }
+        for table in &self.tables {
+            write!(f, "{}", table)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, feature = "store"))]
+mod tests {
+    use super::*;
+    use crate::util::tests::{build, TableBuilder};
+
+    fn items(rows: &[(i32, &str)]) -> TableBuilder {
+        let mut table = TableBuilder::new("Items")
+            .column("id", ValueType::Integer)
+            .column("name", ValueType::Text)
+            .buckets(4);
+        for &(id, name) in rows {
+            table.row(vec![Field::Integer(id), Field::Text(name.to_string())]);
+        }
+        table
+    }
+
+    #[test]
+    fn test_diff_rows() {
+        let old = build(vec![items(&[(1, "a"), (2, "b")]), TableBuilder::new("Old")]);
+        let new = build(vec![items(&[(2, "c"), (3, "d")]), TableBuilder::new("New")]);
+        let changes =
+            diff_databases(Database::new(&old), Database::new(&new)).expect("valid databases");
+
+        assert_eq!(changes.tables_removed, vec!["Old".to_string()]);
+        assert_eq!(changes.tables_added, vec!["New".to_string()]);
+        assert_eq!(changes.tables.len(), 1);
+
+        let items = &changes.tables[0];
+        assert!(items.columns.is_empty());
+        assert_eq!(items.deleted.len(), 1);
+        assert_eq!(items.deleted[0][0], Field::Integer(1));
+        assert_eq!(items.inserted.len(), 1);
+        assert_eq!(items.inserted[0][0], Field::Integer(3));
+        assert_eq!(items.updated.len(), 1);
+        assert_eq!(items.updated[0].key, Field::Integer(2));
+        assert_eq!(items.updated[0].fields.len(), 1);
+        assert_eq!(items.updated[0].fields[0].column, "name");
+        assert_eq!(
+            items.updated[0].fields[0].new,
+            Some(Field::Text("c".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_diff_columns() {
+        let old = build(vec![items(&[])]);
+        let new = build(vec![
+            TableBuilder::new("Items").column("id", ValueType::BigInt)
+        ]);
+        let changes =
+            diff_databases(Database::new(&old), Database::new(&new)).expect("valid databases");
+
+        let items = &changes.tables[0];
+        assert_eq!(items.columns.len(), 2);
+        assert!(matches!(
+            &items.columns[0],
+            ColumnChange::Changed { index: 0, new, .. } if new.value_type == ValueType::BigInt
+        ));
+        assert!(matches!(
+            &items.columns[1],
+            ColumnChange::Removed { index: 1, column } if column.name == "name"
+        ));
+    }
+}
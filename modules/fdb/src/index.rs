@@ -0,0 +1,594 @@
+//! # Secondary indexes
+//!
+//! The rows of a table can only be found efficiently by their primary key,
+//! using the hash buckets of the table. An [`Index`] maps the values of any
+//! other column to the rows that contain them.
+//!
+//! ```
+//! use assembly_fdb::{index::Index, mem::Table};
+//!
+//! fn components(table: Table, component_type: i32) -> Result<(), Box<dyn std::error::Error>> {
+//!     let index = Index::build(table, "component_type")?;
+//!     for row in index.lookup(table, component_type) {
+//!         println!("{:?}", row.field_at(0));
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Rows are referenced by their bucket and their position in that bucket, so
+//! an index can be serialized and reused for the same file. Use [`Index::matches`]
+//! to check that a cached index still fits the table.
+
+use std::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+    ops::{Bound, RangeBounds},
+};
+
+use assembly_fdb_core::value::{Context, Value, ValueType};
+use displaydoc::Display;
+use thiserror::Error;
+
+use crate::mem::{Row, Table};
+
+/// Errors when building or querying an index
+#[derive(Debug, Error, Display)]
+pub enum IndexError {
+    /// Column {column:?} does not exist in table {table:?}
+    MissingColumn {
+        /// The name of the table
+        table: String,
+        /// The name of the column
+        column: String,
+    },
+    /// Column {column:?} has type {value_type}, which does not support range queries
+    Unordered {
+        /// The name of the column
+        column: String,
+        /// The type of the column
+        value_type: ValueType,
+    },
+    /// Column {column:?} has type {value_type}, which can't be compared to {key:?}
+    KeyType {
+        /// The name of the column
+        column: String,
+        /// The type of the column
+        value_type: ValueType,
+        /// The key of the query
+        key: Key,
+    },
+}
+
+/// A value in an index
+///
+/// `Integer` and `BigInt` values are both stored as `Integer`, `Text` and
+/// `VarChar` values are both stored as `Text`.
+///
+/// Floats are compared with [`f32::total_cmp`], so `NaN` is equal to itself
+/// and `-0.0` is not equal to `0.0`.
+#[derive(Debug, Clone)]
+#[cfg_attr(
+    feature = "serde-derives",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub enum Key {
+    /// The NULL value
+    Nothing,
+    /// An integer
+    Integer(i64),
+    /// A float
+    Float(f32),
+    /// A boolean
+    Boolean(bool),
+    /// A string
+    Text(String),
+}
+
+impl Key {
+    pub(crate) fn rank(&self) -> u8 {
+        match self {
+            Key::Nothing => 0,
+            Key::Integer(_) => 1,
+            Key::Float(_) => 2,
+            Key::Boolean(_) => 3,
+            Key::Text(_) => 4,
+        }
+    }
+}
+
+impl PartialEq for Key {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Key {}
+
+// Two floats are equal under `total_cmp` exactly if their bits are equal,
+// so hashing the bits agrees with `PartialEq`.
+impl Hash for Key {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.rank().hash(state);
+        match self {
+            Key::Nothing => {}
+            Key::Integer(i) => i.hash(state),
+            Key::Float(f) => f.to_bits().hash(state),
+            Key::Boolean(b) => b.hash(state),
+            Key::Text(s) => s.hash(state),
+        }
+    }
+}
+
+impl PartialOrd for Key {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Key {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Key::Integer(a), Key::Integer(b)) => a.cmp(b),
+            (Key::Float(a), Key::Float(b)) => a.total_cmp(b),
+            (Key::Boolean(a), Key::Boolean(b)) => a.cmp(b),
+            (Key::Text(a), Key::Text(b)) => a.cmp(b),
+            _ => self.rank().cmp(&other.rank()),
+        }
+    }
+}
+
+impl<C: Context> From<&Value<C>> for Key
+where
+    C::String: AsRef<str>,
+    C::I64: Copy + Into<i64>,
+    C::XML: AsRef<str>,
+{
+    fn from(value: &Value<C>) -> Self {
+        match value {
+            Value::Nothing => Key::Nothing,
+            Value::Integer(i) => Key::Integer(i64::from(*i)),
+            Value::Float(f) => Key::Float(*f),
+            Value::Text(s) => Key::Text(s.as_ref().to_owned()),
+            Value::Boolean(b) => Key::Boolean(*b),
+            Value::BigInt(i) => Key::Integer((*i).into()),
+            Value::VarChar(s) => Key::Text(s.as_ref().to_owned()),
+        }
+    }
+}
+
+impl From<crate::mem::Field<'_>> for Key {
+    fn from(value: crate::mem::Field<'_>) -> Self {
+        match value {
+            Value::Nothing => Key::Nothing,
+            Value::Integer(i) => Key::Integer(i64::from(i)),
+            Value::Float(f) => Key::Float(f),
+            Value::Text(s) => Key::Text(s.decode().into_owned()),
+            Value::Boolean(b) => Key::Boolean(b),
+            Value::BigInt(i) => Key::Integer(i),
+            Value::VarChar(s) => Key::Text(s.decode().into_owned()),
+        }
+    }
+}
+
+impl From<i32> for Key {
+    fn from(value: i32) -> Self {
+        Key::Integer(i64::from(value))
+    }
+}
+
+impl From<i64> for Key {
+    fn from(value: i64) -> Self {
+        Key::Integer(value)
+    }
+}
+
+impl From<f32> for Key {
+    fn from(value: f32) -> Self {
+        Key::Float(value)
+    }
+}
+
+impl From<bool> for Key {
+    fn from(value: bool) -> Self {
+        Key::Boolean(value)
+    }
+}
+
+impl From<&str> for Key {
+    fn from(value: &str) -> Self {
+        Key::Text(value.to_owned())
+    }
+}
+
+impl From<String> for Key {
+    fn from(value: String) -> Self {
+        Key::Text(value)
+    }
+}
+
+/// Reference to a row by its bucket and position in that bucket
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde-derives",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct RowRef {
+    /// The index of the bucket
+    pub bucket: u32,
+    /// The position in the linked list of the bucket
+    pub position: u32,
+}
+
+impl RowRef {
+    /// Get the row from the table
+    pub fn resolve<'a>(self, table: Table<'a>) -> Option<Row<'a>> {
+        table
+            .bucket_at(self.bucket as usize)?
+            .row_iter()
+            .nth(self.position as usize)
+    }
+}
+
+/// All rows with the same key
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde-derives",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Entry {
+    /// The value of the column
+    pub key: Key,
+    /// The rows that contain the value
+    pub rows: Vec<RowRef>,
+}
+
+/// An index over one column of a table
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(
+    feature = "serde-derives",
+    derive(serde::Serialize, serde::Deserialize)
+)]
+pub struct Index {
+    table: String,
+    column: String,
+    column_index: usize,
+    value_type: ValueType,
+    bucket_count: usize,
+    row_count: usize,
+    /// Sorted by key
+    entries: Vec<Entry>,
+}
+
+impl Index {
+    /// Build an index over the column `column` of `table`
+    pub fn build(table: Table<'_>, column: &str) -> Result<Self, IndexError> {
+        let (column_index, value_type) = table
+            .column_iter()
+            .enumerate()
+            .find(|(_, c)| c.name() == column)
+            .map(|(i, c)| (i, c.value_type()))
+            .ok_or_else(|| IndexError::MissingColumn {
+                table: table.name().into_owned(),
+                column: column.to_owned(),
+            })?;
+
+        let mut pairs = Vec::new();
+        for (bucket, b) in table.bucket_iter().enumerate() {
+            for (position, row) in b.row_iter().enumerate() {
+                let key = row.field_at(column_index).map_or(Key::Nothing, Key::from);
+                let row_ref = RowRef {
+                    bucket: bucket as u32,
+                    position: position as u32,
+                };
+                pairs.push((key, row_ref));
+            }
+        }
+        let row_count = pairs.len();
+        pairs.sort();
+
+        let mut entries: Vec<Entry> = Vec::new();
+        for (key, row_ref) in pairs {
+            match entries.last_mut() {
+                Some(entry) if entry.key == key => entry.rows.push(row_ref),
+                _ => entries.push(Entry {
+                    key,
+                    rows: vec![row_ref],
+                }),
+            }
+        }
+
+        Ok(Self {
+            table: table.name().into_owned(),
+            column: column.to_owned(),
+            column_index,
+            value_type,
+            bucket_count: table.bucket_count(),
+            row_count,
+            entries,
+        })
+    }
+
+    /// Check whether this index was built for `table`
+    ///
+    /// This compares the name, the column, the number of buckets and the number
+    /// of rows, but not the values.
+    pub fn matches(&self, table: Table<'_>) -> bool {
+        table.name() == self.table
+            && table.bucket_count() == self.bucket_count
+            && table
+                .column_at(self.column_index)
+                .is_some_and(|c| c.name() == self.column && c.value_type() == self.value_type)
+            && table.row_iter().count() == self.row_count
+    }
+
+    /// Get the name of the table
+    pub fn table_name(&self) -> &str {
+        &self.table
+    }
+
+    /// Get the name of the column
+    pub fn column_name(&self) -> &str {
+        &self.column
+    }
+
+    /// Get the type of the column
+    pub fn value_type(&self) -> ValueType {
+        self.value_type
+    }
+
+    /// Get all distinct values and their rows, sorted by value
+    pub fn entries(&self) -> &[Entry] {
+        &self.entries
+    }
+
+    /// Convert a key to the representation used for this column
+    ///
+    /// Integral floats are converted to integers for `Integer` and `BigInt`
+    /// columns. Returns an error if no value of the column can be equal to `key`.
+    fn coerce(&self, key: Key) -> Result<Key, IndexError> {
+        match (self.value_type, key) {
+            (_, Key::Nothing) => Ok(Key::Nothing),
+            (ValueType::Integer | ValueType::BigInt, key @ Key::Integer(_)) => Ok(key),
+            (ValueType::Integer | ValueType::BigInt, Key::Float(f))
+                if f.fract() == 0.0 && f >= i64::MIN as f32 && f < i64::MAX as f32 =>
+            {
+                Ok(Key::Integer(f as i64))
+            }
+            (ValueType::Float, Key::Integer(i)) => Ok(Key::Float(i as f32)),
+            (ValueType::Float, key @ Key::Float(_))
+            | (ValueType::Boolean, key @ Key::Boolean(_))
+            | (ValueType::Text | ValueType::VarChar, key @ Key::Text(_)) => Ok(key),
+            (value_type, key) => Err(IndexError::KeyType {
+                column: self.column.clone(),
+                value_type,
+                key,
+            }),
+        }
+    }
+
+    /// Get all rows where the column is equal to `key`
+    ///
+    /// This is empty if no value of the column can be equal to `key`, e.g. for
+    /// the float `1.5` on an `Integer` column.
+    pub fn get<K: Into<Key>>(&self, key: K) -> &[RowRef] {
+        let key = match self.coerce(key.into()) {
+            Ok(key) => key,
+            Err(_) => return &[],
+        };
+        match self.entries.binary_search_by(|e| e.key.cmp(&key)) {
+            Ok(index) => &self.entries[index].rows,
+            Err(_) => &[],
+        }
+    }
+
+    /// Get all rows where the column is in `range`
+    ///
+    /// This is only supported for integer and float columns, and the bounds
+    /// must be numbers that can be values of the column. NULL values are never
+    /// part of a range.
+    pub fn range<K, R>(&self, range: R) -> Result<impl Iterator<Item = RowRef> + '_, IndexError>
+    where
+        K: Into<Key> + Clone,
+        R: RangeBounds<K>,
+    {
+        if !matches!(
+            self.value_type,
+            ValueType::Integer | ValueType::BigInt | ValueType::Float
+        ) {
+            return Err(IndexError::Unordered {
+                column: self.column.clone(),
+                value_type: self.value_type,
+            });
+        }
+
+        let start = match range.start_bound() {
+            Bound::Included(k) => {
+                let k = self.coerce(k.clone().into())?;
+                self.entries.partition_point(|e| e.key < k)
+            }
+            Bound::Excluded(k) => {
+                let k = self.coerce(k.clone().into())?;
+                self.entries.partition_point(|e| e.key <= k)
+            }
+            Bound::Unbounded => self.entries.partition_point(|e| e.key == Key::Nothing),
+        };
+        let end = match range.end_bound() {
+            Bound::Included(k) => {
+                let k = self.coerce(k.clone().into())?;
+                self.entries.partition_point(|e| e.key <= k)
+            }
+            Bound::Excluded(k) => {
+                let k = self.coerce(k.clone().into())?;
+                self.entries.partition_point(|e| e.key < k)
+            }
+            Bound::Unbounded => self.entries.len(),
+        };
+        let entries = self.entries.get(start..end).unwrap_or(&[]);
+        Ok(entries
+            .iter()
+            .filter(|e| e.key != Key::Nothing)
+            .flat_map(|e| e.rows.iter().copied()))
+    }
+
+    /// Get all rows of `table` where the column is equal to `key`
+    pub fn lookup<'s, 'a: 's, K: Into<Key>>(
+        &'s self,
+        table: Table<'a>,
+        key: K,
+    ) -> impl Iterator<Item = Row<'a>> + 's {
+        self.get(key)
+            .iter()
+            .filter_map(move |row_ref| row_ref.resolve(table))
+    }
+}
+
+#[cfg(all(test, feature = "store"))]
+mod tests {
+    use super::*;
+    use crate::{
+        mem::Database,
+        util::tests::{build, TableBuilder},
+        value::owned::Field,
+    };
+
+    fn components() -> Vec<u8> {
+        let mut table = TableBuilder::new("ComponentsRegistry")
+            .column("id", ValueType::Integer)
+            .column("component_type", ValueType::Integer)
+            .column("weight", ValueType::Float)
+            .column("name", ValueType::Text);
+        for (id, component_type, weight, name) in [
+            (1, Some(7), 0.5, "a"),
+            (1, Some(2), 1.5, "b"),
+            (2, Some(7), 2.5, "a"),
+            (3, None, 3.5, "c"),
+            (4, Some(9), -1.0, "a"),
+        ] {
+            table.row(vec![
+                Field::Integer(id),
+                component_type.map_or(Field::Nothing, Field::Integer),
+                Field::Float(weight),
+                Field::Text(name.to_string()),
+            ]);
+        }
+        build(vec![table])
+    }
+
+    fn ids(table: Table<'_>, rows: impl Iterator<Item = RowRef>) -> Vec<i32> {
+        let mut ids: Vec<i32> = rows
+            .map(|r| match r.resolve(table).unwrap().field_at(0) {
+                Some(Value::Integer(i)) => i,
+                _ => panic!(),
+            })
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+
+    #[test]
+    fn test_eq() {
+        let buf = components();
+        let table = Database::new(&buf)
+            .tables()
+            .unwrap()
+            .by_name("ComponentsRegistry")
+            .unwrap()
+            .unwrap();
+        let index = Index::build(table, "component_type").unwrap();
+        assert!(index.matches(table));
+        assert_eq!(ids(table, index.get(7).iter().copied()), vec![1, 2]);
+        assert_eq!(ids(table, index.get(Key::Nothing).iter().copied()), vec![3]);
+        assert!(index.get(8).is_empty());
+        assert_eq!(ids(table, index.get(7.0).iter().copied()), vec![1, 2]);
+        assert!(index.get(7.5).is_empty());
+        assert!(index.get("7").is_empty());
+        assert_eq!(index.lookup(table, 9).count(), 1);
+
+        let names = Index::build(table, "name").unwrap();
+        assert_eq!(names.get("a").len(), 3);
+        assert!(names.range(0..1).is_err());
+        assert!(Index::build(table, "missing").is_err());
+    }
+
+    #[test]
+    fn test_key_eq() {
+        let nan = Key::Float(f32::NAN);
+        assert_eq!(nan, nan.clone());
+        assert_eq!(nan.cmp(&nan.clone()), Ordering::Equal);
+        assert_ne!(Key::Float(0.0), Key::Float(-0.0));
+        assert_ne!(Key::Integer(1), Key::Float(1.0));
+    }
+
+    #[test]
+    fn test_key_hash() {
+        use std::collections::HashSet;
+
+        let keys: HashSet<Key> = vec![
+            Key::Float(f32::NAN),
+            Key::Float(f32::NAN),
+            Key::Float(0.0),
+            Key::Float(-0.0),
+            Key::Nothing,
+            Key::Nothing,
+        ]
+        .into_iter()
+        .collect();
+        assert_eq!(keys.len(), 4);
+        assert!(keys.contains(&Key::Float(f32::NAN)));
+        assert!(keys.contains(&Key::Float(-0.0)));
+    }
+
+    #[test]
+    fn test_range() {
+        let buf = components();
+        let table = Database::new(&buf)
+            .tables()
+            .unwrap()
+            .by_name("ComponentsRegistry")
+            .unwrap()
+            .unwrap();
+        let index = Index::build(table, "component_type").unwrap();
+        assert_eq!(ids(table, index.range(..8).unwrap()), vec![1, 1, 2]);
+        assert_eq!(ids(table, index.range(7..=9).unwrap()), vec![1, 2, 4]);
+        assert_eq!(
+            ids(table, index.range::<i32, _>(..).unwrap()),
+            vec![1, 1, 2, 4]
+        );
+
+        let weights = Index::build(table, "weight").unwrap();
+        assert_eq!(ids(table, weights.range(0.0..2.0).unwrap()), vec![1, 1]);
+        assert_eq!(ids(table, weights.range(2..).unwrap()), vec![2, 3]);
+
+        // Integral floats are integers, others can't be compared
+        assert_eq!(ids(table, index.range(7.0..=9.0).unwrap()), vec![1, 2, 4]);
+        assert!(matches!(
+            index.range(7.5..),
+            Err(IndexError::KeyType {
+                key: Key::Float(_),
+                ..
+            })
+        ));
+        assert!(matches!(
+            index.range(Key::Text(String::from("7"))..),
+            Err(IndexError::KeyType { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "serde-derives")]
+    fn test_serde() {
+        let buf = components();
+        let table = Database::new(&buf)
+            .tables()
+            .unwrap()
+            .by_name("ComponentsRegistry")
+            .unwrap()
+            .unwrap();
+        let index = Index::build(table, "name").unwrap();
+        let json = serde_json::to_string(&index).unwrap();
+        let cached: Index = serde_json::from_str(&json).unwrap();
+        assert_eq!(cached, index);
+        assert!(cached.matches(table));
+    }
+}
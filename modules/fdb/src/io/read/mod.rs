@@ -197,7 +197,7 @@ where
                     Some(Ok(entry.row_header_addr))
                 }
                 Err(e) => {
-                    self.next_addr = std::u32::MAX;
+                    self.next_addr = u32::MAX;
                     Some(Err(e))
                 }
             },
@@ -42,20 +42,38 @@
 #![doc(html_favicon_url = "https://assembly.lu-dev.net/rust-logo-lu-256.png")]
 #![warn(missing_docs)]
 
+#[cfg(feature = "arrow")]
+pub mod arrow;
+pub mod codegen;
 #[cfg(feature = "core")]
 pub mod core;
+pub mod diff;
+pub mod index;
 #[cfg(any(feature = "io-read", feature = "io-write"))]
 pub mod io;
 pub mod mem;
+#[cfg(feature = "store")]
+pub mod merge;
+pub mod profile;
 pub mod query;
+#[cfg(feature = "store")]
+pub mod rehash;
 #[cfg(feature = "ro")]
 pub mod ro;
 #[cfg(feature = "store")]
 pub mod store;
+#[cfg(any(feature = "csv", feature = "ndjson"))]
+pub mod text;
+pub mod typed;
+pub mod validate;
 
 mod handle;
 mod util;
 
+// Allows the `FdbRow` derive and the output of `codegen` to be used within this crate's tests
+#[cfg(all(test, any(feature = "derive", feature = "store")))]
+extern crate self as assembly_fdb;
+
 // Re-exports
 pub use assembly_fdb_core::{file, value, FdbHash};
 
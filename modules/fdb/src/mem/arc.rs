@@ -0,0 +1,349 @@
+//! # Owned handles
+//!
+//! The types in [`mem`](super) borrow the buffer of the database. This module
+//! contains an [`ArcDatabase`] that owns its buffer through an [`Arc`], and
+//! handles that keep that buffer alive. All of them are `Send + Sync + 'static`,
+//! so they can be stored in shared state and moved across threads.
+//!
+//! ```
+//! use assembly_fdb::mem::ArcDatabase;
+//!
+//! # let buf = vec![0, 0, 0, 0, 8, 0, 0, 0];
+//! let db = ArcDatabase::new(buf);
+//! let tables = db.tables()?;
+//!
+//! std::thread::spawn(move || {
+//!     for table in tables.iter() {
+//!         let table = table.unwrap();
+//!         println!("{}: {} rows", table.name(), table.row_iter().count());
+//!     }
+//! })
+//! .join()
+//! .unwrap();
+//! # Ok::<(), assembly_core::buffer::CastError>(())
+//! ```
+//!
+//! The borrowed API is available with the `as_mem` methods, which return a
+//! handle that is bounded by the lifetime of the owned handle.
+
+use std::{borrow::Cow, fmt, sync::Arc};
+
+use assembly_core::buffer::CastError;
+use latin1str::Latin1Str;
+
+use super::{
+    iter::{FieldIter, TableIter, TableRowIter},
+    Column, Database, Field, Row, Table, Tables,
+};
+
+/// Keeps the buffer alive
+type Owner = Arc<dyn Send + Sync>;
+
+/// An owned, reference counted database
+///
+/// Cloning this is cheap and does not copy the buffer.
+#[derive(Clone)]
+pub struct ArcDatabase {
+    owner: Owner,
+    // SAFETY: The buffer is owned by `owner` and never mutated or moved, so
+    // the references stay valid for as long as `owner` is alive. The `'static`
+    // lifetime is never exposed outside of this module.
+    inner: Database<'static>,
+}
+
+impl fmt::Debug for ArcDatabase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArcDatabase")
+            .field("len", &self.inner.as_bytes().len())
+            .finish()
+    }
+}
+
+impl ArcDatabase {
+    /// Create a database that owns `buf`
+    ///
+    /// This can be a `Vec<u8>`, a `Box<[u8]>`, a memory map or a `&'static [u8]`.
+    pub fn new<B>(buf: B) -> Self
+    where
+        B: AsRef<[u8]> + Send + Sync + 'static,
+    {
+        Self::from_arc(Arc::new(buf))
+    }
+
+    /// Create a database from a shared buffer, e.g. an `Arc<[u8]>` or an `Arc<Mmap>`
+    pub fn from_arc<B>(buf: Arc<B>) -> Self
+    where
+        B: AsRef<[u8]> + Send + Sync + ?Sized + 'static,
+    {
+        let bytes: &[u8] = (*buf).as_ref();
+        // SAFETY: see the comment on `inner`
+        let bytes: &'static [u8] = unsafe { &*(bytes as *const [u8]) };
+        Self {
+            owner: Arc::new(buf),
+            inner: Database::new(bytes),
+        }
+    }
+
+    /// Get the borrowed database
+    pub fn as_mem(&self) -> Database<'_> {
+        self.inner
+    }
+
+    /// Get the byte slice of the whole file
+    pub fn as_bytes(&self) -> &[u8] {
+        self.inner.as_bytes()
+    }
+
+    /// Returns a reference to the tables array
+    pub fn tables(&self) -> Result<ArcTables, CastError> {
+        Ok(ArcTables {
+            owner: self.owner.clone(),
+            inner: self.inner.tables()?,
+        })
+    }
+}
+
+impl From<Database<'static>> for ArcDatabase {
+    fn from(db: Database<'static>) -> Self {
+        Self::new(db.as_bytes())
+    }
+}
+
+/// Owned reference to the tables array
+#[derive(Clone)]
+pub struct ArcTables {
+    owner: Owner,
+    inner: Tables<'static>,
+}
+
+impl ArcTables {
+    fn wrap(&self, table: Table<'static>) -> ArcTable {
+        ArcTable {
+            owner: self.owner.clone(),
+            inner: table,
+        }
+    }
+
+    /// Get the borrowed tables array
+    pub fn as_mem(&self) -> Tables<'_> {
+        self.inner
+    }
+
+    /// Returns the length of the tables array
+    pub fn len(&self) -> usize {
+        self.inner.len()
+    }
+
+    /// Checks whether the tables array is empty
+    pub fn is_empty(&self) -> bool {
+        self.inner.is_empty()
+    }
+
+    /// Get the table at the specified index
+    pub fn get(&self, index: usize) -> Option<Result<ArcTable, CastError>> {
+        let table = self.inner.get(index)?;
+        Some(table.map(|t| self.wrap(t)))
+    }
+
+    /// Get an iterator over all tables
+    pub fn iter(&self) -> ArcTableIter {
+        ArcTableIter {
+            owner: self.owner.clone(),
+            inner: self.inner.iter(),
+        }
+    }
+
+    /// Get a table by its name
+    pub fn by_name(&self, name: &str) -> Option<Result<ArcTable, CastError>> {
+        let table = self.inner.by_name(name)?;
+        Some(table.map(|t| self.wrap(t)))
+    }
+}
+
+/// Iterator created by [`ArcTables::iter`]
+#[derive(Clone)]
+pub struct ArcTableIter {
+    owner: Owner,
+    inner: TableIter<'static>,
+}
+
+impl Iterator for ArcTableIter {
+    type Item = Result<ArcTable, CastError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let table = self.inner.next()?;
+        Some(table.map(|inner| ArcTable {
+            owner: self.owner.clone(),
+            inner,
+        }))
+    }
+}
+
+/// Owned reference to a single table
+#[derive(Clone)]
+pub struct ArcTable {
+    owner: Owner,
+    inner: Table<'static>,
+}
+
+impl ArcTable {
+    /// Get the borrowed table
+    pub fn as_mem(&self) -> Table<'_> {
+        self.inner
+    }
+
+    /// Get the undecoded name of the table
+    pub fn name_raw(&self) -> &Latin1Str {
+        self.inner.name_raw()
+    }
+
+    /// Get the name of the table
+    pub fn name(&self) -> Cow<'_, str> {
+        self.inner.name()
+    }
+
+    /// Get the column at the index
+    pub fn column_at(&self, index: usize) -> Option<Column<'_>> {
+        self.as_mem().column_at(index)
+    }
+
+    /// Get the column iterator
+    pub fn column_iter(&self) -> impl Iterator<Item = Column<'_>> + Clone + '_ {
+        self.as_mem().column_iter()
+    }
+
+    /// The amount of columns in this table
+    pub fn column_count(&self) -> usize {
+        self.inner.column_count()
+    }
+
+    /// Get the amount of buckets
+    pub fn bucket_count(&self) -> usize {
+        self.inner.bucket_count()
+    }
+
+    /// Get an iterator over all rows
+    pub fn row_iter(&self) -> ArcRowIter {
+        ArcRowIter {
+            owner: self.owner.clone(),
+            inner: self.inner.row_iter(),
+        }
+    }
+
+    /// Get a list of all rows in the bucket of a given index
+    pub fn bucket_index_iter(&self, id: u32) -> ArcRowIter {
+        ArcRowIter {
+            owner: self.owner.clone(),
+            inner: self.inner.bucket_index_iter(id),
+        }
+    }
+}
+
+/// Iterator created by [`ArcTable::row_iter`]
+#[derive(Clone)]
+pub struct ArcRowIter {
+    owner: Owner,
+    inner: TableRowIter<'static>,
+}
+
+impl Iterator for ArcRowIter {
+    type Item = ArcRow;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let inner = self.inner.next()?;
+        Some(ArcRow {
+            _owner: self.owner.clone(),
+            inner,
+        })
+    }
+}
+
+/// Owned reference to a single row
+#[derive(Clone)]
+pub struct ArcRow {
+    _owner: Owner,
+    inner: Row<'static>,
+}
+
+impl ArcRow {
+    /// Get the borrowed row
+    pub fn as_mem(&self) -> Row<'_> {
+        self.inner
+    }
+
+    /// Get the field at the index
+    pub fn field_at(&self, index: usize) -> Option<Field<'_>> {
+        self.as_mem().field_at(index)
+    }
+
+    /// Get the iterator over all fields
+    pub fn field_iter(&self) -> FieldIter<'_> {
+        self.as_mem().field_iter()
+    }
+
+    /// Get the count of fields
+    pub fn field_count(&self) -> usize {
+        self.inner.field_count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_send_sync<T: Send + Sync + 'static>() {}
+
+    #[test]
+    fn test_send_sync() {
+        assert_send_sync::<ArcDatabase>();
+        assert_send_sync::<ArcTables>();
+        assert_send_sync::<ArcTable>();
+        assert_send_sync::<ArcRowIter>();
+        assert_send_sync::<ArcRow>();
+    }
+
+    #[test]
+    #[cfg(feature = "store")]
+    fn test_rows() {
+        use crate::{
+            util::tests::{build, TableBuilder},
+            value::owned,
+        };
+        use assembly_fdb_core::value::ValueType;
+
+        let mut table = TableBuilder::new("Rows")
+            .column("id", ValueType::Integer)
+            .column("name", ValueType::Text);
+        for id in 0..4 {
+            table.row(vec![
+                owned::Field::Integer(id),
+                owned::Field::Text(format!("row {}", id)),
+            ]);
+        }
+        let buf = build(vec![table]);
+
+        let table = ArcDatabase::new(buf)
+            .tables()
+            .unwrap()
+            .by_name("Rows")
+            .unwrap()
+            .unwrap();
+        let rows: Vec<ArcRow> = std::thread::spawn(move || table.bucket_index_iter(2).collect())
+            .join()
+            .unwrap();
+        assert_eq!(rows.len(), 1);
+        assert_eq!(rows[0].field_at(0), Some(Field::Integer(2)));
+        assert_eq!(
+            rows[0].field_iter().nth(1),
+            Some(Field::Text(Latin1Str::from_bytes_until_nul(b"row 2\0")))
+        );
+    }
+
+    #[test]
+    fn test_outlives_buffer() {
+        let buf: Arc<[u8]> = Arc::from(&[0u8, 0, 0, 0, 8, 0, 0, 0][..]);
+        let tables = ArcDatabase::from_arc(buf).tables().unwrap();
+        assert!(tables.is_empty());
+        assert!(tables.by_name("Foo").is_none());
+    }
+}
@@ -23,6 +23,7 @@ mod c;
 //use super::ro::{Handle, RefHandle, TryFromHandle};
 use crate::{
     handle::{self, Handle, RefHandle, TryFromHandle},
+    typed::{FieldError, FieldErrorKind, FromField},
     util::compare_bytes,
 };
 use c::{
@@ -34,8 +35,10 @@ use std::{
     convert::{Infallible, TryFrom},
 };
 
+pub mod arc;
 pub mod iter;
 
+pub use arc::ArcDatabase;
 use iter::{BucketIter, TableRowIter};
 pub use iter::{FieldIter, RowHeaderIter, TableIter}; // < FIXME> remove with next major update
 
@@ -60,6 +63,11 @@ impl<'a> Database<'a> {
         Self { inner }
     }
 
+    /// Get the byte slice of the whole file
+    pub fn as_bytes(self) -> &'a [u8] {
+        self.inner.mem
+    }
+
     /// Get a reference to the header
     pub fn header(self) -> Result<Header<'a>, CastError> {
         let inner = self.inner.try_map_cast(0)?;
@@ -362,7 +370,7 @@ fn get_field<'a>(buf: &'a [u8], data: &'a FDBFieldDataC) -> Field<'a> {
     get_field_raw(buf, data_type, bytes)
 }
 
-fn get_field_raw(buf: &[u8], data_type: ValueType, bytes: [u8; 4]) -> Field {
+fn get_field_raw(buf: &[u8], data_type: ValueType, bytes: [u8; 4]) -> Field<'_> {
     match data_type {
         ValueType::Nothing => Field::Nothing,
         ValueType::Integer => Field::Integer(i32::from_le_bytes(bytes)),
@@ -405,6 +413,57 @@ impl<'a> Row<'a> {
     pub fn field_count(&self) -> usize {
         self.inner.raw().len()
     }
+
+    /// Get the field at `index` as `T`
+    ///
+    /// The row doesn't know its table, so the error has no table name and
+    /// calls the column `#<index>`. Use [`Row::get_by_name`] for errors that
+    /// name both.
+    ///
+    /// ```
+    /// # use assembly_fdb::mem::Row;
+    /// # fn example(row: Row) -> Result<(), assembly_fdb::typed::FieldError> {
+    /// let id: i32 = row.get(0)?;
+    /// let name = row.get::<Option<String>>(1)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn get<T: FromField<'a>>(&self, index: usize) -> Result<T, FieldError> {
+        let error = |kind| FieldError {
+            table: None,
+            column: format!("#{}", index),
+            kind,
+        };
+        let field = self
+            .field_at(index)
+            .ok_or_else(|| error(FieldErrorKind::MissingField))?;
+        T::from_value(field).map_err(|actual| {
+            error(FieldErrorKind::Type {
+                expected: T::VALUE_TYPE,
+                actual,
+            })
+        })
+    }
+
+    /// Get the field of the column `name` as `T`
+    ///
+    /// The column is looked up in `table`, which should be the table of this row.
+    pub fn get_by_name<T: FromField<'a>>(
+        &self,
+        table: Table<'a>,
+        name: &str,
+    ) -> Result<T, FieldError> {
+        let error = |kind| FieldError {
+            table: Some(table.name().into_owned()),
+            column: name.to_owned(),
+            kind,
+        };
+        let index = table
+            .column_iter()
+            .position(|c| c.name() == name)
+            .ok_or_else(|| error(FieldErrorKind::MissingColumn))?;
+        self.get(index).map_err(|e| error(e.kind))
+    }
 }
 
 impl<'a> IntoIterator for Row<'a> {
@@ -0,0 +1,727 @@
+//! # Merge databases
+//!
+//! This module combines a base database with one or more overlays, e.g. the
+//! official CDClient with the extra rows of a server project. The databases
+//! are added as layers to a [`Merger`], starting with the base. Rows are
+//! matched by the value of their first field (the primary key), and rows with
+//! a key that already exists are handled according to the [`ConflictPolicy`]
+//! of their table.
+//!
+//! Columns are matched by name. Tables that only exist in an overlay are added
+//! to the result, and with [`SchemaPolicy::Extend`] columns that only exist in
+//! an overlay are appended to the table, with `NULL` in all existing rows.
+//!
+//! ```
+//! use assembly_fdb::{
+//!     mem::Database,
+//!     merge::{ConflictPolicy, MergeError, MergeOptions, Merger},
+//! };
+//!
+//! fn merge(base: Database, overlay: Database) -> Result<(), MergeError> {
+//!     let options = MergeOptions::new().table("LootMatrix", ConflictPolicy::Append);
+//!     let mut merger = Merger::new(options);
+//!     merger.add("cdclient", base)?;
+//!     merger.add("server", overlay)?;
+//!     let (db, report) = merger.finish();
+//!     for table in &report.tables {
+//!         println!("{}: {} rows replaced", table.name, table.replaced.len());
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use std::collections::{BTreeMap, HashMap};
+
+use assembly_core::buffer::CastError;
+use assembly_fdb_core::value::{owned::Field, ValueType};
+use displaydoc::Display;
+use latin1str::Latin1String;
+use thiserror::Error;
+
+use crate::{
+    index::Key,
+    mem::{self, MemToOwned},
+    store,
+};
+
+/// Errors when merging databases
+#[derive(Debug, Error, Display)]
+pub enum MergeError {
+    /// Failed to load layer {layer:?}: {source}
+    Cast {
+        /// The name of the layer
+        layer: String,
+        /// The error
+        source: CastError,
+    },
+    /// Layer {layer:?} has a row with the existing key {key:?} in table {table:?}
+    Conflict {
+        /// The name of the layer
+        layer: String,
+        /// The name of the table
+        table: String,
+        /// The primary key of the row
+        key: Key,
+    },
+    /// Layer {layer:?} declares column {table}.{column} as {found}, expected {expected}
+    ColumnType {
+        /// The name of the layer
+        layer: String,
+        /// The name of the table
+        table: String,
+        /// The name of the column
+        column: String,
+        /// The type of the column in the previous layers
+        expected: ValueType,
+        /// The type of the column in this layer
+        found: ValueType,
+    },
+    /// Layer {layer:?} has different columns for table {table:?}
+    Columns {
+        /// The name of the layer
+        layer: String,
+        /// The name of the table
+        table: String,
+    },
+}
+
+/// What to do with rows of a layer that have a key that already exists
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub enum ConflictPolicy {
+    /// Replace all existing rows with that key
+    OverlayWins,
+    /// Keep the existing rows and skip the new ones
+    BaseWins,
+    /// Fail with [`MergeError::Conflict`]
+    Error,
+    /// Keep the existing rows and add the new ones, for tables with multiple rows per key
+    Append,
+}
+
+/// What to do with tables that have different columns in a layer
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum SchemaPolicy {
+    /// Fail with [`MergeError::Columns`] unless the columns are the same
+    Strict,
+    /// Append new columns to the table, and use `NULL` for missing columns
+    Extend,
+}
+
+/// Options for a [`Merger`]
+#[derive(Debug, Clone)]
+pub struct MergeOptions {
+    conflicts: ConflictPolicy,
+    tables: BTreeMap<String, ConflictPolicy>,
+    schema: SchemaPolicy,
+}
+
+impl Default for MergeOptions {
+    fn default() -> Self {
+        Self {
+            conflicts: ConflictPolicy::OverlayWins,
+            tables: BTreeMap::new(),
+            schema: SchemaPolicy::Extend,
+        }
+    }
+}
+
+impl MergeOptions {
+    /// Create the default options
+    ///
+    /// These use [`ConflictPolicy::OverlayWins`] and [`SchemaPolicy::Extend`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the conflict policy for all tables without an explicit one
+    pub fn conflicts(mut self, policy: ConflictPolicy) -> Self {
+        self.conflicts = policy;
+        self
+    }
+
+    /// Set the conflict policy for a single table
+    pub fn table(mut self, name: &str, policy: ConflictPolicy) -> Self {
+        self.tables.insert(name.to_owned(), policy);
+        self
+    }
+
+    /// Set how tables with different columns are handled
+    pub fn schema(mut self, policy: SchemaPolicy) -> Self {
+        self.schema = policy;
+        self
+    }
+
+    /// Get the conflict policy for the table `name`
+    pub fn policy_for(&self, name: &str) -> ConflictPolicy {
+        self.tables.get(name).copied().unwrap_or(self.conflicts)
+    }
+}
+
+/// A database that can be added to a [`Merger`]
+#[derive(Copy, Clone)]
+pub enum Source<'a> {
+    /// A database file
+    Mem(mem::Database<'a>),
+    /// A schema from the `core` API
+    #[cfg(feature = "core")]
+    Schema(&'a crate::core::Schema),
+}
+
+impl<'a> From<mem::Database<'a>> for Source<'a> {
+    fn from(db: mem::Database<'a>) -> Self {
+        Self::Mem(db)
+    }
+}
+
+#[cfg(feature = "core")]
+impl<'a> From<&'a crate::core::Schema> for Source<'a> {
+    fn from(schema: &'a crate::core::Schema) -> Self {
+        Self::Schema(schema)
+    }
+}
+
+/// The origin of a row in the result
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct RowOrigin {
+    /// The primary key of the row
+    pub key: Key,
+    /// The index of the layer
+    pub layer: usize,
+}
+
+/// A key of a layer that collided with rows of previous layers
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct KeyConflict {
+    /// The primary key
+    pub key: Key,
+    /// The index of the layer that has the key again
+    pub layer: usize,
+    /// The number of rows with that key from this layer
+    pub rows: usize,
+}
+
+/// A column that was added by a layer
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct AddedColumn {
+    /// The name of the column
+    pub name: String,
+    /// The type of the column
+    pub value_type: ValueType,
+    /// The index of the layer
+    pub layer: usize,
+}
+
+/// What happened to a single table
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct TableReport {
+    /// The name of the table
+    pub name: String,
+    /// The conflict policy that was used
+    pub policy: ConflictPolicy,
+    /// The index of the layer that added the table
+    pub created_by: usize,
+    /// The columns that were added after the table was created
+    pub added_columns: Vec<AddedColumn>,
+    /// The keys that replaced existing rows, with [`ConflictPolicy::OverlayWins`]
+    pub replaced: Vec<KeyConflict>,
+    /// The keys that were skipped, with [`ConflictPolicy::BaseWins`]
+    pub skipped: Vec<KeyConflict>,
+    /// The origin of every row of the result, in the order in which they were added
+    pub rows: Vec<RowOrigin>,
+}
+
+impl TableReport {
+    /// Get the number of rows of the result that come from `layer`
+    pub fn rows_from(&self, layer: usize) -> usize {
+        self.rows.iter().filter(|r| r.layer == layer).count()
+    }
+}
+
+/// What happened during a merge
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct MergeReport {
+    /// The names of the layers, starting with the base
+    pub layers: Vec<String>,
+    /// The tables of the result, sorted by name
+    pub tables: Vec<TableReport>,
+}
+
+/// A table with the rows of all layers so far
+struct TableState {
+    columns: Vec<(String, ValueType)>,
+    /// The rows that were not replaced, with their key and layer
+    rows: Vec<Option<(Key, Vec<Field>, usize)>>,
+    /// The positions of the rows for every key
+    keys: HashMap<Key, Vec<usize>>,
+    report: TableReport,
+}
+
+impl TableState {
+    fn push(&mut self, key: Key, fields: Vec<Field>, layer: usize) {
+        self.keys
+            .entry(key.clone())
+            .or_default()
+            .push(self.rows.len());
+        self.rows.push(Some((key, fields, layer)));
+    }
+}
+
+/// Combines a base database with overlays
+pub struct Merger {
+    options: MergeOptions,
+    layers: Vec<String>,
+    tables: BTreeMap<String, TableState>,
+}
+
+/// A table of a layer
+struct LayerTable {
+    name: String,
+    columns: Vec<(String, ValueType)>,
+    rows: Vec<Vec<Field>>,
+}
+
+impl Merger {
+    /// Create a new merger
+    pub fn new(options: MergeOptions) -> Self {
+        Self {
+            options,
+            layers: Vec::new(),
+            tables: BTreeMap::new(),
+        }
+    }
+
+    /// Add the next layer
+    ///
+    /// The first layer is the base. If this fails, the merger may contain
+    /// part of the layer and should not be used any more.
+    pub fn add<'a, S: Into<Source<'a>>>(
+        &mut self,
+        name: &str,
+        source: S,
+    ) -> Result<(), MergeError> {
+        let layer = self.layers.len();
+        self.layers.push(name.to_owned());
+        let tables = load(source.into()).map_err(|source| MergeError::Cast {
+            layer: name.to_owned(),
+            source,
+        })?;
+        for table in tables {
+            self.add_table(layer, table)?;
+        }
+        Ok(())
+    }
+
+    fn add_table(&mut self, layer: usize, table: LayerTable) -> Result<(), MergeError> {
+        let policy = self.options.policy_for(&table.name);
+        let layer_name = &self.layers[layer];
+        let state = self
+            .tables
+            .entry(table.name.clone())
+            .or_insert_with(|| TableState {
+                columns: table.columns.clone(),
+                rows: Vec::new(),
+                keys: HashMap::new(),
+                report: TableReport {
+                    name: table.name.clone(),
+                    policy,
+                    created_by: layer,
+                    added_columns: Vec::new(),
+                    replaced: Vec::new(),
+                    skipped: Vec::new(),
+                    rows: Vec::new(),
+                },
+            });
+
+        if self.options.schema == SchemaPolicy::Strict && state.columns != table.columns {
+            return Err(MergeError::Columns {
+                layer: layer_name.clone(),
+                table: table.name,
+            });
+        }
+        for (name, value_type) in &table.columns {
+            match state.columns.iter().find(|(n, _)| n == name) {
+                Some((_, expected)) if expected != value_type => {
+                    return Err(MergeError::ColumnType {
+                        layer: layer_name.clone(),
+                        table: table.name,
+                        column: name.clone(),
+                        expected: *expected,
+                        found: *value_type,
+                    })
+                }
+                Some(_) => {}
+                None => {
+                    state.columns.push((name.clone(), *value_type));
+                    for (_, fields, _) in state.rows.iter_mut().flatten() {
+                        fields.push(Field::Nothing);
+                    }
+                    state.report.added_columns.push(AddedColumn {
+                        name: name.clone(),
+                        value_type: *value_type,
+                        layer,
+                    });
+                }
+            }
+        }
+        // Find the position of every column of the state in the layer
+        let mapping: Vec<Option<usize>> = state
+            .columns
+            .iter()
+            .map(|(name, _)| table.columns.iter().position(|(n, _)| n == name))
+            .collect();
+
+        // Group the rows of the layer by key
+        let mut order = Vec::new();
+        let mut groups: HashMap<Key, Vec<Vec<Field>>> = HashMap::new();
+        for row in table.rows {
+            let fields: Vec<Field> = mapping
+                .iter()
+                .map(|index| {
+                    index
+                        .and_then(|i| row.get(i))
+                        .cloned()
+                        .unwrap_or(Field::Nothing)
+                })
+                .collect();
+            let key = fields.first().map_or(Key::Nothing, Key::from);
+            groups
+                .entry(key.clone())
+                .or_insert_with(|| {
+                    order.push(key);
+                    Vec::new()
+                })
+                .push(fields);
+        }
+
+        for key in order {
+            let rows = groups.remove(&key).unwrap();
+            let existing = state.keys.get(&key).filter(|p| !p.is_empty());
+            if let Some(positions) = existing {
+                let conflict = KeyConflict {
+                    key: key.clone(),
+                    layer,
+                    rows: rows.len(),
+                };
+                match policy {
+                    ConflictPolicy::OverlayWins => {
+                        for &position in positions {
+                            state.rows[position] = None;
+                        }
+                        state.keys.remove(&key);
+                        state.report.replaced.push(conflict);
+                    }
+                    ConflictPolicy::BaseWins => {
+                        state.report.skipped.push(conflict);
+                        continue;
+                    }
+                    ConflictPolicy::Error => {
+                        return Err(MergeError::Conflict {
+                            layer: layer_name.clone(),
+                            table: table.name,
+                            key,
+                        })
+                    }
+                    ConflictPolicy::Append => {}
+                }
+            }
+            for fields in rows {
+                state.push(key.clone(), fields, layer);
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the merged tables to a new database
+    ///
+    /// The rows are hashed into buckets by their first field, see
+    /// [`store::Table::auto_resize_buckets`].
+    pub fn finish(self) -> (store::Database, MergeReport) {
+        let mut db = store::Database::new();
+        let mut tables = Vec::with_capacity(self.tables.len());
+        for (name, state) in self.tables {
+            let mut table = store::Table::new(0);
+            for (column, value_type) in &state.columns {
+                table.push_column(Latin1String::encode(column), *value_type);
+            }
+            let mut report = state.report;
+            for (key, fields, layer) in state.rows.into_iter().flatten() {
+                table.push_row_hashed(&fields);
+                report.rows.push(RowOrigin { key, layer });
+            }
+            table.auto_resize_buckets();
+            db.push_table(Latin1String::encode(&name), table);
+            tables.push(report);
+        }
+        let report = MergeReport {
+            layers: self.layers,
+            tables,
+        };
+        (db, report)
+    }
+}
+
+/// Load all tables of a source
+fn load(source: Source<'_>) -> Result<Vec<LayerTable>, CastError> {
+    let mut tables = Vec::new();
+    match source {
+        Source::Mem(db) => {
+            for table in db.tables()?.iter() {
+                let table = table?;
+                tables.push(LayerTable {
+                    name: table.name().into_owned(),
+                    columns: table
+                        .column_iter()
+                        .map(|c| (c.name().into_owned(), c.value_type()))
+                        .collect(),
+                    rows: table
+                        .row_iter()
+                        .map(|row| row.field_iter().map(|f| f.map(&mut MemToOwned)).collect())
+                        .collect(),
+                });
+            }
+        }
+        #[cfg(feature = "core")]
+        Source::Schema(schema) => {
+            for (name, table) in &schema.tables {
+                tables.push(LayerTable {
+                    name: name.clone(),
+                    columns: table
+                        .columns()
+                        .iter()
+                        .map(|c| (c.name.clone(), c.field_type))
+                        .collect(),
+                    rows: table
+                        .buckets()
+                        .iter()
+                        .flat_map(|b| b.rows_ref())
+                        .map(|row| row.fields().clone())
+                        .collect(),
+                });
+            }
+        }
+    }
+    Ok(tables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::tests::{build, TableBuilder};
+
+    type Rows<'a> = &'a [(i32, &'a str)];
+
+    fn database(tables: &[(&str, &[&str], Rows)]) -> Vec<u8> {
+        let mut builders = Vec::new();
+        for (name, columns, rows) in tables {
+            let mut table = TableBuilder::new(name).column("id", ValueType::Integer);
+            for column in *columns {
+                table = table.column(column, ValueType::Text);
+            }
+            for (id, text) in *rows {
+                let mut fields = vec![Field::Integer(*id)];
+                fields.extend(columns.iter().map(|_| Field::Text(text.to_string())));
+                table.row(fields);
+            }
+            builders.push(table);
+        }
+        build(builders)
+    }
+
+    /// Get the rows of a table, sorted
+    fn rows(buf: &[u8], name: &str) -> Vec<Vec<Field>> {
+        let table = mem::Database::new(buf)
+            .tables()
+            .unwrap()
+            .by_name(name)
+            .unwrap()
+            .unwrap();
+        let mut rows: Vec<Vec<Field>> = table
+            .row_iter()
+            .map(|row| row.field_iter().map(|f| f.map(&mut MemToOwned)).collect())
+            .collect();
+        rows.sort_by_key(|row| format!("{:?}", row));
+        rows
+    }
+
+    fn row(id: i32, texts: &[Option<&str>]) -> Vec<Field> {
+        let mut fields = vec![Field::Integer(id)];
+        fields.extend(texts.iter().map(|t| match t {
+            Some(t) => Field::Text(t.to_string()),
+            None => Field::Nothing,
+        }));
+        fields
+    }
+
+    fn merge(
+        options: MergeOptions,
+        base: &[u8],
+        overlay: &[u8],
+    ) -> Result<(Vec<u8>, MergeReport), MergeError> {
+        let mut merger = Merger::new(options);
+        merger.add("base", mem::Database::new(base))?;
+        merger.add("overlay", mem::Database::new(overlay))?;
+        let (db, report) = merger.finish();
+        let mut buf = Vec::new();
+        db.write(&mut buf).unwrap();
+        Ok((buf, report))
+    }
+
+    #[test]
+    fn test_policies() {
+        let base = database(&[
+            ("Items", &["name"], &[(1, "a"), (2, "b")]),
+            ("Loot", &["name"], &[(1, "a"), (1, "b")]),
+        ]);
+        let overlay = database(&[
+            ("Items", &["name"], &[(2, "B"), (3, "C")]),
+            ("Loot", &["name"], &[(1, "c")]),
+            ("Extra", &[], &[(7, "")]),
+        ]);
+
+        let options = MergeOptions::new().table("Loot", ConflictPolicy::Append);
+        let (buf, report) = merge(options, &base, &overlay).unwrap();
+        assert_eq!(
+            rows(&buf, "Items"),
+            vec![
+                row(1, &[Some("a")]),
+                row(2, &[Some("B")]),
+                row(3, &[Some("C")])
+            ]
+        );
+        assert_eq!(rows(&buf, "Loot").len(), 3);
+        assert_eq!(rows(&buf, "Extra"), vec![row(7, &[])]);
+
+        assert_eq!(report.layers, ["base", "overlay"]);
+        let names: Vec<_> = report.tables.iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(names, ["Extra", "Items", "Loot"]);
+        let items = &report.tables[1];
+        assert_eq!(
+            items.replaced,
+            vec![KeyConflict {
+                key: Key::Integer(2),
+                layer: 1,
+                rows: 1
+            }]
+        );
+        assert_eq!((items.rows_from(0), items.rows_from(1)), (1, 2));
+        assert_eq!(report.tables[0].created_by, 1);
+        assert_eq!(report.tables[2].policy, ConflictPolicy::Append);
+
+        let options = MergeOptions::new().conflicts(ConflictPolicy::BaseWins);
+        let (buf, report) = merge(options, &base, &overlay).unwrap();
+        assert_eq!(
+            rows(&buf, "Items"),
+            vec![
+                row(1, &[Some("a")]),
+                row(2, &[Some("b")]),
+                row(3, &[Some("C")])
+            ]
+        );
+        assert_eq!(rows(&buf, "Loot").len(), 2);
+        assert_eq!(report.tables[2].skipped.len(), 1);
+
+        // Replacing a key removes all rows with that key
+        let (buf, _) = merge(MergeOptions::new(), &base, &overlay).unwrap();
+        assert_eq!(rows(&buf, "Loot"), vec![row(1, &[Some("c")])]);
+
+        let options = MergeOptions::new().table("Items", ConflictPolicy::Error);
+        let err = merge(options, &base, &overlay).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Layer \"overlay\" has a row with the existing key Integer(2) in table \"Items\""
+        );
+    }
+
+    #[test]
+    fn test_schema() {
+        let base = database(&[("Items", &["name"], &[(1, "a")])]);
+        let overlay = database(&[("Items", &["icon", "name"], &[(2, "b")])]);
+
+        let (buf, report) = merge(MergeOptions::new(), &base, &overlay).unwrap();
+        assert_eq!(
+            rows(&buf, "Items"),
+            vec![row(1, &[Some("a"), None]), row(2, &[Some("b"), Some("b")])]
+        );
+        assert_eq!(
+            report.tables[0].added_columns,
+            vec![AddedColumn {
+                name: String::from("icon"),
+                value_type: ValueType::Text,
+                layer: 1
+            }]
+        );
+        // The base is missing the new column
+        let (buf, _) = merge(MergeOptions::new(), &overlay, &base).unwrap();
+        assert_eq!(
+            rows(&buf, "Items"),
+            vec![row(1, &[None, Some("a")]), row(2, &[Some("b"), Some("b")])]
+        );
+
+        let options = MergeOptions::new().schema(SchemaPolicy::Strict);
+        assert!(matches!(
+            merge(options, &base, &overlay),
+            Err(MergeError::Columns { .. })
+        ));
+
+        let other = build(vec![
+            TableBuilder::new("Items").column("id", ValueType::BigInt)
+        ]);
+        assert!(matches!(
+            merge(MergeOptions::new(), &base, &other),
+            Err(MergeError::ColumnType {
+                expected: ValueType::Integer,
+                found: ValueType::BigInt,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_column_order() {
+        let base = database(&[("Items", &["name"], &[(1, "a"), (2, "b")])]);
+
+        // The key is in the second column, and one row is missing its fields
+        let mut table = TableBuilder::new("Items")
+            .column("name", ValueType::Text)
+            .column("id", ValueType::Integer);
+        table.row(vec![Field::Text(String::from("c")), Field::Integer(1)]);
+        table.row(vec![Field::Text(String::from("d"))]);
+        let overlay = build(vec![table]);
+
+        let (buf, report) = merge(MergeOptions::new(), &base, &overlay).unwrap();
+        assert_eq!(
+            rows(&buf, "Items"),
+            vec![
+                row(1, &[Some("c")]),
+                row(2, &[Some("b")]),
+                vec![Field::Nothing, Field::Text(String::from("d"))],
+            ]
+        );
+        assert_eq!(report.tables[0].replaced.len(), 1);
+        assert_eq!(report.tables[0].replaced[0].key, Key::Integer(1));
+    }
+
+    #[test]
+    #[cfg(feature = "core")]
+    fn test_schema_source() {
+        use std::convert::TryFrom;
+
+        let base = database(&[("Items", &["name"], &[(1, "a")])]);
+        let overlay = database(&[("Items", &["name"], &[(1, "b")])]);
+        let schema = crate::core::Schema::try_from(mem::Database::new(&overlay)).unwrap();
+
+        let mut merger = Merger::new(MergeOptions::new());
+        merger.add("base", mem::Database::new(&base)).unwrap();
+        merger.add("schema", &schema).unwrap();
+        let (db, _) = merger.finish();
+        let mut buf = Vec::new();
+        db.write(&mut buf).unwrap();
+        assert_eq!(rows(&buf, "Items"), vec![row(1, &[Some("b")])]);
+    }
+}
@@ -0,0 +1,394 @@
+//! # Profiles of tables and columns
+//!
+//! This module collects statistics on the content of a database, to find data
+//! quality problems and bad hash distributions:
+//!
+//! - For every table, the number of rows and how they are spread across buckets
+//! - For every column, the number of `NULL` and distinct values, the range of
+//!   numeric values, the longest string and the number of fields that don't
+//!   have the declared type of the column
+//!
+//! ```
+//! use assembly_fdb::{mem::Database, profile::profile_database};
+//!
+//! # let buf = vec![0, 0, 0, 0, 8, 0, 0, 0];
+//! let db = Database::new(&buf);
+//! for table in profile_database(db)?.tables {
+//!     for column in &table.columns {
+//!         if column.type_mismatches > 0 {
+//!             println!("{}.{} has fields of the wrong type", table.name, column.name);
+//!         }
+//!     }
+//! }
+//! # Ok::<(), assembly_core::buffer::CastError>(())
+//! ```
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashSet},
+    hash::{Hash, Hasher},
+};
+
+use assembly_core::buffer::CastError;
+use assembly_fdb_core::value::ValueType;
+
+use crate::{
+    index::Key,
+    mem::{Database, Field, Table},
+};
+
+/// Options for [`profile_table_with`]
+#[derive(Debug, Clone)]
+pub struct ProfileOptions {
+    exact_limit: usize,
+}
+
+impl Default for ProfileOptions {
+    fn default() -> Self {
+        Self {
+            exact_limit: 100_000,
+        }
+    }
+}
+
+impl ProfileOptions {
+    /// Create the default options
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Count distinct values exactly up to this number, and estimate above it
+    ///
+    /// The default is `100_000`. The estimate uses a HyperLogLog sketch with a
+    /// standard error of about 1.6%.
+    pub fn exact_limit(mut self, exact_limit: usize) -> Self {
+        self.exact_limit = exact_limit;
+        self
+    }
+}
+
+/// The profiles of all tables in a database
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct DatabaseProfile {
+    /// The tables, in the order of the file
+    pub tables: Vec<TableProfile>,
+}
+
+/// The profile of a single table
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct TableProfile {
+    /// The name of the table
+    pub name: String,
+    /// The number of rows
+    pub row_count: usize,
+    /// How the rows are spread across the buckets
+    pub buckets: BucketProfile,
+    /// The columns, in the order of the table definition
+    pub columns: Vec<ColumnProfile>,
+}
+
+/// How the rows of a table are spread across its buckets
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct BucketProfile {
+    /// The number of buckets
+    pub bucket_count: usize,
+    /// The number of buckets without rows
+    pub empty: usize,
+    /// The number of rows in the fullest bucket
+    pub longest_chain: usize,
+    /// The number of buckets with `n` rows at index `n`
+    pub chain_lengths: Vec<usize>,
+}
+
+/// The profile of a single column
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct ColumnProfile {
+    /// The name of the column
+    pub name: String,
+    /// The declared type of the column
+    pub value_type: ValueType,
+    /// The number of `NULL` fields
+    pub null_count: usize,
+    /// The number of distinct values, excluding `NULL`
+    pub distinct_count: usize,
+    /// Whether `distinct_count` is exact, or an estimate
+    pub distinct_exact: bool,
+    /// The smallest value of the column type, for numeric columns
+    pub min: Option<Key>,
+    /// The largest value of the column type, for numeric columns
+    pub max: Option<Key>,
+    /// The length of the longest string in bytes, for string columns
+    pub max_string_len: Option<usize>,
+    /// The number of fields that are neither `NULL` nor of the declared type
+    pub type_mismatches: usize,
+}
+
+/// Profile all tables of a database with the default options
+pub fn profile_database(db: Database<'_>) -> Result<DatabaseProfile, CastError> {
+    let options = ProfileOptions::default();
+    let tables = db
+        .tables()?
+        .iter()
+        .map(|table| table.map(|table| profile_table_with(table, &options)))
+        .collect::<Result<_, _>>()?;
+    Ok(DatabaseProfile { tables })
+}
+
+/// Profile a single table with the default options
+pub fn profile_table(table: Table<'_>) -> TableProfile {
+    profile_table_with(table, &ProfileOptions::default())
+}
+
+/// Profile a single table
+pub fn profile_table_with(table: Table<'_>, options: &ProfileOptions) -> TableProfile {
+    let mut columns: Vec<ColumnState> = table
+        .column_iter()
+        .map(|column| ColumnState::new(column.name().into_owned(), column.value_type()))
+        .collect();
+
+    let mut buckets = BucketProfile {
+        bucket_count: table.bucket_count(),
+        empty: 0,
+        longest_chain: 0,
+        chain_lengths: Vec::new(),
+    };
+    let mut row_count = 0;
+    for bucket in table.bucket_iter() {
+        let mut chain = 0;
+        for row in bucket.row_iter() {
+            chain += 1;
+            for (column, field) in columns.iter_mut().zip(row.field_iter()) {
+                column.add(field, options);
+            }
+        }
+        row_count += chain;
+        if buckets.chain_lengths.len() <= chain {
+            buckets.chain_lengths.resize(chain + 1, 0);
+        }
+        buckets.chain_lengths[chain] += 1;
+        buckets.longest_chain = buckets.longest_chain.max(chain);
+    }
+    buckets.empty = buckets.chain_lengths.first().copied().unwrap_or(0);
+
+    TableProfile {
+        name: table.name().into_owned(),
+        row_count,
+        buckets,
+        columns: columns.into_iter().map(ColumnState::finish).collect(),
+    }
+}
+
+/// Counts the distinct values of a column
+enum Distinct {
+    Exact(HashSet<Key>),
+    Sketch(HyperLogLog),
+}
+
+/// The intermediate profile of a column
+struct ColumnState {
+    profile: ColumnProfile,
+    distinct: Distinct,
+}
+
+impl ColumnState {
+    fn new(name: String, value_type: ValueType) -> Self {
+        Self {
+            profile: ColumnProfile {
+                name,
+                value_type,
+                null_count: 0,
+                distinct_count: 0,
+                distinct_exact: true,
+                min: None,
+                max: None,
+                max_string_len: None,
+                type_mismatches: 0,
+            },
+            distinct: Distinct::Exact(HashSet::new()),
+        }
+    }
+
+    fn add(&mut self, field: Field<'_>, options: &ProfileOptions) {
+        let profile = &mut self.profile;
+        let field_type = ValueType::from(&field);
+        if field_type == ValueType::Nothing {
+            profile.null_count += 1;
+            return;
+        }
+        if field_type != profile.value_type {
+            profile.type_mismatches += 1;
+        }
+        if let Field::Text(s) | Field::VarChar(s) = field {
+            let len = profile.max_string_len.unwrap_or(0).max(s.len());
+            profile.max_string_len = Some(len);
+        }
+
+        let key = Key::from(field);
+        // Values of another type don't count towards the range of the column
+        if field_type == profile.value_type
+            && matches!(
+                field_type,
+                ValueType::Integer | ValueType::Float | ValueType::BigInt
+            )
+        {
+            match &profile.min {
+                Some(min) if *min <= key => {}
+                _ => profile.min = Some(key.clone()),
+            }
+            match &profile.max {
+                Some(max) if *max >= key => {}
+                _ => profile.max = Some(key.clone()),
+            }
+        }
+
+        match &mut self.distinct {
+            Distinct::Exact(set) => {
+                set.insert(key);
+                if set.len() > options.exact_limit {
+                    let mut sketch = HyperLogLog::new();
+                    set.drain().for_each(|key| sketch.insert(&key));
+                    self.distinct = Distinct::Sketch(sketch);
+                }
+            }
+            Distinct::Sketch(sketch) => sketch.insert(&key),
+        }
+    }
+
+    fn finish(self) -> ColumnProfile {
+        let mut profile = self.profile;
+        match self.distinct {
+            Distinct::Exact(set) => profile.distinct_count = set.len(),
+            Distinct::Sketch(sketch) => {
+                profile.distinct_count = sketch.estimate();
+                profile.distinct_exact = false;
+            }
+        }
+        profile
+    }
+}
+
+/// A HyperLogLog sketch with 2^12 registers
+struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl HyperLogLog {
+    const BITS: u32 = 12;
+
+    fn new() -> Self {
+        Self {
+            registers: vec![0; 1 << Self::BITS],
+        }
+    }
+
+    fn insert<T: Hash>(&mut self, value: &T) {
+        let mut hasher = DefaultHasher::new();
+        value.hash(&mut hasher);
+        let hash = hasher.finish();
+        let index = (hash >> (64 - Self::BITS)) as usize;
+        let rank = ((hash << Self::BITS) | (1 << (Self::BITS - 1))).leading_zeros() + 1;
+        self.registers[index] = self.registers[index].max(rank as u8);
+    }
+
+    fn estimate(&self) -> usize {
+        let m = self.registers.len() as f64;
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let sum: f64 = self.registers.iter().map(|&r| 2f64.powi(-(r as i32))).sum();
+        let raw = alpha * m * m / sum;
+        let zeros = self.registers.iter().filter(|&&r| r == 0).count();
+        if raw <= 2.5 * m && zeros > 0 {
+            // Linear counting for small cardinalities
+            (m * (m / zeros as f64).ln()).round() as usize
+        } else {
+            raw.round() as usize
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hyperloglog() {
+        let mut sketch = HyperLogLog::new();
+        for i in 0..200_000i64 {
+            sketch.insert(&Key::Integer(i % 50_000));
+        }
+        let estimate = sketch.estimate() as f64;
+        assert!(
+            (estimate - 50_000.0).abs() < 50_000.0 * 0.05,
+            "{}",
+            estimate
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "store")]
+    fn test_profile_table() {
+        use crate::{
+            util::tests::{build, TableBuilder},
+            value::owned,
+        };
+        use std::convert::TryFrom;
+
+        let mut table = TableBuilder::new("Items")
+            .column("id", ValueType::Integer)
+            .column("name", ValueType::Text)
+            .column("chance", ValueType::Float)
+            .buckets(8);
+        for i in 0..20 {
+            table.row(vec![
+                owned::Field::Integer(i * 4),
+                match i % 4 {
+                    0 => owned::Field::Nothing,
+                    1 => owned::Field::Integer(i),
+                    _ => owned::Field::Text(format!("name {}", i % 3)),
+                },
+                match i {
+                    19 => owned::Field::Integer(100),
+                    _ => owned::Field::Float(i as f32 / 2.0 - 3.0),
+                },
+            ]);
+        }
+        let buf = build(vec![table]);
+
+        let mem = Database::new(&buf);
+        let profile = profile_table(mem.tables().unwrap().get(0).unwrap().unwrap());
+        assert_eq!(profile.row_count, 20);
+        // All ids are multiples of 4, so only every fourth bucket is used
+        assert_eq!(
+            profile.buckets,
+            BucketProfile {
+                bucket_count: 8,
+                empty: 6,
+                longest_chain: 10,
+                chain_lengths: vec![6, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2],
+            }
+        );
+
+        let [id, name, chance] = <[ColumnProfile; 3]>::try_from(profile.columns).unwrap();
+        assert_eq!(id.distinct_count, 20);
+        assert_eq!(id.min, Some(Key::Integer(0)));
+        assert_eq!(id.max, Some(Key::Integer(76)));
+        assert_eq!(id.max_string_len, None);
+
+        assert_eq!(name.null_count, 5);
+        assert_eq!(name.type_mismatches, 5);
+        assert_eq!(name.distinct_count, 3 + 5);
+        assert_eq!(name.max_string_len, Some(6));
+        assert_eq!(name.min, None);
+
+        assert_eq!(chance.min, Some(Key::Float(-3.0)));
+        assert_eq!(chance.max, Some(Key::Float(6.0)));
+        assert_eq!(chance.type_mismatches, 1);
+
+        let options = ProfileOptions::new().exact_limit(4);
+        let profile = profile_table_with(mem.tables().unwrap().get(0).unwrap().unwrap(), &options);
+        assert!(!profile.columns[0].distinct_exact);
+        assert_eq!(profile.columns[0].distinct_count, 20);
+    }
+}
@@ -1,4 +1,7 @@
 //! ## Query the database
+//!
+//! This module contains filters for primary keys and a [`Query`] builder that
+//! can filter, join, sort and project the rows of a [`mem::Database`](crate::mem::Database).
 use std::num::ParseIntError;
 
 use assembly_fdb_core::{
@@ -8,6 +11,10 @@ use assembly_fdb_core::{
 use displaydoc::Display;
 use thiserror::Error;
 
+mod select;
+
+pub use select::{Op, Order, Predicate, Query, QueryError, ResultSet};
+
 /// A struct that can act as a PK filter
 ///
 /// This structure works much like a pre-implemented closure
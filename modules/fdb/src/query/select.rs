@@ -0,0 +1,693 @@
+//! ## Chained queries
+//!
+//! A [`Query`] describes which rows of one or more tables to return. It is
+//! built once and can be run against any [`Tables`] with [`Query::execute`].
+//!
+//! ```
+//! use assembly_fdb::{mem::Tables, query::{Order, Query, QueryError}};
+//!
+//! fn print_missions(tables: Tables) -> Result<(), QueryError> {
+//!     let result = Query::from("Missions")
+//!         .eq("defined_type", "Ninjago")
+//!         .like("defined_subtype", "%Daily%")
+//!         .join("MissionTasks", "id", "id")
+//!         .select(["Missions.id", "MissionTasks.target"])
+//!         .order_by("Missions.id", Order::Descending)
+//!         .limit(10)
+//!         .execute(tables)?;
+//!
+//!     for row in result.to_owned_rows() {
+//!         println!("{:?}", row);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+//!
+//! Values are compared like in SQLite: `Integer` and `BigInt` values are equal
+//! if they have the same value, comparisons with `NULL` are never true and
+//! `LIKE` patterns are case insensitive for ASCII letters.
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use assembly_core::buffer::CastError;
+use assembly_fdb_core::value::owned;
+use displaydoc::Display;
+use thiserror::Error;
+
+use super::PrimaryKeyFilter;
+use crate::{
+    index::Key,
+    mem::{self, MemToOwned, Row, Table, Tables},
+};
+
+/// Errors when running a query
+#[derive(Debug, Error, Display)]
+pub enum QueryError {
+    /// Failed to load table: {0}
+    Cast(#[from] CastError),
+    /// Table {0:?} does not exist
+    MissingTable(String),
+    /// Column {0:?} does not exist
+    MissingColumn(String),
+    /// Column {0:?} exists in more than one table, use `Table.column`
+    AmbiguousColumn(String),
+}
+
+/// A comparison operator
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Op {
+    /// `column = value`
+    Eq,
+    /// `column <> value`
+    Ne,
+    /// `column < value`
+    Lt,
+    /// `column <= value`
+    Le,
+    /// `column > value`
+    Gt,
+    /// `column >= value`
+    Ge,
+    /// `column LIKE value`, where `%` matches any string and `_` any character
+    Like,
+    /// `column IS NULL`
+    IsNull,
+    /// `column IS NOT NULL`
+    IsNotNull,
+}
+
+/// A condition on a single column
+#[derive(Debug, Clone, PartialEq)]
+pub struct Predicate {
+    /// The name of the column, optionally prefixed with `Table.`
+    pub column: String,
+    /// The operator
+    pub op: Op,
+    /// The value to compare with
+    pub value: Key,
+}
+
+impl Predicate {
+    /// Create a new predicate
+    pub fn new<S: Into<String>, K: Into<Key>>(column: S, op: Op, value: K) -> Self {
+        Self {
+            column: column.into(),
+            op,
+            value: value.into(),
+        }
+    }
+
+    /// Check whether a field satisfies this predicate
+    pub fn matches(&self, field: &Key) -> bool {
+        match (self.op, field) {
+            (Op::IsNull, key) => *key == Key::Nothing,
+            (Op::IsNotNull, key) => *key != Key::Nothing,
+            (_, Key::Nothing) => false,
+            (Op::Like, Key::Text(text)) => match &self.value {
+                Key::Text(pattern) => {
+                    let pattern: Vec<char> = pattern.chars().collect();
+                    let text: Vec<char> = text.chars().collect();
+                    like(&pattern, &text)
+                }
+                _ => false,
+            },
+            (Op::Like, _) => false,
+            (op, key) => match compare(key, &self.value) {
+                Some(ordering) => match op {
+                    Op::Eq => ordering == Ordering::Equal,
+                    Op::Ne => ordering != Ordering::Equal,
+                    Op::Lt => ordering == Ordering::Less,
+                    Op::Le => ordering != Ordering::Greater,
+                    Op::Gt => ordering == Ordering::Greater,
+                    Op::Ge => ordering != Ordering::Less,
+                    _ => unreachable!(),
+                },
+                None => false,
+            },
+        }
+    }
+}
+
+/// Compare two keys of the same kind, treating integers and floats as numbers
+fn compare(a: &Key, b: &Key) -> Option<Ordering> {
+    match (a, b) {
+        (Key::Nothing, _) | (_, Key::Nothing) => None,
+        (Key::Integer(a), Key::Float(b)) => (*a as f64).partial_cmp(&f64::from(*b)),
+        (Key::Float(a), Key::Integer(b)) => f64::from(*a).partial_cmp(&(*b as f64)),
+        (a, b) if a.rank() == b.rank() => Some(a.cmp(b)),
+        _ => None,
+    }
+}
+
+/// Match an SQL `LIKE` pattern
+///
+/// `_` matches a single character, letters are compared ignoring ASCII case
+/// like in SQLite. When a literal doesn't match, the last `%` is extended by one character,
+/// so this takes at most `pattern.len() * text.len()` steps.
+fn like(pattern: &[char], text: &[char]) -> bool {
+    let (mut p, mut t) = (0, 0);
+    // The position after the last `%`, and the text position it matched up to
+    let mut star: Option<(usize, usize)> = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('%') => {
+                p += 1;
+                star = Some((p, t));
+            }
+            Some('_') => {
+                p += 1;
+                t += 1;
+            }
+            Some(c) if c.eq_ignore_ascii_case(&text[t]) => {
+                p += 1;
+                t += 1;
+            }
+            _ => match &mut star {
+                Some((star_p, star_t)) => {
+                    *star_t += 1;
+                    p = *star_p;
+                    t = *star_t;
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '%')
+}
+
+/// The direction of an ordering
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Order {
+    /// Smallest value first, NULL values before all others
+    Ascending,
+    /// Largest value first, NULL values after all others
+    Descending,
+}
+
+#[derive(Debug, Clone)]
+struct Join {
+    table: String,
+    left: String,
+    right: String,
+}
+
+/// A query over one or more tables
+#[derive(Debug, Clone)]
+pub struct Query {
+    table: String,
+    primary_key: Option<PrimaryKeyFilter>,
+    joins: Vec<Join>,
+    predicates: Vec<Predicate>,
+    projection: Option<Vec<String>>,
+    order: Vec<(String, Order)>,
+    limit: Option<usize>,
+}
+
+impl Query {
+    /// Start a query on the table `table`
+    pub fn from<S: Into<String>>(table: S) -> Self {
+        Self {
+            table: table.into(),
+            primary_key: None,
+            joins: Vec::new(),
+            predicates: Vec::new(),
+            projection: None,
+            order: Vec::new(),
+            limit: None,
+        }
+    }
+
+    /// Only return rows of the first table with this primary key
+    ///
+    /// This uses the hash buckets of the table instead of scanning all rows.
+    pub fn primary_key(mut self, filter: PrimaryKeyFilter) -> Self {
+        self.primary_key = Some(filter);
+        self
+    }
+
+    /// Add a predicate
+    pub fn filter(mut self, predicate: Predicate) -> Self {
+        self.predicates.push(predicate);
+        self
+    }
+
+    /// Only return rows where `column = value`
+    pub fn eq<S: Into<String>, K: Into<Key>>(self, column: S, value: K) -> Self {
+        self.filter(Predicate::new(column, Op::Eq, value))
+    }
+
+    /// Only return rows where `column <> value`
+    pub fn ne<S: Into<String>, K: Into<Key>>(self, column: S, value: K) -> Self {
+        self.filter(Predicate::new(column, Op::Ne, value))
+    }
+
+    /// Only return rows where `column < value`
+    pub fn lt<S: Into<String>, K: Into<Key>>(self, column: S, value: K) -> Self {
+        self.filter(Predicate::new(column, Op::Lt, value))
+    }
+
+    /// Only return rows where `column > value`
+    pub fn gt<S: Into<String>, K: Into<Key>>(self, column: S, value: K) -> Self {
+        self.filter(Predicate::new(column, Op::Gt, value))
+    }
+
+    /// Only return rows where `column LIKE pattern`
+    pub fn like<S: Into<String>>(self, column: S, pattern: &str) -> Self {
+        self.filter(Predicate::new(column, Op::Like, pattern))
+    }
+
+    /// Only return rows where `column IS NULL`
+    pub fn is_null<S: Into<String>>(self, column: S) -> Self {
+        self.filter(Predicate::new(column, Op::IsNull, Key::Nothing))
+    }
+
+    /// Join with the rows of `table` where `left = right`
+    ///
+    /// `left` is a column of one of the previous tables, `right` a column
+    /// of `table`. Rows without a partner are dropped (inner join).
+    pub fn join<T, L, R>(mut self, table: T, left: L, right: R) -> Self
+    where
+        T: Into<String>,
+        L: Into<String>,
+        R: Into<String>,
+    {
+        self.joins.push(Join {
+            table: table.into(),
+            left: left.into(),
+            right: right.into(),
+        });
+        self
+    }
+
+    /// Only return the given columns
+    pub fn select<I, S>(mut self, columns: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.projection = Some(columns.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Sort the rows by `column`
+    ///
+    /// Calling this multiple times sorts by the first column first.
+    pub fn order_by<S: Into<String>>(mut self, column: S, order: Order) -> Self {
+        self.order.push((column.into(), order));
+        self
+    }
+
+    /// Return at most `limit` rows
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Run the query
+    pub fn execute<'a>(&self, tables: Tables<'a>) -> Result<ResultSet<'a>, QueryError> {
+        let get_table = |name: &str| {
+            tables
+                .by_name(name)
+                .ok_or_else(|| QueryError::MissingTable(name.to_owned()))?
+                .map_err(QueryError::from)
+        };
+
+        let mut sources = Sources(vec![get_table(&self.table)?]);
+        for join in &self.joins {
+            sources.0.push(get_table(&join.table)?);
+        }
+
+        // Assign every predicate to the table it refers to
+        let mut predicates = vec![Vec::new(); sources.0.len()];
+        for predicate in &self.predicates {
+            let (source, column) = sources.resolve(&predicate.column)?;
+            predicates[source].push((column, predicate));
+        }
+        let keep = |source: usize, row: &Row<'a>| {
+            predicates[source]
+                .iter()
+                .all(|(column, p)| p.matches(&field_key(row, *column)))
+        };
+
+        let base = sources.0[0];
+        let mut rows: Vec<Vec<Row<'a>>> = match &self.primary_key {
+            Some(pk) if base.bucket_count() > 0 => {
+                let key = Key::from(pk.original());
+                base.bucket_for_hash(pk.hash())
+                    .row_iter()
+                    .filter(|row| field_key(row, 0) == key)
+                    .filter(|row| keep(0, row))
+                    .map(|row| vec![row])
+                    .collect()
+            }
+            Some(_) => Vec::new(),
+            None => base
+                .row_iter()
+                .filter(|row| keep(0, row))
+                .map(|row| vec![row])
+                .collect(),
+        };
+
+        for (index, join) in self.joins.iter().enumerate() {
+            let source = index + 1;
+            let (left_source, left_column) = sources.resolve_in(&join.left, 0..source)?;
+            let right_column = sources.resolve_in(&join.right, source..source + 1)?.1;
+
+            let mut hashed: HashMap<Key, Vec<Row<'a>>> = HashMap::new();
+            for row in sources.0[source].row_iter().filter(|row| keep(source, row)) {
+                let key = normalize(field_key(&row, right_column));
+                if key != Key::Nothing {
+                    hashed.entry(key).or_default().push(row);
+                }
+            }
+
+            let mut joined = Vec::new();
+            for left in rows {
+                let key = normalize(field_key(&left[left_source], left_column));
+                for right in hashed.get(&key).into_iter().flatten() {
+                    let mut row = left.clone();
+                    row.push(*right);
+                    joined.push(row);
+                }
+            }
+            rows = joined;
+        }
+
+        if !self.order.is_empty() {
+            let order = self
+                .order
+                .iter()
+                .map(|(column, order)| Ok((sources.resolve(column)?, *order)))
+                .collect::<Result<Vec<_>, QueryError>>()?;
+            rows.sort_by(|a, b| {
+                for &((source, column), order) in &order {
+                    let a = field_key(&a[source], column);
+                    let b = field_key(&b[source], column);
+                    let ordering = match compare(&a, &b) {
+                        Some(ordering) => ordering,
+                        None => a.cmp(&b),
+                    };
+                    let ordering = match order {
+                        Order::Ascending => ordering,
+                        Order::Descending => ordering.reverse(),
+                    };
+                    if ordering != Ordering::Equal {
+                        return ordering;
+                    }
+                }
+                Ordering::Equal
+            });
+        }
+
+        if let Some(limit) = self.limit {
+            rows.truncate(limit);
+        }
+
+        let (columns, projection) = match &self.projection {
+            Some(names) => {
+                let projection = names
+                    .iter()
+                    .map(|name| sources.resolve(name))
+                    .collect::<Result<Vec<_>, _>>()?;
+                (names.clone(), projection)
+            }
+            None => {
+                let qualify = sources.0.len() > 1;
+                let mut columns = Vec::new();
+                let mut projection = Vec::new();
+                for (source, table) in sources.0.iter().enumerate() {
+                    for (index, column) in table.column_iter().enumerate() {
+                        columns.push(match qualify {
+                            true => format!("{}.{}", table.name(), column.name()),
+                            false => column.name().into_owned(),
+                        });
+                        projection.push((source, index));
+                    }
+                }
+                (columns, projection)
+            }
+        };
+
+        Ok(ResultSet {
+            columns,
+            projection,
+            rows,
+        })
+    }
+}
+
+/// Get a field as a key, NULL if it does not exist
+fn field_key(row: &Row<'_>, column: usize) -> Key {
+    row.field_at(column).map_or(Key::Nothing, Key::from)
+}
+
+/// Use the same key for numbers that compare equal
+fn normalize(key: Key) -> Key {
+    match key {
+        Key::Float(f) if f.fract() == 0.0 && f.abs() < 2f32.powi(24) => Key::Integer(f as i64),
+        key => key,
+    }
+}
+
+/// The tables of a query, in the order they were joined
+struct Sources<'a>(Vec<Table<'a>>);
+
+impl<'a> Sources<'a> {
+    fn resolve(&self, name: &str) -> Result<(usize, usize), QueryError> {
+        self.resolve_in(name, 0..self.0.len())
+    }
+
+    /// Find the table and index of a column among the tables in `range`
+    fn resolve_in(
+        &self,
+        name: &str,
+        range: std::ops::Range<usize>,
+    ) -> Result<(usize, usize), QueryError> {
+        let mut found = None;
+        for source in range.clone() {
+            if let Some(index) = column_index(self.0[source], name) {
+                if found.is_some() {
+                    return Err(QueryError::AmbiguousColumn(name.to_owned()));
+                }
+                found = Some((source, index));
+            }
+        }
+        if let (None, Some((table, column))) = (found, name.split_once('.')) {
+            for source in range {
+                if self.0[source].name() == table {
+                    if let Some(index) = column_index(self.0[source], column) {
+                        found = Some((source, index));
+                        break;
+                    }
+                }
+            }
+        }
+        found.ok_or_else(|| QueryError::MissingColumn(name.to_owned()))
+    }
+}
+
+fn column_index(table: Table<'_>, name: &str) -> Option<usize> {
+    table.column_iter().position(|c| c.name() == name)
+}
+
+/// The result of [`Query::execute`]
+pub struct ResultSet<'a> {
+    columns: Vec<String>,
+    /// The table and column index for each output column
+    projection: Vec<(usize, usize)>,
+    /// One row of every table for each result
+    rows: Vec<Vec<Row<'a>>>,
+}
+
+impl<'a> ResultSet<'a> {
+    /// Get the names of the output columns
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Get the number of rows
+    pub fn len(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Check whether there are no rows
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    /// Get the rows of the first table, without projection
+    pub fn mem_rows(&self) -> Vec<Row<'a>> {
+        self.rows.iter().map(|row| row[0]).collect()
+    }
+
+    /// Get the rows of all tables, in the order they were joined
+    pub fn joined_rows(&self) -> &[Vec<Row<'a>>] {
+        &self.rows
+    }
+
+    /// Get the selected fields of the row at `index`
+    pub fn fields(&self, index: usize) -> Option<Vec<mem::Field<'a>>> {
+        let row = self.rows.get(index)?;
+        Some(
+            self.projection
+                .iter()
+                .map(|&(source, column)| {
+                    row[source].field_at(column).unwrap_or(mem::Field::Nothing)
+                })
+                .collect(),
+        )
+    }
+
+    /// Get the selected fields of all rows as owned values
+    pub fn to_owned_rows(&self) -> Vec<Vec<owned::Field>> {
+        (0..self.rows.len())
+            .filter_map(|index| self.fields(index))
+            .map(|fields| fields.iter().map(|f| f.map(&mut MemToOwned)).collect())
+            .collect()
+    }
+}
+
+#[cfg(all(test, feature = "store"))]
+mod tests {
+    use super::*;
+    use crate::{
+        mem::Database,
+        util::tests::{build, TableBuilder},
+    };
+    use assembly_fdb_core::value::ValueType;
+    use owned::Field;
+
+    fn database() -> Vec<u8> {
+        let mut missions = TableBuilder::new("Missions")
+            .column("id", ValueType::Integer)
+            .column("defined_type", ValueType::Text)
+            .column("reward", ValueType::BigInt);
+        for (id, kind, reward) in [
+            (1, Some("Daily Ninjago"), 10),
+            (2, Some("Ninjago"), 20),
+            (3, None, 30),
+            (4, Some("daily ninjago"), 40),
+        ] {
+            missions.row(vec![
+                Field::Integer(id),
+                kind.map_or(Field::Nothing, |k| Field::Text(k.to_string())),
+                Field::BigInt(reward),
+            ]);
+        }
+
+        let mut tasks = TableBuilder::new("MissionTasks")
+            .column("id", ValueType::Integer)
+            .column("target", ValueType::Float);
+        for (id, target) in [(1, 1.0), (1, 2.0), (2, 3.0), (5, 4.0)] {
+            tasks.row(vec![Field::Integer(id), Field::Float(target)]);
+        }
+
+        build(vec![missions, tasks])
+    }
+
+    fn ids(result: &ResultSet) -> Vec<Field> {
+        result
+            .to_owned_rows()
+            .into_iter()
+            .map(|r| r[0].clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_filter_order_limit() {
+        let buf = database();
+        let tables = Database::new(&buf).tables().unwrap();
+
+        let result = Query::from("Missions")
+            .like("defined_type", "%ninjago")
+            .ne("id", 2)
+            .order_by("reward", Order::Descending)
+            .select(["id"])
+            .execute(tables)
+            .unwrap();
+        assert_eq!(ids(&result), vec![Field::Integer(4), Field::Integer(1)]);
+
+        let result = Query::from("Missions")
+            .is_null("defined_type")
+            .execute(tables)
+            .unwrap();
+        assert_eq!(result.columns(), ["id", "defined_type", "reward"]);
+        assert_eq!(ids(&result), vec![Field::Integer(3)]);
+
+        let result = Query::from("Missions")
+            .gt("reward", 10)
+            .order_by("id", Order::Ascending)
+            .limit(2)
+            .execute(tables)
+            .unwrap();
+        assert_eq!(ids(&result), vec![Field::Integer(2), Field::Integer(3)]);
+
+        let result = Query::from("Missions")
+            .primary_key(PrimaryKeyFilter::integer(4))
+            .execute(tables)
+            .unwrap();
+        assert_eq!(result.mem_rows().len(), 1);
+        assert_eq!(ids(&result), vec![Field::Integer(4)]);
+    }
+
+    #[test]
+    fn test_join() {
+        let buf = database();
+        let tables = Database::new(&buf).tables().unwrap();
+
+        let result = Query::from("Missions")
+            .join("MissionTasks", "id", "id")
+            .lt("target", 3)
+            .select(["Missions.id", "target"])
+            .order_by("target", Order::Ascending)
+            .execute(tables)
+            .unwrap();
+        assert_eq!(
+            result.to_owned_rows(),
+            vec![
+                vec![Field::Integer(1), Field::Float(1.0)],
+                vec![Field::Integer(1), Field::Float(2.0)],
+            ]
+        );
+
+        let err = Query::from("Missions")
+            .join("MissionTasks", "id", "id")
+            .select(["id"])
+            .execute(tables)
+            .err()
+            .unwrap();
+        assert!(matches!(err, QueryError::AmbiguousColumn(_)));
+
+        let err = Query::from("Missing").execute(tables).err().unwrap();
+        assert!(matches!(err, QueryError::MissingTable(_)));
+    }
+
+    #[test]
+    fn test_like() {
+        let like = |pattern: &str, text: &str| {
+            let pattern: Vec<char> = pattern.chars().collect();
+            let text: Vec<char> = text.chars().collect();
+            like(&pattern, &text)
+        };
+        assert!(like("%Daily%", "My daily mission"));
+        assert!(like("a_c", "ABC"));
+        assert!(!like("a_c", "ac"));
+        assert!(like("%", ""));
+        assert!(!like("abc", "abcd"));
+        assert!(like("%a%b%", "xxaxxbxx"));
+        assert!(!like("%a%b%c", "xxaxxbxx"));
+        assert!(like("_%_", "ab"));
+        assert!(!like("_%_", "a"));
+
+        // `_` is a single character, even if it is not ASCII
+        assert!(like("Gr_ße", "Grüße"));
+        assert!(like("gr%SSE", "Grüsse"));
+        assert!(!like("Gr__ße", "Grüße"));
+        let predicate = Predicate::new("name", Op::Like, "Gr_ße");
+        assert!(predicate.matches(&Key::Text(String::from("Grüße"))));
+
+        // Many wildcards don't take exponential time
+        let text = "a".repeat(64);
+        assert!(!like("%a%a%a%a%a%a%a%a%a%a%a%a%b", &text));
+    }
+}
@@ -0,0 +1,261 @@
+//! # Change the number of buckets
+//!
+//! The number of buckets of a table decides how many rows need to be compared
+//! in a lookup with [`Table::bucket_for_hash`](crate::mem::Table::bucket_for_hash).
+//! This module rewrites tables with a new bucket count, and moves every row to
+//! the bucket `hash % bucket_count`, where `hash` is the [`FdbHash`] of its
+//! first field.
+//!
+//! Rows with the same primary key stay in the order of the source, so every
+//! lookup by primary key returns the same rows before and after. Rows that were
+//! not in the bucket of their hash (see [`validate`](crate::validate)) could not
+//! be found before, and are moved to the right bucket.
+//!
+//! ```
+//! use assembly_fdb::{mem, rehash::{rehash_database, BucketCount, RehashOptions}};
+//!
+//! # let buf = vec![0, 0, 0, 0, 8, 0, 0, 0];
+//! let options = RehashOptions::new()
+//!     .bucket_count(BucketCount::Auto)
+//!     .table("LootTable", BucketCount::Fixed(1024));
+//! let db = rehash_database(mem::Database::new(&buf), &options)?;
+//! # Ok::<(), assembly_core::buffer::CastError>(())
+//! ```
+
+use std::collections::BTreeMap;
+
+use assembly_core::buffer::CastError;
+use assembly_fdb_core::FdbHash;
+
+use crate::{
+    mem::{self, MemToOwned},
+    store,
+};
+
+/// The new number of buckets of a table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BucketCount {
+    /// Keep the bucket count of the source
+    Keep,
+    /// Use this number of buckets
+    ///
+    /// A table with rows needs at least one bucket, otherwise
+    /// [`store::Database::write`] fails.
+    Fixed(usize),
+    /// Use the smallest power of two that is at least the number of distinct keys
+    ///
+    /// This is the size used in the original database files, see
+    /// [`store::Table::auto_resize_buckets`].
+    Auto,
+}
+
+/// Options for [`rehash_database`]
+#[derive(Debug, Clone)]
+pub struct RehashOptions {
+    default: BucketCount,
+    tables: BTreeMap<String, BucketCount>,
+}
+
+impl Default for RehashOptions {
+    fn default() -> Self {
+        Self {
+            default: BucketCount::Auto,
+            tables: BTreeMap::new(),
+        }
+    }
+}
+
+impl RehashOptions {
+    /// Create the default options, which use [`BucketCount::Auto`] for all tables
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the bucket count for all tables without an explicit one
+    pub fn bucket_count(mut self, bucket_count: BucketCount) -> Self {
+        self.default = bucket_count;
+        self
+    }
+
+    /// Set the bucket count for a single table
+    pub fn table(mut self, name: &str, bucket_count: BucketCount) -> Self {
+        self.tables.insert(name.to_owned(), bucket_count);
+        self
+    }
+
+    /// Get the bucket count for the table `name`
+    pub fn bucket_count_for(&self, name: &str) -> BucketCount {
+        self.tables.get(name).copied().unwrap_or(self.default)
+    }
+}
+
+/// Copy a table and move its rows into `bucket_count` buckets
+pub fn rehash_table(src: mem::Table<'_>, bucket_count: BucketCount) -> store::Table {
+    let mut table = store::Table::new(0);
+    for column in src.column_iter() {
+        table.push_column(column.name_raw(), column.value_type());
+    }
+    let mut fields = Vec::with_capacity(src.column_count());
+    for row in src.row_iter() {
+        fields.extend(row.field_iter().map(|f| f.map(&mut MemToOwned)));
+        table.push_row_hashed(&fields);
+        fields.clear();
+    }
+    match bucket_count {
+        BucketCount::Keep => table.resize_buckets(src.bucket_count()),
+        BucketCount::Fixed(count) => table.resize_buckets(count),
+        BucketCount::Auto => table.auto_resize_buckets(),
+    }
+    table
+}
+
+/// Copy a database and move the rows of every table into new buckets
+pub fn rehash_database(
+    src: mem::Database<'_>,
+    options: &RehashOptions,
+) -> Result<store::Database, CastError> {
+    let mut db = store::Database::new();
+    for table in src.tables()?.iter() {
+        let table = table?;
+        let bucket_count = options.bucket_count_for(&table.name());
+        db.push_table(table.name_raw(), rehash_table(table, bucket_count));
+    }
+    Ok(db)
+}
+
+/// Check that every lookup by primary key returns the same rows in both tables
+///
+/// This looks up the key of every row of `a` in `b` and the other way around,
+/// and compares the fields of all rows with that key.
+pub fn same_lookups(a: mem::Table<'_>, b: mem::Table<'_>) -> bool {
+    covers_lookups(a, b) && covers_lookups(b, a)
+}
+
+/// Check that the lookups for all keys of `a` are the same in `b`
+fn covers_lookups(a: mem::Table<'_>, b: mem::Table<'_>) -> bool {
+    for row in a.row_iter() {
+        let key = match row.field_at(0) {
+            Some(key) => key.map(&mut MemToOwned),
+            None => continue,
+        };
+        let hash = FdbHash::hash(&key);
+        let lookup = |table: mem::Table<'_>| -> Vec<Vec<_>> {
+            if table.bucket_count() == 0 {
+                return Vec::new();
+            }
+            table
+                .bucket_for_hash(hash)
+                .row_iter()
+                .filter(|row| {
+                    row.field_at(0).map(|f| f.map(&mut MemToOwned)).as_ref() == Some(&key)
+                })
+                .map(|row| row.field_iter().map(|f| f.map(&mut MemToOwned)).collect())
+                .collect()
+        };
+        if lookup(a) != lookup(b) {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        util::tests::{build, TableBuilder},
+        value::{owned::Field, ValueType},
+    };
+    use latin1str::Latin1String;
+
+    /// A table with 16 buckets and rows with duplicate keys
+    fn source() -> Vec<u8> {
+        let mut table = TableBuilder::new("Rows")
+            .column("id", ValueType::Integer)
+            .column("name", ValueType::Text)
+            .buckets(16);
+        for i in 0..40 {
+            table.row(vec![
+                Field::Integer(i % 25),
+                Field::Text(format!("row {}", i)),
+            ]);
+        }
+        let mut key = TableBuilder::new("Keys")
+            .column("key", ValueType::Text)
+            .buckets(1);
+        for name in ["a", "b", "c"] {
+            key.row(vec![Field::Text(name.to_owned())]);
+        }
+        build(vec![table, key])
+    }
+
+    fn table<'a>(buf: &'a [u8], name: &str) -> mem::Table<'a> {
+        mem::Database::new(buf)
+            .tables()
+            .unwrap()
+            .by_name(name)
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_rehash_database() {
+        let src = source();
+        let options = RehashOptions::new()
+            .bucket_count(BucketCount::Fixed(3))
+            .table("Keys", BucketCount::Auto);
+        let db = rehash_database(mem::Database::new(&src), &options).unwrap();
+        let mut out = Vec::new();
+        db.write(&mut out).unwrap();
+
+        let rows = table(&out, "Rows");
+        assert_eq!(rows.bucket_count(), 3);
+        assert_eq!(table(&out, "Keys").bucket_count(), 4);
+        assert!(same_lookups(table(&src, "Rows"), rows));
+        assert!(same_lookups(table(&src, "Keys"), table(&out, "Keys")));
+        assert!(crate::validate::validate_buffer(&out).is_valid());
+
+        // Duplicate keys keep their order
+        let names: Vec<_> = rows
+            .bucket_for_hash(FdbHash::hash(&3i32))
+            .row_iter()
+            .filter(|row| row.field_at(0) == Some(mem::Field::Integer(3)))
+            .map(|row| {
+                row.field_at(1)
+                    .unwrap()
+                    .into_opt_text()
+                    .unwrap()
+                    .decode()
+                    .into_owned()
+            })
+            .collect();
+        assert_eq!(names, ["row 3", "row 28"]);
+    }
+
+    #[test]
+    fn test_rehash_table() {
+        let src = source();
+        for (count, expected) in [
+            (BucketCount::Keep, 16),
+            (BucketCount::Fixed(1), 1),
+            (BucketCount::Auto, 32),
+        ] {
+            let table = rehash_table(table(&src, "Rows"), count);
+            assert_eq!(table.bucket_count(), expected);
+            assert_eq!(table.row_count(), 40);
+        }
+    }
+
+    #[test]
+    fn test_same_lookups() {
+        let src = source();
+        let mut copy = store::Table::from(table(&src, "Rows"));
+        copy.resize_buckets(7);
+        let mut db = store::Database::new();
+        db.push_table(Latin1String::encode("Rows"), copy);
+        let mut out = Vec::new();
+        db.write(&mut out).unwrap();
+        assert_eq!(table(&out, "Rows").bucket_count(), 7);
+        assert!(same_lookups(table(&src, "Rows"), table(&out, "Rows")));
+    }
+}
@@ -22,7 +22,7 @@ impl<B: AsRef<[u8]>, T: Copy> ArcHandle<B, T> {
     /// Borrow the atomically-reference counted handle as a byte handle
     ///
     /// You can use this function to make cloning cheaper
-    pub fn as_bytes_handle(&self) -> Handle<T> {
+    pub fn as_bytes_handle(&self) -> Handle<'_, T> {
         BaseHandle {
             mem: self.mem.as_ref().as_ref(),
             raw: self.raw,
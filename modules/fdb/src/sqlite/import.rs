@@ -0,0 +1,592 @@
+//! # Import from SQLite
+
+use std::{collections::HashMap, convert::TryFrom};
+
+use assembly_core::buffer::CastError;
+use assembly_fdb_core::value::{owned::Field, ValueType};
+use displaydoc::Display;
+use latin1str::Latin1String;
+use rusqlite::{types::ValueRef, Connection};
+use thiserror::Error;
+
+use super::{meta, quote};
+use crate::{mem, store, util::is_latin1};
+
+/// Decides whether a table is imported
+type TableFilter<'a> = Box<dyn Fn(&str) -> bool + 'a>;
+
+/// Options for [`try_import_db`]
+#[derive(Default)]
+pub struct ImportOptions<'a> {
+    template: Option<mem::Database<'a>>,
+    type_map: HashMap<String, ValueType>,
+    column_types: HashMap<(String, String), ValueType>,
+    default_type: Option<ValueType>,
+    ignore_metadata: bool,
+    filter: Option<TableFilter<'a>>,
+}
+
+impl<'a> ImportOptions<'a> {
+    /// Create the default options
+    ///
+    /// These import all tables and use the declared types of the SQLite columns.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take the tables, columns and column types from a template database
+    ///
+    /// Only the tables of the template are imported, in the order of the template.
+    /// The columns are selected by name, other columns of the SQLite tables are ignored.
+    pub fn template(mut self, template: mem::Database<'a>) -> Self {
+        self.template = Some(template);
+        self
+    }
+
+    /// Ignore the schema recorded by a [lossless](super::ExportOptions::lossless) export
+    pub fn ignore_metadata(mut self) -> Self {
+        self.ignore_metadata = true;
+        self
+    }
+
+    /// Use `value_type` for all columns with the declared type `decl_type`
+    ///
+    /// The declared type is compared case-insensitively, and this mapping takes
+    /// precedence over [`ValueType::from_sqlite_type`].
+    pub fn map_type(mut self, decl_type: &str, value_type: ValueType) -> Self {
+        self.type_map
+            .insert(decl_type.to_ascii_uppercase(), value_type);
+        self
+    }
+
+    /// Use `value_type` for one column, regardless of its declared or template type
+    pub fn column_type(mut self, table: &str, column: &str, value_type: ValueType) -> Self {
+        self.column_types
+            .insert((table.to_owned(), column.to_owned()), value_type);
+        self
+    }
+
+    /// Use `value_type` for columns that have no declared type, or an unknown one
+    pub fn default_type(mut self, value_type: ValueType) -> Self {
+        self.default_type = Some(value_type);
+        self
+    }
+
+    /// Only import the tables for which `filter` returns `true`
+    pub fn filter<F>(mut self, filter: F) -> Self
+    where
+        F: Fn(&str) -> bool + 'a,
+    {
+        self.filter = Some(Box::new(filter));
+        self
+    }
+
+    fn includes(&self, table: &str) -> bool {
+        match &self.filter {
+            Some(filter) => filter(table),
+            None => true,
+        }
+    }
+
+    fn column_override(&self, table: &str, column: &str) -> Option<ValueType> {
+        self.column_types
+            .get(&(table.to_owned(), column.to_owned()))
+            .copied()
+    }
+
+    fn resolve(
+        &self,
+        table: &str,
+        column: &str,
+        decl_type: Option<&str>,
+    ) -> Result<ValueType, ImportError> {
+        if let Some(value_type) = self.column_override(table, column) {
+            return Ok(value_type);
+        }
+        let value_type = decl_type.and_then(|decl| {
+            self.type_map
+                .get(&decl.to_ascii_uppercase())
+                .copied()
+                .or_else(|| ValueType::from_sqlite_type(decl))
+        });
+        value_type
+            .or(self.default_type)
+            .ok_or_else(|| match decl_type {
+                Some(decl_type) => ImportError::UnknownType {
+                    table: table.to_owned(),
+                    column: column.to_owned(),
+                    decl_type: decl_type.to_owned(),
+                },
+                None => ImportError::MissingType {
+                    table: table.to_owned(),
+                    column: column.to_owned(),
+                },
+            })
+    }
+}
+
+/// Error when importing a database from SQLite
+#[derive(Debug, Display, Error)]
+pub enum ImportError {
+    /// SQLite error: {0}
+    Sqlite(#[from] rusqlite::Error),
+    /// Failed to read the template: {0}
+    Template(#[from] CastError),
+    /// Failed to read table {table:?}: {source}
+    Table {
+        /// The name of the table
+        table: String,
+        /// The error from SQLite
+        source: rusqlite::Error,
+    },
+    /// Table {table:?} does not exist in the SQLite database
+    MissingTable {
+        /// The name of the table
+        table: String,
+    },
+    /// Table {table:?} has no columns in the template
+    EmptyTemplate {
+        /// The name of the table
+        table: String,
+    },
+    /// Column {column:?} of table {table:?} has the invalid type {value_type} in the metadata
+    InvalidMetadata {
+        /// The name of the table
+        table: String,
+        /// The name of the column
+        column: String,
+        /// The recorded type id
+        value_type: u32,
+    },
+    /// Column {column:?} of table {table:?} has no declared type, use a template or a column type
+    MissingType {
+        /// The name of the table
+        table: String,
+        /// The name of the column
+        column: String,
+    },
+    /// Column {column:?} of table {table:?} has the unknown type {decl_type:?}
+    UnknownType {
+        /// The name of the table
+        table: String,
+        /// The name of the column
+        column: String,
+        /// The declared type of the column
+        decl_type: String,
+    },
+    /// Row {row} of table {table:?}, column {column:?}: cannot store {found} as {expected}
+    Value {
+        /// The name of the table
+        table: String,
+        /// The index of the row, in the order returned by SQLite
+        row: usize,
+        /// The name of the column
+        column: String,
+        /// The type of the column
+        expected: ValueType,
+        /// A description of the SQLite value
+        found: String,
+    },
+}
+
+/// Try to import a database from a SQL connection
+///
+/// This is the inverse of [`try_export_db`](super::try_export_db). Without a
+/// [template](ImportOptions::template), every table of the SQLite database is
+/// imported and the column types are derived from the declared SQLite types.
+/// The rows are placed in buckets by the hash of their first field.
+///
+/// If the database was exported with [metadata](super::ExportOptions::metadata),
+/// the recorded tables, column types and bucket counts are used instead.
+///
+/// ```
+/// use assembly_fdb::{mem, sqlite::{try_import_db, Connection, ImportOptions}, value::ValueType};
+///
+/// let conn = Connection::open_in_memory()?;
+/// conn.execute_batch(
+///     "CREATE TABLE Foo (id INT32, name TEXT4, flag);
+///     INSERT INTO Foo VALUES (1, 'one', 0), (2, 'two', 1);",
+/// )?;
+///
+/// let options = ImportOptions::new().column_type("Foo", "flag", ValueType::Boolean);
+/// let db = try_import_db(&conn, &options)?;
+///
+/// let mut buf = Vec::new();
+/// db.write(&mut buf)?;
+/// let table = mem::Database::new(&buf).tables()?.by_name("Foo").unwrap()?;
+/// assert_eq!(table.row_iter().count(), 2);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn try_import_db(
+    conn: &Connection,
+    options: &ImportOptions<'_>,
+) -> Result<store::Database, ImportError> {
+    let mut db = store::Database::new();
+    let mut exists = conn.prepare(
+        "SELECT count(*) FROM sqlite_master WHERE type IN ('table', 'view') AND name = ?1",
+    )?;
+    let mut check_exists = |name: &str| match exists.query_row([name], |row| row.get::<_, i64>(0)) {
+        Ok(0) => Err(ImportError::MissingTable {
+            table: name.to_owned(),
+        }),
+        Ok(_) => Ok(()),
+        Err(e) => Err(e.into()),
+    };
+
+    let metadata = match (options.template, options.ignore_metadata) {
+        (None, false) => meta::read(conn)?,
+        _ => None,
+    };
+
+    if let Some(template) = options.template {
+        for table in template.tables()?.iter() {
+            let table = table?;
+            let name = table.name();
+            if !options.includes(&name) {
+                continue;
+            }
+            if table.column_count() == 0 {
+                return Err(ImportError::EmptyTemplate {
+                    table: name.into_owned(),
+                });
+            }
+            check_exists(&name)?;
+
+            let columns: Vec<_> = table
+                .column_iter()
+                .map(|c| (c.name().into_owned(), c.value_type()))
+                .collect();
+            let dest = import_table(conn, options, &name, Some(&columns))?;
+            db.push_table(table.name_raw(), dest);
+        }
+    } else if let Some(tables) = metadata {
+        for table in tables.iter().filter(|t| options.includes(&t.name)) {
+            let mut dest = if table.columns.is_empty() {
+                store::Table::new(0)
+            } else {
+                check_exists(&table.name)?;
+                import_table(conn, options, &table.name, Some(&table.columns))?
+            };
+            dest.resize_buckets(table.bucket_count);
+            db.push_table(Latin1String::encode(&table.name), dest);
+        }
+    } else {
+        let mut stmt = conn.prepare(
+            "SELECT name FROM sqlite_master WHERE type = 'table' \
+            AND name NOT LIKE 'sqlite_%' AND name NOT IN (?1, ?2)",
+        )?;
+        let names = stmt
+            .query_map([meta::TABLES, meta::COLUMNS], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        for name in names.iter().filter(|name| options.includes(name)) {
+            let dest = import_table(conn, options, name, None)?;
+            db.push_table(Latin1String::encode(name), dest);
+        }
+    }
+
+    Ok(db)
+}
+
+/// Import a single table
+///
+/// If `columns` is set, only these are selected and their types are used,
+/// otherwise all columns are selected and the types are derived from the declaration.
+fn import_table(
+    conn: &Connection,
+    options: &ImportOptions<'_>,
+    name: &str,
+    columns: Option<&[(String, ValueType)]>,
+) -> Result<store::Table, ImportError> {
+    let table_error = |source| ImportError::Table {
+        table: name.to_owned(),
+        source,
+    };
+    let sql = match columns {
+        Some(columns) => {
+            let names: Vec<String> = columns.iter().map(|(c, _)| quote(c)).collect();
+            format!("SELECT {} FROM {}", names.join(", "), quote(name))
+        }
+        None => format!("SELECT * FROM {}", quote(name)),
+    };
+    let mut stmt = conn.prepare(&sql).map_err(table_error)?;
+
+    // Buckets are sized after all rows were added
+    let mut dest = store::Table::new(0);
+    let mut value_types = Vec::with_capacity(stmt.column_count());
+    for (index, column) in stmt.columns().iter().enumerate() {
+        let value_type = match columns.and_then(|c| c.get(index)) {
+            Some((_, value_type)) => options
+                .column_override(name, column.name())
+                .unwrap_or(*value_type),
+            None => options.resolve(name, column.name(), column.decl_type())?,
+        };
+        dest.push_column(Latin1String::encode(column.name()), value_type);
+        value_types.push((column.name().to_owned(), value_type));
+    }
+
+    let mut rows = stmt.query([]).map_err(table_error)?;
+    let mut fields = Vec::with_capacity(value_types.len());
+    let mut row = 0;
+    while let Some(sqlite_row) = rows.next().map_err(table_error)? {
+        fields.clear();
+        for (index, (column, value_type)) in value_types.iter().enumerate() {
+            let value = sqlite_row.get_ref(index).map_err(table_error)?;
+            let field = convert(*value_type, value).ok_or_else(|| ImportError::Value {
+                table: name.to_owned(),
+                row,
+                column: column.clone(),
+                expected: *value_type,
+                found: describe(value),
+            })?;
+            fields.push(field);
+        }
+        dest.push_row_hashed(&fields);
+        row += 1;
+    }
+
+    dest.auto_resize_buckets();
+    Ok(dest)
+}
+
+/// Convert an SQLite value to a field of the given type
+///
+/// Integers of `Integer` columns may be in the range of `i32` or `u32`. Text
+/// must be stored losslessly as a Windows-1252 string without NUL.
+fn convert(value_type: ValueType, value: ValueRef<'_>) -> Option<Field> {
+    let text = |t| {
+        std::str::from_utf8(t)
+            .ok()
+            .filter(|s| is_latin1(s))
+            .map(str::to_owned)
+    };
+    match (value_type, value) {
+        (_, ValueRef::Null) | (ValueType::Nothing, _) => Some(Field::Nothing),
+        (ValueType::Integer, ValueRef::Integer(i)) => i32::try_from(i)
+            .ok()
+            .or_else(|| u32::try_from(i).ok().map(|u| u as i32))
+            .map(Field::Integer),
+        (ValueType::Boolean, ValueRef::Integer(i)) => Some(Field::Boolean(i != 0)),
+        (ValueType::BigInt, ValueRef::Integer(i)) => Some(Field::BigInt(i)),
+        (ValueType::Float, ValueRef::Integer(i)) => Some(Field::Float(i as f32)),
+        (ValueType::Float, ValueRef::Real(f)) => Some(Field::Float(f as f32)),
+        (ValueType::Text, ValueRef::Text(t)) => text(t).map(Field::Text),
+        (ValueType::VarChar, ValueRef::Text(t)) => text(t).map(Field::VarChar),
+        _ => None,
+    }
+}
+
+/// Describe an SQLite value for an error message
+fn describe(value: ValueRef<'_>) -> String {
+    match value {
+        ValueRef::Null => String::from("NULL"),
+        ValueRef::Integer(i) => format!("the integer {}", i),
+        ValueRef::Real(f) => format!("the real {}", f),
+        ValueRef::Text(t) => match std::str::from_utf8(t) {
+            Ok(s) => format!("the text {:?}", s),
+            Err(_) => String::from("text that is not valid UTF-8"),
+        },
+        ValueRef::Blob(b) => format!("a blob of {} bytes", b.len()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        mem::MemToOwned,
+        sqlite::{try_export_db, try_export_db_with, ExportOptions},
+        util::tests::{build, TableBuilder},
+    };
+
+    fn write(db: &store::Database) -> Vec<u8> {
+        let mut buf = Vec::new();
+        db.write(&mut buf).unwrap();
+        buf
+    }
+
+    fn rows_table() -> TableBuilder {
+        let mut table = TableBuilder::new("Rows")
+            .column("id", ValueType::Integer)
+            .column("name", ValueType::Text)
+            .column("big", ValueType::BigInt)
+            .column("flag", ValueType::Boolean)
+            .column("ratio", ValueType::Float)
+            .column("xml", ValueType::VarChar);
+        for i in 0..10 {
+            table.row(vec![
+                Field::Integer(i - 5),
+                Field::Text(format!("row {}", i)),
+                Field::BigInt(i64::from(i) << 40),
+                Field::Boolean(i % 2 == 0),
+                Field::Float(i as f32 / 4.0),
+                if i == 3 {
+                    Field::Nothing
+                } else {
+                    Field::VarChar(format!("<r i=\"{}\"/>", i))
+                },
+            ]);
+        }
+        table
+    }
+
+    fn source() -> Vec<u8> {
+        let other = TableBuilder::new("Other").column("id", ValueType::Integer);
+        build(vec![rows_table(), other])
+    }
+
+    fn rows(buf: &[u8], name: &str) -> Vec<Vec<Field>> {
+        let table = mem::Database::new(buf)
+            .tables()
+            .unwrap()
+            .by_name(name)
+            .unwrap()
+            .unwrap();
+        table
+            .row_iter()
+            .map(|row| row.field_iter().map(|f| f.map(&mut MemToOwned)).collect())
+            .collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let src = source();
+        let mut conn = Connection::open_in_memory().unwrap();
+        try_export_db(&mut conn, mem::Database::new(&src)).unwrap();
+
+        let db = try_import_db(&conn, &ImportOptions::new().filter(|t| t == "Rows")).unwrap();
+        let out = write(&db);
+        assert_eq!(rows(&src, "Rows"), rows(&out, "Rows"));
+        assert!(mem::Database::new(&out)
+            .tables()
+            .unwrap()
+            .by_name("Other")
+            .is_none());
+    }
+
+    #[test]
+    fn test_quoted_names() {
+        let mut table = TableBuilder::new("My \"Table\"")
+            .column("a]b", ValueType::Integer)
+            .column("[c]", ValueType::Text);
+        table.row(vec![Field::Integer(1), Field::Text(String::from("x"))]);
+        let src = build(vec![table]);
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        try_export_db(&mut conn, mem::Database::new(&src)).unwrap();
+        let out = write(&try_import_db(&conn, &ImportOptions::new()).unwrap());
+        assert_eq!(rows(&src, "My \"Table\""), rows(&out, "My \"Table\""));
+        let columns: Vec<_> = mem::Database::new(&out)
+            .tables()
+            .unwrap()
+            .by_name("My \"Table\"")
+            .unwrap()
+            .unwrap()
+            .column_iter()
+            .map(|c| c.name().into_owned())
+            .collect();
+        assert_eq!(columns, ["a]b", "[c]"]);
+    }
+
+    #[test]
+    fn test_lossless_round_trip() {
+        let src = build(vec![
+            rows_table().buckets(7),
+            TableBuilder::new("Empty")
+                .column("id", ValueType::Integer)
+                .buckets(3),
+            TableBuilder::new("NoColumns").buckets(2),
+        ]);
+
+        let mut conn = Connection::open_in_memory().unwrap();
+        // A schema that does not tell `Text` and `VarChar` apart
+        conn.execute_batch(
+            "CREATE TABLE Rows (id INTEGER, name TEXT, big INTEGER, flag INTEGER, ratio REAL, xml TEXT)",
+        )
+        .unwrap();
+        try_export_db_with(
+            &mut conn,
+            mem::Database::new(&src),
+            &ExportOptions::lossless(),
+        )
+        .unwrap();
+
+        let index: String = conn
+            .query_row(
+                "SELECT sql FROM sqlite_master WHERE type = 'index' AND tbl_name = 'Rows'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(index, "CREATE INDEX \"Rows_id\" ON \"Rows\" (\"id\")");
+
+        // The rows are stored in the order of `row_iter`
+        let expected = write(&store::Database::try_from(mem::Database::new(&src)).unwrap());
+        let out = write(&try_import_db(&conn, &ImportOptions::new()).unwrap());
+        assert_eq!(expected, out);
+    }
+
+    #[test]
+    fn test_template() {
+        let template = build(vec![TableBuilder::new("T")
+            .column("b", ValueType::BigInt)
+            .column("a", ValueType::Boolean)]);
+
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE T (a, b, c); INSERT INTO T VALUES (1, 2, 3);")
+            .unwrap();
+        let options = ImportOptions::new().template(mem::Database::new(&template));
+        let out = write(&try_import_db(&conn, &options).unwrap());
+        assert_eq!(
+            rows(&out, "T"),
+            vec![vec![Field::BigInt(2), Field::Boolean(true)]]
+        );
+
+        conn.execute_batch("DROP TABLE T").unwrap();
+        let err = try_import_db(&conn, &options).err().unwrap();
+        assert!(matches!(err, ImportError::MissingTable { table } if table == "T"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE T (id INT32, name TEXT4);
+            INSERT INTO T VALUES (1, 'a'), (2, 'b'), (3, x'00');",
+        )
+        .unwrap();
+        let err = try_import_db(&conn, &ImportOptions::new()).err().unwrap();
+        assert_eq!(
+            err.to_string(),
+            "Row 2 of table \"T\", column \"name\": cannot store a blob of 1 bytes as TEXT"
+        );
+        conn.execute_batch("UPDATE T SET name = '日本' WHERE id = 3")
+            .unwrap();
+        let err = try_import_db(&conn, &ImportOptions::new()).err().unwrap();
+        assert!(matches!(err, ImportError::Value { row: 2, .. }), "{}", err);
+        conn.execute_batch("UPDATE T SET name = 'a' || char(0) WHERE id = 3")
+            .unwrap();
+        let err = try_import_db(&conn, &ImportOptions::new()).err().unwrap();
+        assert!(matches!(err, ImportError::Value { row: 2, .. }), "{}", err);
+        conn.execute_batch("UPDATE T SET name = 'Grüße' WHERE id = 3")
+            .unwrap();
+        try_import_db(&conn, &ImportOptions::new()).unwrap();
+
+        conn.execute_batch("CREATE TABLE U (id DOUBLE)").unwrap();
+        let options = ImportOptions::new().filter(|t| t == "U");
+        let err = try_import_db(&conn, &options).err().unwrap();
+        assert!(matches!(err, ImportError::UnknownType { decl_type, .. } if decl_type == "DOUBLE"));
+
+        let options = options.map_type("double", ValueType::Float);
+        let db = try_import_db(&conn, &options).unwrap();
+        let table = mem::Database::new(&write(&db))
+            .tables()
+            .unwrap()
+            .by_name("U")
+            .unwrap()
+            .unwrap()
+            .column_at(0)
+            .unwrap()
+            .value_type();
+        assert_eq!(table, ValueType::Float);
+    }
+}
@@ -0,0 +1,117 @@
+//! # Schema metadata
+//!
+//! The declared types of an SQLite database may not match the FDB column types,
+//! e.g. if the tables were created from a separate schema. A lossless export
+//! records the schema of the FDB file in two additional tables:
+//!
+//! - `_fdb_tables (name, position, bucket_count)`
+//! - `_fdb_columns (table_name, position, name, value_type)`, where `value_type`
+//!   is the numeric id of the [`ValueType`](crate::value::ValueType)
+
+#[cfg(feature = "store")]
+use std::convert::TryFrom;
+
+#[cfg(feature = "store")]
+use assembly_fdb_core::value::ValueType;
+use rusqlite::{params, Connection};
+
+#[cfg(feature = "store")]
+use super::ImportError;
+use crate::mem::Table;
+
+/// The name of the table that lists the tables
+pub(super) const TABLES: &str = "_fdb_tables";
+/// The name of the table that lists the columns
+pub(super) const COLUMNS: &str = "_fdb_columns";
+
+/// The recorded schema of a single table
+#[cfg(feature = "store")]
+pub(super) struct TableMeta {
+    pub name: String,
+    pub bucket_count: usize,
+    pub columns: Vec<(String, ValueType)>,
+}
+
+/// Create the metadata tables, if they don't exist
+pub(super) fn create(conn: &Connection) -> rusqlite::Result<()> {
+    conn.execute_batch(&format!(
+        "CREATE TABLE IF NOT EXISTS {} (
+            name TEXT PRIMARY KEY,
+            position INTEGER NOT NULL,
+            bucket_count INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS {} (
+            table_name TEXT NOT NULL,
+            position INTEGER NOT NULL,
+            name TEXT NOT NULL,
+            value_type INTEGER NOT NULL,
+            PRIMARY KEY (table_name, position)
+        );",
+        TABLES, COLUMNS
+    ))
+}
+
+/// Record the schema of `table`
+pub(super) fn insert(conn: &Connection, position: usize, table: Table<'_>) -> rusqlite::Result<()> {
+    let name = table.name();
+    conn.execute(
+        &format!("INSERT OR REPLACE INTO {} VALUES (?1, ?2, ?3)", TABLES),
+        params![name, position, table.bucket_count()],
+    )?;
+    let mut stmt = conn.prepare(&format!(
+        "INSERT OR REPLACE INTO {} VALUES (?1, ?2, ?3, ?4)",
+        COLUMNS
+    ))?;
+    for (index, column) in table.column_iter().enumerate() {
+        let value_type = u32::from(column.value_type());
+        stmt.execute(params![name, index, column.name(), value_type])?;
+    }
+    Ok(())
+}
+
+/// Read the recorded schema, if there is one
+#[cfg(feature = "store")]
+pub(super) fn read(conn: &Connection) -> Result<Option<Vec<TableMeta>>, ImportError> {
+    let exists: i64 = conn.query_row(
+        "SELECT count(*) FROM sqlite_master WHERE type = 'table' AND name IN (?1, ?2)",
+        [TABLES, COLUMNS],
+        |row| row.get(0),
+    )?;
+    if exists < 2 {
+        return Ok(None);
+    }
+
+    let mut tables = conn
+        .prepare(&format!(
+            "SELECT name, bucket_count FROM {} ORDER BY position",
+            TABLES
+        ))?
+        .query_map([], |row| {
+            Ok(TableMeta {
+                name: row.get(0)?,
+                bucket_count: row.get(1)?,
+                columns: Vec::new(),
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT name, value_type FROM {} WHERE table_name = ?1 ORDER BY position",
+        COLUMNS
+    ))?;
+    for table in &mut tables {
+        let mut rows = stmt.query([&table.name])?;
+        while let Some(row) = rows.next()? {
+            let name: String = row.get(0)?;
+            let value_type: u32 = row.get(1)?;
+            let value_type =
+                ValueType::try_from(value_type).map_err(|_| ImportError::InvalidMetadata {
+                    table: table.name.clone(),
+                    column: name.clone(),
+                    value_type,
+                })?;
+            table.columns.push((name, value_type));
+        }
+    }
+    Ok(Some(tables))
+}
@@ -2,34 +2,137 @@
 
 use std::fmt::Write;
 
+use assembly_core::buffer::CastError;
+use displaydoc::Display;
 use rusqlite::params_from_iter;
 pub use rusqlite::{Connection, Error, Result};
+use thiserror::Error;
 
+#[cfg(feature = "store")]
+mod import;
+mod meta;
 #[cfg(feature = "sqlite-vtab")]
 mod vtab;
+#[cfg(feature = "store")]
+pub use import::{try_import_db, ImportError, ImportOptions};
 #[cfg(feature = "sqlite-vtab")]
-pub use vtab::load_module;
+pub use vtab::{create_virtual_tables, load_all_tables, load_arc_module, load_module};
 
 use super::mem::Database;
 
+/// Quote an SQL identifier
+fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// Options for [`try_export_db_with`]
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    metadata: bool,
+    indexes: bool,
+}
+
+impl ExportOptions {
+    /// Create the default options, which only create the tables and insert the rows
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable everything that is needed to import the database without losing information
+    pub fn lossless() -> Self {
+        Self {
+            metadata: true,
+            indexes: true,
+        }
+    }
+
+    /// Record the column types and bucket counts in the tables `_fdb_tables` and `_fdb_columns`
+    ///
+    /// [`try_import_db`] uses these to restore the exact schema.
+    pub fn metadata(mut self, metadata: bool) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    /// Create an index on the first column of every table
+    pub fn indexes(mut self, indexes: bool) -> Self {
+        self.indexes = indexes;
+        self
+    }
+}
+
+/// Error when exporting a database to SQLite
+#[derive(Debug, Display, Error)]
+pub enum ExportError {
+    /// SQLite error: {0}
+    Sqlite(#[from] rusqlite::Error),
+    /// Failed to read the database: {0}
+    Cast(#[from] CastError),
+}
+
+impl From<ExportError> for rusqlite::Error {
+    fn from(e: ExportError) -> Self {
+        match e {
+            ExportError::Sqlite(e) => e,
+            ExportError::Cast(e) => rusqlite::Error::ToSqlConversionFailure(Box::new(e)),
+        }
+    }
+}
+
+/// Try to export a database to a SQL connection
+///
+/// This is the same as [`try_export_db_with`] with the default options. If the
+/// database can't be read, the [`CastError`] is returned as
+/// [`Error::ToSqlConversionFailure`].
+pub fn try_export_db(conn: &mut Connection, db: Database) -> rusqlite::Result<()> {
+    try_export_db_with(conn, db, &ExportOptions::new()).map_err(Error::from)
+}
+
 /// Try to export a database to a SQL connection
 ///
 /// This function does the following:
 ///
 /// 1. `BEGIN`s a transaction
 /// 2. For every table:
-///   a. Run `CREATE TABLE IF NOT EXISTS`
-///   b. Prepares an `INSERT` statement
-///   c. Runs the insert with data from every row
+///    a. Run `CREATE TABLE IF NOT EXISTS`
+///    b. Prepares an `INSERT` statement
+///    c. Runs the insert with data from every row
+///    d. Optionally, creates an index and records the schema
 /// 3. `COMMIT`s the transaction
-pub fn try_export_db(conn: &mut Connection, db: Database) -> rusqlite::Result<()> {
-    conn.execute("BEGIN", rusqlite::params![])?;
+///
+/// Tables without columns can't be created in SQLite, they are only recorded
+/// in the metadata. If any step fails, the transaction is rolled back.
+///
+/// ```
+/// use assembly_fdb::{mem, sqlite::{try_export_db_with, Connection, ExportOptions}};
+///
+/// # let buf = vec![0, 0, 0, 0, 8, 0, 0, 0];
+/// let mut conn = Connection::open_in_memory()?;
+/// try_export_db_with(&mut conn, mem::Database::new(&buf), &ExportOptions::lossless())?;
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+pub fn try_export_db_with(
+    conn: &mut Connection,
+    db: Database,
+    options: &ExportOptions,
+) -> Result<(), ExportError> {
+    let tx = conn.transaction()?;
+    if options.metadata {
+        meta::create(&tx)?;
+    }
+
+    for (position, table) in db.tables()?.iter().enumerate() {
+        let table = table?;
+        if options.metadata {
+            meta::insert(&tx, position, table)?;
+        }
+        if table.column_count() == 0 {
+            continue;
+        }
 
-    let tables = db.tables().unwrap();
-    for table in tables.iter() {
-        let table = table.unwrap();
-        let mut create_query = format!("CREATE TABLE IF NOT EXISTS \"{}\"\n(\n", table.name());
-        let mut insert_query = format!("INSERT INTO \"{}\" (", table.name());
+        let name = quote(&table.name());
+        let mut create_query = format!("CREATE TABLE IF NOT EXISTS {}\n(\n", name);
+        let mut insert_query = format!("INSERT INTO {} (", name);
         let mut first = true;
         for col in table.column_iter() {
             if first {
@@ -39,8 +142,9 @@ pub fn try_export_db(conn: &mut Connection, db: Database) -> rusqlite::Result<()
                 write!(insert_query, ", ").unwrap();
             }
             let typ = col.value_type().to_sqlite_type();
-            write!(create_query, "    [{}] {}", col.name(), typ).unwrap();
-            write!(insert_query, "[{}]", col.name()).unwrap();
+            let col_name = quote(&col.name());
+            write!(create_query, "    {} {}", col_name, typ).unwrap();
+            write!(insert_query, "{}", col_name).unwrap();
         }
         create_query.push_str(");");
         insert_query.push_str(") VALUES (?1");
@@ -48,14 +152,27 @@ pub fn try_export_db(conn: &mut Connection, db: Database) -> rusqlite::Result<()
             write!(insert_query, ", ?{}", i).unwrap();
         }
         insert_query.push_str(");");
-        conn.execute(&create_query, rusqlite::params![])?;
+        tx.execute(&create_query, rusqlite::params![])?;
 
-        let mut stmt = conn.prepare(&insert_query)?;
+        let mut stmt = tx.prepare(&insert_query)?;
         for row in table.row_iter() {
             stmt.execute(params_from_iter(row.field_iter()))?;
         }
+
+        if let (true, Some(key)) = (options.indexes, table.column_at(0)) {
+            let index = quote(&format!("{}_{}", table.name(), key.name()));
+            tx.execute(
+                &format!(
+                    "CREATE INDEX IF NOT EXISTS {} ON {} ({})",
+                    index,
+                    name,
+                    quote(&key.name())
+                ),
+                rusqlite::params![],
+            )?;
+        }
     }
 
-    conn.execute("COMMIT", rusqlite::params![])?;
+    tx.commit()?;
     Ok(())
 }
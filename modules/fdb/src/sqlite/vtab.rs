@@ -1,15 +1,22 @@
 //! # Virtual Table implementation
 
-use rusqlite::vtab::{read_only_module, CreateVTab, IndexConstraintOp, VTab, VTabCursor};
+use std::convert::TryFrom;
 
-use crate::mem;
+use assembly_fdb_core::{value::ValueType, FdbHash};
+use rusqlite::{
+    types::Value,
+    vtab::{read_only_module, CreateVTab, IndexConstraintOp, VTab, VTabCursor},
+};
+
+use super::quote;
+use crate::mem::{self, arc::ArcTable, ArcDatabase};
 
 #[repr(C)]
-struct FdbTab<'vtab> {
+struct FdbTab {
     /// Base class. Must be first
     base: rusqlite::vtab::sqlite3_vtab,
     /* Virtual table implementations will typically add additional fields */
-    table: mem::Table<'vtab>,
+    table: ArcTable,
 }
 
 /// Register the module
@@ -17,9 +24,65 @@ pub fn load_module(
     conn: &rusqlite::Connection,
     db: mem::Database<'static>,
 ) -> rusqlite::Result<()> {
+    load_arc_module(conn, ArcDatabase::from(db))
+}
+
+/// Register the module for a database that owns its buffer
+pub fn load_arc_module(conn: &rusqlite::Connection, db: ArcDatabase) -> rusqlite::Result<()> {
     conn.create_module("fdb", read_only_module::<FdbTab>(), Some(db))
 }
 
+/// Create a virtual table in the `temp` schema for every table of the database
+///
+/// This requires that the module was loaded with [`load_module`] or [`load_arc_module`].
+pub fn create_virtual_tables(
+    conn: &rusqlite::Connection,
+    tables: mem::Tables<'_>,
+) -> rusqlite::Result<()> {
+    for table in tables.iter() {
+        let table = table.map_err(|e| {
+            rusqlite::Error::ModuleError(format!("FDB: Failed to get [Tables]: {}", e))
+        })?;
+        let name = quote(&table.name());
+        let sql = format!("CREATE VIRTUAL TABLE temp.{} USING fdb({});", name, name);
+        conn.execute(&sql, [])?;
+    }
+    Ok(())
+}
+
+/// Register the module and create a virtual table for every table of the database
+pub fn load_all_tables(conn: &rusqlite::Connection, db: ArcDatabase) -> rusqlite::Result<()> {
+    load_arc_module(conn, db.clone())?;
+    let tables = db
+        .tables()
+        .map_err(|e| rusqlite::Error::ModuleError(format!("FDB: Failed to get [Tables]: {}", e)))?;
+    create_virtual_tables(conn, tables.as_mem())
+}
+
+/// Remove the quotes of an SQL identifier, if present
+fn unquote(name: &str) -> String {
+    match name.as_bytes().first() {
+        Some(b'"') | Some(b'\'') | Some(b'`') if name.len() >= 2 => {
+            let quote = &name[..1];
+            name[1..name.len() - 1].replace(&quote.repeat(2), quote)
+        }
+        Some(b'[') if name.ends_with(']') => name[1..name.len() - 1].to_owned(),
+        _ => name.to_owned(),
+    }
+}
+
+/// Get the hash of a primary key, if the value has the type of the column
+fn hash_value(value_type: ValueType, value: &Value) -> Option<u32> {
+    match (value_type, value) {
+        (ValueType::Integer, Value::Integer(i)) => i32::try_from(*i).ok().map(|i| i.hash()),
+        (ValueType::BigInt, Value::Integer(i)) => Some(i.hash()),
+        (ValueType::Boolean, Value::Integer(i)) => Some((*i != 0).hash()),
+        (ValueType::Float, Value::Real(f)) => Some((*f as f32).hash()),
+        (ValueType::Text | ValueType::VarChar, Value::Text(t)) => Some(t.hash()),
+        _ => None,
+    }
+}
+
 struct BufferedIter<'vtab> {
     /// The backing iterator
     iter: mem::iter::TableRowIter<'vtab>,
@@ -66,12 +129,17 @@ unsafe impl<'vtab> VTabCursor for FdbTabCursor<'vtab> {
         _idx_str: Option<&str>,
         args: &rusqlite::vtab::Values<'_>,
     ) -> rusqlite::Result<()> {
-        self.iter = BufferedIter::new(if idx_num == 1 {
-            // Filter by first column
-            let idx_value = args.get::<u32>(0)?;
-            self.table.bucket_index_iter(idx_value)
-        } else {
-            self.table.row_iter()
+        // Filter by first column. SQLite checks the constraint again, so
+        // falling back to all rows is always correct.
+        let hash = match (idx_num, self.table.column_at(0)) {
+            (1, Some(column)) if self.table.bucket_count() > 0 => {
+                hash_value(column.value_type(), &args.get::<Value>(0)?)
+            }
+            _ => None,
+        };
+        self.iter = BufferedIter::new(match hash {
+            Some(hash) => self.table.bucket_index_iter(hash),
+            None => self.table.row_iter(),
         });
         Ok(())
     }
@@ -114,8 +182,8 @@ unsafe impl<'vtab> VTabCursor for FdbTabCursor<'vtab> {
     }
 }
 
-unsafe impl<'vtab> VTab<'vtab> for FdbTab<'vtab> {
-    type Aux = mem::Database<'vtab>;
+unsafe impl<'vtab> VTab<'vtab> for FdbTab {
+    type Aux = ArcDatabase;
 
     type Cursor = FdbTabCursor<'vtab>;
 
@@ -126,10 +194,16 @@ unsafe impl<'vtab> VTab<'vtab> for FdbTab<'vtab> {
     ) -> rusqlite::Result<(String, Self)> {
         match *args {
             [] => Err(rusqlite::Error::InvalidParameterCount(0, 1)),
-            [mod_name, db_name, table_name] => {
-                let name = std::str::from_utf8(table_name).map_err(|e| {
-                    rusqlite::Error::ModuleError(format!("FDB: Table name is invalid UTF-8: {}", e))
-                })?;
+            [mod_name, db_name, table_name, ..] if args.len() <= 4 => {
+                // `USING fdb` uses the name of the virtual table, `USING fdb(X)` the table `X`
+                let name = std::str::from_utf8(args.get(3).copied().unwrap_or(table_name))
+                    .map_err(|e| {
+                        rusqlite::Error::ModuleError(format!(
+                            "FDB: Table name is invalid UTF-8: {}",
+                            e
+                        ))
+                    })?;
+                let name = &unquote(name.trim());
 
                 let table = aux
                     .ok_or_else(|| {
@@ -155,7 +229,7 @@ unsafe impl<'vtab> VTab<'vtab> for FdbTab<'vtab> {
                     if index > 0 {
                         schema.push_str(", ");
                     }
-                    schema.push_str(col.name().as_ref());
+                    schema.push_str(&quote(&col.name()));
                     schema.push(' ');
                     schema.push_str(col.value_type().to_sqlite_type());
                 }
@@ -200,13 +274,13 @@ unsafe impl<'vtab> VTab<'vtab> for FdbTab<'vtab> {
     fn open(&'vtab self) -> rusqlite::Result<Self::Cursor> {
         Ok(FdbTabCursor {
             base: rusqlite::vtab::sqlite3_vtab_cursor::default(),
-            table: self.table,
-            iter: BufferedIter::new(self.table.row_iter()),
+            table: self.table.as_mem(),
+            iter: BufferedIter::new(self.table.as_mem().row_iter()),
         })
     }
 }
 
-impl<'vtab> CreateVTab<'vtab> for FdbTab<'vtab> {
+impl<'vtab> CreateVTab<'vtab> for FdbTab {
     fn create(
         db: &mut rusqlite::vtab::VTabConnection,
         aux: Option<&Self::Aux>,
@@ -219,3 +293,66 @@ impl<'vtab> CreateVTab<'vtab> for FdbTab<'vtab> {
         Ok(())
     }
 }
+
+#[cfg(all(test, feature = "store"))]
+mod tests {
+    use super::*;
+    use crate::{
+        util::tests::{build, TableBuilder},
+        value::owned::Field,
+    };
+
+    fn database() -> ArcDatabase {
+        let mut texts = TableBuilder::new("Text Keys")
+            .column("key", ValueType::Text)
+            .column("value", ValueType::Integer);
+        let mut bigints = TableBuilder::new("BigIntKeys")
+            .column("key", ValueType::BigInt)
+            .column("table", ValueType::Integer);
+        for i in 0..32 {
+            texts.row(vec![Field::Text(format!("key {}", i)), Field::Integer(i)]);
+            bigints.row(vec![Field::BigInt(i64::from(i) << 33), Field::Integer(i)]);
+        }
+        ArcDatabase::new(build(vec![texts, bigints]))
+    }
+
+    #[test]
+    fn test_primary_key_lookup() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        load_all_tables(&conn, database()).unwrap();
+
+        let value: i32 = conn
+            .query_row(
+                "SELECT value FROM \"Text Keys\" WHERE key = 'key 17'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(value, 17);
+
+        let value: i32 = conn
+            .query_row(
+                "SELECT \"table\" FROM BigIntKeys WHERE key = ?1",
+                [5i64 << 33],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(value, 5);
+
+        let count: i32 = conn
+            .query_row(
+                "SELECT count(*) FROM BigIntKeys WHERE key = 'x'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_unquote() {
+        assert_eq!(unquote("\"a \"\"b\"\"\""), "a \"b\"");
+        assert_eq!(unquote("[Text Keys]"), "Text Keys");
+        assert_eq!(unquote("Plain"), "Plain");
+    }
+}
@@ -0,0 +1,367 @@
+//! # Layout of an existing file
+//!
+//! The regular writer of [`Database`] places all structures in a fixed order.
+//! The files shipped with the game use a different order, and may share strings
+//! or contain padding. A [`Layout`] records the address of every structure of a
+//! file, so that [`Database::write_with_layout`] can reproduce it byte by byte.
+
+use std::{
+    collections::{BTreeMap, HashSet},
+    convert::{TryFrom, TryInto},
+    io,
+    ops::Range,
+};
+
+use assembly_core::buffer::CastError;
+use assembly_fdb_core::value::ValueType;
+use latin1str::{Latin1Str, Latin1String};
+
+use super::{Database, Field, I64Ref, Table, TextRef};
+
+/// The addresses of all structures in an existing database file
+///
+/// ```
+/// use std::convert::TryFrom;
+/// use assembly_fdb::{mem, store::{Database, Layout}};
+///
+/// # let mut buf = Vec::new();
+/// # Database::new().write(&mut buf).unwrap();
+/// let src = mem::Database::new(&buf);
+/// let layout = Layout::capture(src)?;
+/// let db = Database::try_from(src)?;
+///
+/// let mut out = Vec::new();
+/// db.write_with_layout(&layout, &mut out)?;
+/// assert_eq!(buf, out);
+/// # Ok::<(), Box<dyn std::error::Error>>(())
+/// ```
+#[derive(Clone)]
+pub struct Layout {
+    /// The size of the file
+    len: usize,
+    /// The address of the table header list
+    table_list: u32,
+    tables: Vec<(Latin1String, TableLayout)>,
+    /// Bytes that are not part of any structure, e.g. padding after strings
+    fill: Vec<(u32, Vec<u8>)>,
+}
+
+#[derive(Debug, Clone)]
+struct TableLayout {
+    def_header: u32,
+    data_header: u32,
+    column_list: u32,
+    name: u32,
+    column_names: Vec<u32>,
+    bucket_list: u32,
+    rows: Vec<RowLayout>,
+}
+
+#[derive(Debug, Clone)]
+struct RowLayout {
+    /// The address of the row header list entry
+    entry: u32,
+    header: u32,
+    field_list: u32,
+    /// The address of the string or `i64` of every field, or 0
+    values: Vec<u32>,
+}
+
+/// Reads the structures of a file and records the ranges they cover
+struct Capture<'a> {
+    buf: &'a [u8],
+    covered: Vec<Range<usize>>,
+}
+
+impl<'a> Capture<'a> {
+    fn words<const N: usize>(&mut self, offset: u32) -> Result<[u32; N], CastError> {
+        let start = offset as usize;
+        let bytes = self
+            .buf
+            .get(start..start + 4 * N)
+            .ok_or(CastError::OutOfBounds { offset })?;
+        let mut words = [0; N];
+        for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+            *word = u32::from_le_bytes(chunk.try_into().unwrap());
+        }
+        self.covered.push(start..start + 4 * N);
+        Ok(words)
+    }
+
+    /// Check that `count` elements of `size` bytes at `base` fit into the buffer
+    fn array(&self, base: u32, count: u32, size: u32) -> Result<(), CastError> {
+        let end = u64::from(base) + u64::from(count) * u64::from(size);
+        if end > self.buf.len() as u64 {
+            return Err(CastError::OutOfBounds { offset: base });
+        }
+        Ok(())
+    }
+
+    /// Get the address of the element at `index` of an array at `base`
+    fn element(base: u32, index: u32, size: u32) -> Result<u32, CastError> {
+        index
+            .checked_mul(size)
+            .and_then(|offset| base.checked_add(offset))
+            .ok_or(CastError::OutOfBounds { offset: base })
+    }
+
+    fn string(&mut self, offset: u32) -> Result<&'a Latin1Str, CastError> {
+        let start = offset as usize;
+        let rest = self
+            .buf
+            .get(start..)
+            .filter(|rest| rest.contains(&0))
+            .ok_or(CastError::OutOfBounds { offset })?;
+        let string = Latin1Str::from_bytes_until_nul(rest);
+        self.covered.push(start..start + string.len() + 1);
+        Ok(string)
+    }
+
+    fn table(&mut self, def_header: u32, data_header: u32) -> Result<TableLayout, CastError> {
+        let [column_count, name, column_list] = self.words(def_header)?;
+        self.string(name)?;
+        self.array(column_list, column_count, 8)?;
+        let mut column_names = Vec::with_capacity(column_count as usize);
+        for index in 0..column_count {
+            let [_value_type, column_name] = self.words(Self::element(column_list, index, 8)?)?;
+            self.string(column_name)?;
+            column_names.push(column_name);
+        }
+
+        let [bucket_count, bucket_list] = self.words(data_header)?;
+        self.array(bucket_list, bucket_count, 4)?;
+        let mut rows = Vec::new();
+        let mut visited = HashSet::new();
+        for index in 0..bucket_count {
+            let [mut entry] = self.words(Self::element(bucket_list, index, 4)?)?;
+            while entry != u32::MAX && visited.insert(entry) {
+                let [header, next] = self.words(entry)?;
+                let [field_count, field_list] = self.words(header)?;
+                self.array(field_list, field_count, 8)?;
+                let mut values = Vec::with_capacity(field_count as usize);
+                for index in 0..field_count {
+                    let [data_type, value] = self.words(Self::element(field_list, index, 8)?)?;
+                    values.push(match ValueType::try_from(data_type) {
+                        Ok(ValueType::Text) | Ok(ValueType::VarChar) => {
+                            self.string(value)?;
+                            value
+                        }
+                        Ok(ValueType::BigInt) => {
+                            self.words::<2>(value)?;
+                            value
+                        }
+                        _ => 0,
+                    });
+                }
+                rows.push(RowLayout {
+                    entry,
+                    header,
+                    field_list,
+                    values,
+                });
+                entry = next;
+            }
+        }
+
+        Ok(TableLayout {
+            def_header,
+            data_header,
+            column_list,
+            name,
+            column_names,
+            bucket_list,
+            rows,
+        })
+    }
+
+    /// Get all bytes that are not covered by a structure
+    fn fill(mut self) -> Vec<(u32, Vec<u8>)> {
+        self.covered.sort_by_key(|r| r.start);
+        let mut fill = Vec::new();
+        let mut pos = 0;
+        for range in self.covered {
+            if range.start > pos {
+                fill.push((pos as u32, self.buf[pos..range.start].to_vec()));
+            }
+            pos = pos.max(range.end);
+        }
+        if pos < self.buf.len() {
+            fill.push((pos as u32, self.buf[pos..].to_vec()));
+        }
+        fill
+    }
+}
+
+impl Layout {
+    /// Record the layout of a database
+    ///
+    /// The rows are recorded in the same order in which
+    /// [`Database::try_from`](TryFrom::try_from) adds them to each table.
+    pub fn capture(src: crate::mem::Database<'_>) -> Result<Self, CastError> {
+        let mut capture = Capture {
+            buf: src.as_bytes(),
+            covered: Vec::new(),
+        };
+        let [count, table_list] = capture.words(0)?;
+        capture.array(table_list, count, 8)?;
+        let mut tables = Vec::with_capacity(count as usize);
+        for index in 0..count {
+            let [def_header, data_header] =
+                capture.words(Capture::element(table_list, index, 8)?)?;
+            let table = capture.table(def_header, data_header)?;
+            let name = capture.string(table.name)?.to_owned();
+            tables.push((name, table));
+        }
+        Ok(Self {
+            len: capture.buf.len(),
+            table_list,
+            tables,
+            fill: capture.fill(),
+        })
+    }
+}
+
+fn mismatch(table: &Latin1Str, what: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!(
+            "{} of table {:?} does not match the layout",
+            what,
+            table.decode()
+        ),
+    )
+}
+
+/// The output buffer
+struct Image(Vec<u8>);
+
+impl Image {
+    fn put(&mut self, offset: u32, bytes: &[u8]) -> io::Result<()> {
+        let start = offset as usize;
+        self.0
+            .get_mut(start..start + bytes.len())
+            .ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("structure at {:#x} is outside of the layout", offset),
+                )
+            })?
+            .copy_from_slice(bytes);
+        Ok(())
+    }
+
+    fn words(&mut self, offset: u32, words: &[u32]) -> io::Result<()> {
+        let bytes: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+        self.put(offset, &bytes)
+    }
+
+    fn string(&mut self, offset: u32, string: &Latin1Str) -> io::Result<()> {
+        self.put(offset, string.as_bytes())?;
+        self.put(offset + string.len() as u32, &[0])
+    }
+}
+
+impl Table {
+    fn write_layout(
+        &self,
+        name: &Latin1Str,
+        layout: &TableLayout,
+        out: &mut Image,
+    ) -> io::Result<()> {
+        if self.columns.len() != layout.column_names.len() {
+            return Err(mismatch(name, "column count"));
+        }
+        if self.rows.len() != layout.rows.len() {
+            return Err(mismatch(name, "row count"));
+        }
+
+        let column_count = self.columns.len() as u32;
+        out.words(
+            layout.def_header,
+            &[column_count, layout.name, layout.column_list],
+        )?;
+        out.string(layout.name, name)?;
+        for (index, (column, &addr)) in self.columns.iter().zip(&layout.column_names).enumerate() {
+            let header = layout.column_list + index as u32 * 8;
+            out.words(header, &[column.data_type.into(), addr])?;
+            out.string(addr, &column.name)?;
+        }
+
+        let entry = |row: Option<usize>| row.map_or(u32::MAX, |row| layout.rows[row].entry);
+        let bucket_count = self.buckets.len() as u32;
+        out.words(layout.data_header, &[bucket_count, layout.bucket_list])?;
+        for (index, bucket) in self.buckets.iter().enumerate() {
+            let head = entry(bucket.first_row_last.map(|(first, _)| first));
+            out.words(layout.bucket_list + index as u32 * 4, &[head])?;
+        }
+
+        for (row, row_layout) in self.rows.iter().zip(&layout.rows) {
+            if row.count as usize != row_layout.values.len() {
+                return Err(mismatch(name, "field count"));
+            }
+            out.words(row_layout.entry, &[row_layout.header, entry(row.next_row)])?;
+            out.words(row_layout.header, &[row.count, row_layout.field_list])?;
+
+            let fields = &self.fields[row.first_field_index..][..row.count as usize];
+            for (index, (field, &addr)) in fields.iter().zip(&row_layout.values).enumerate() {
+                let (data_type, value) = match field {
+                    Field::Nothing => (0, 0),
+                    Field::Integer(i) => (1, *i as u32),
+                    Field::Float(f) => (3, f.to_bits()),
+                    Field::Text(TextRef { outer, inner }) => {
+                        out.string(addr, &self.strings[outer][*inner])?;
+                        (4, addr)
+                    }
+                    Field::Boolean(b) => (5, u32::from(*b)),
+                    Field::BigInt(I64Ref { index }) => {
+                        out.put(addr, &self.i64s[*index].to_le_bytes())?;
+                        (6, addr)
+                    }
+                    Field::VarChar(TextRef { outer, inner }) => {
+                        out.string(addr, &self.strings[outer][*inner])?;
+                        (8, addr)
+                    }
+                };
+                out.words(
+                    row_layout.field_list + index as u32 * 8,
+                    &[data_type, value],
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Database {
+    /// Write the database, placing every structure where it was in the file of `layout`
+    ///
+    /// This fails if the tables, columns or rows don't match the layout, e.g.
+    /// because the database was modified after it was loaded.
+    pub fn write_with_layout<O: io::Write>(&self, layout: &Layout, out: &mut O) -> io::Result<()> {
+        let mut image = Image(vec![0; layout.len]);
+        for (offset, bytes) in &layout.fill {
+            image.put(*offset, bytes)?;
+        }
+
+        let names: BTreeMap<&Latin1Str, &TableLayout> =
+            layout.tables.iter().map(|(n, t)| (&**n, t)).collect();
+        if names.len() != self.tables.len() || layout.tables.len() != self.tables.len() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "the tables do not match the layout",
+            ));
+        }
+
+        image.words(0, &[self.tables.len() as u32, layout.table_list])?;
+        for (index, (name, table_layout)) in layout.tables.iter().enumerate() {
+            let table = self
+                .tables
+                .get(name)
+                .ok_or_else(|| mismatch(name, "existence"))?;
+            let header = layout.table_list + index as u32 * 8;
+            image.words(header, &[table_layout.def_header, table_layout.data_header])?;
+            table.write_layout(name, table_layout, &mut image)?;
+        }
+        out.write_all(&image.0)
+    }
+}
@@ -34,35 +34,64 @@
 //! let mut out: Vec<u8> = Vec::new();
 //! db.write(&mut out).expect("success");
 //! ```
+//!
+//! ## Conversions
+//!
+//! A [`Database`] can also be created from an existing [`mem::Database`] with
+//! [`TryFrom`], or from a `core::Schema` with [`From`] (requires the `core` feature).
+//! All rows keep the index of their bucket.
+//!
+//! To reproduce the exact bytes of an existing file, record its [`Layout`] and
+//! use [`Database::write_with_layout`].
 
-use crate::io::write::WriteLE;
+use crate::{io::write::WriteLE, mem};
+use assembly_core::buffer::CastError;
 use assembly_fdb_core::{
     file::{
         ArrayHeader, FDBBucketHeader, FDBColumnHeader, FDBFieldData, FDBHeader, FDBRowHeader,
         FDBRowHeaderListEntry, FDBTableDataHeader, FDBTableDefHeader, FDBTableHeader,
     },
     value::{owned::OwnedContext, Context, Value, ValueMapperMut, ValueType},
+    FdbHash,
 };
 use latin1str::{Latin1Str, Latin1String};
 use std::{
-    collections::BTreeMap,
+    collections::{BTreeMap, BTreeSet},
     convert::{TryFrom, TryInto},
     io,
     mem::size_of,
 };
 
+mod layout;
 #[cfg(test)]
 mod tests;
 
+pub use layout::Layout;
+
 /// Calculates the number of 4-byte units that are needed to store
 /// this string with at least one null terminator.
 fn req_buf_len(s: &Latin1Str) -> usize {
     s.len() / 4 + 1
 }
 
+/// The scope in which identical strings and `i64` values are stored only once
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum Interning {
+    /// Only intern values in tables that have [`Table::set_interning`] enabled
+    #[default]
+    None,
+    /// Intern values within every table
+    Table,
+    /// Intern values across all tables of the database
+    ///
+    /// Fields may then point into the data of a previous table.
+    Database,
+}
+
 /// The whole database
 pub struct Database {
     tables: BTreeMap<Latin1String, Table>,
+    interning: Interning,
 }
 
 impl Default for Database {
@@ -76,9 +105,38 @@ impl Database {
     pub fn new() -> Self {
         Self {
             tables: BTreeMap::new(),
+            interning: Interning::None,
         }
     }
 
+    /// Set the scope in which values are interned when writing
+    ///
+    /// ```
+    /// use latin1str::Latin1String;
+    /// use assembly_fdb::{
+    ///     store::{Database, Interning, Table},
+    ///     value::{owned::Field, ValueType},
+    /// };
+    ///
+    /// let mut db = Database::new();
+    /// for name in ["A", "B"] {
+    ///     let mut table = Table::new(1);
+    ///     table.push_column(Latin1String::encode("path"), ValueType::Text);
+    ///     table.push_row(0, &[Field::Text(String::from("scripts/ai/foo.lua"))]);
+    ///     table.push_row(0, &[Field::Text(String::from("scripts/ai/foo.lua"))]);
+    ///     db.push_table(Latin1String::encode(name), table);
+    /// }
+    ///
+    /// let size = db.compute_size();
+    /// db.set_interning(Interning::Table);
+    /// assert_eq!(db.compute_size(), size - 2 * 20);
+    /// db.set_interning(Interning::Database);
+    /// assert_eq!(db.compute_size(), size - 3 * 20);
+    /// ```
+    pub fn set_interning(&mut self, interning: Interning) {
+        self.interning = interning;
+    }
+
     /// Push a table to the database
     pub fn push_table<S>(&mut self, name: S, table: Table)
     where
@@ -89,18 +147,60 @@ impl Database {
 
     /// Computes the size of the serialized database
     pub fn compute_size(&self) -> usize {
+        let table_list_base = 8 + self.tables.len() * size_of::<FDBTableHeader>();
         let table_size: usize = self
-            .tables
+            .layout(table_list_base as u32)
             .iter()
-            .map(|(n, t)| t.compute_size(n))
             .map(|x| x.def + x.data)
             .sum();
-        8 // FDBHeader
-        + table_size
+        table_list_base + table_size
+    }
+
+    /// Call [`Table::auto_resize_buckets`] on all tables
+    pub fn auto_resize_buckets(&mut self) {
+        for table in self.tables.values_mut() {
+            table.auto_resize_buckets();
+        }
+    }
+
+    /// Compute the layout of all tables, starting at `start`
+    fn layout(&self, mut start: u32) -> Vec<TableLayout> {
+        let mut shared = Pool::default();
+        let mut layouts = Vec::with_capacity(self.tables.len());
+        for (name, table) in &self.tables {
+            let mut local = Pool::default();
+            let pool = match self.interning {
+                Interning::Database => Some(&mut shared),
+                Interning::Table => Some(&mut local),
+                Interning::None if table.interning => Some(&mut local),
+                Interning::None => None,
+            };
+            let layout = table.layout(name, start, pool);
+            start += (layout.def + layout.data) as u32;
+            layouts.push(layout);
+        }
+        layouts
     }
 
     /// Write the database to an output stream
+    ///
+    /// This fails with [`io::ErrorKind::InvalidInput`] if a table has rows but
+    /// no buckets, because those rows could not be reached in the output.
+    /// Call [`Table::resize_buckets`] or [`Database::auto_resize_buckets`] first.
     pub fn write<O: io::Write>(&self, out: &mut O) -> io::Result<()> {
+        for (name, table) in &self.tables {
+            if table.buckets.is_empty() && !table.rows.is_empty() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "table {:?} has {} rows but no buckets",
+                        name.decode(),
+                        table.rows.len()
+                    ),
+                ));
+            }
+        }
+
         let base_offset = 8;
         let count = self
             .tables
@@ -111,28 +211,64 @@ impl Database {
             tables: ArrayHeader { base_offset, count },
         };
         header.tables.write_le(out)?;
-        let len_vec: Vec<_> = self.tables.iter().map(|(n, t)| t.compute_size(n)).collect();
-        let mut start_vec = Vec::with_capacity(self.tables.len());
         let table_list_base = base_offset + count * size_of::<FDBTableHeader>() as u32;
+        let layouts = self.layout(table_list_base);
         let mut start = table_list_base;
-        for len in len_vec.iter() {
-            start_vec.push(start);
-            Table::write_header(&mut start, len, out)?;
+        for layout in &layouts {
+            Table::write_header(&mut start, layout, out)?;
         }
 
         let mut start = table_list_base;
-        for (table_name, table) in &self.tables {
-            start = table.write(table_name, start, out)?;
+        for ((table_name, table), layout) in self.tables.iter().zip(&layouts) {
+            start = table.write(table_name, start, layout, out)?;
         }
 
         Ok(())
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-struct TableSize {
+impl<'a> TryFrom<mem::Database<'a>> for Database {
+    type Error = CastError;
+
+    fn try_from(src: mem::Database<'a>) -> Result<Self, Self::Error> {
+        let mut db = Database::new();
+        for table in src.tables()?.iter() {
+            let table = table?;
+            db.push_table(table.name_raw(), Table::from(table));
+        }
+        Ok(db)
+    }
+}
+
+#[cfg(feature = "core")]
+impl From<&crate::core::Schema> for Database {
+    fn from(schema: &crate::core::Schema) -> Self {
+        let mut db = Database::new();
+        for (name, table) in &schema.tables {
+            db.push_table(Latin1String::encode(name), Table::from(table));
+        }
+        db
+    }
+}
+
+/// Addresses of interned values
+#[derive(Default)]
+struct Pool<'a> {
+    strings: BTreeMap<&'a Latin1Str, u32>,
+    i64s: BTreeMap<i64, u32>,
+}
+
+/// The sizes of a table and the addresses of its strings and `i64` values
+struct TableLayout {
     def: usize,
     data: usize,
+    /// The address of every string in the arena
+    strings: BTreeMap<usize, Vec<u32>>,
+    /// The address of every `i64` value
+    i64s: Vec<u32>,
+    /// Whether the string / `i64` needs to be written in this table
+    own_strings: BTreeMap<usize, Vec<bool>>,
+    own_i64s: Vec<bool>,
 }
 
 /// A single table
@@ -143,6 +279,7 @@ pub struct Table {
     buckets: Vec<Bucket>,
     rows: Vec<Row>,
     fields: Vec<Field>,
+    interning: bool,
 }
 
 type StringArena = BTreeMap<usize, Vec<Latin1String>>;
@@ -157,13 +294,8 @@ impl<'t> ValueMapperMut<OwnedContext, StoreContext> for StoreMapper<'t> {
         let s = Latin1String::encode(from).into_owned();
         let lkey = req_buf_len(&s);
         let lstrings = self.strings.entry(lkey).or_default();
-        let inner = /*if let Some(index) = lstrings.iter().position(|p| s == *p) {
-            index
-        } else */{
-            let len = lstrings.len();
-            lstrings.push(s);
-            len
-        };
+        let inner = lstrings.len();
+        lstrings.push(s);
         TextRef { outer: lkey, inner }
     }
 
@@ -193,9 +325,17 @@ impl Table {
             strings: BTreeMap::new(),
             rows: vec![],
             i64s: vec![],
+            interning: false,
         }
     }
 
+    /// Store identical strings and `i64` values of this table only once
+    ///
+    /// This is applied when writing, see also [`Database::set_interning`].
+    pub fn set_interning(&mut self, interning: bool) {
+        self.interning = interning;
+    }
+
     /// Get all columns
     pub fn columns(&self) -> &[Column] {
         &self.columns
@@ -212,28 +352,33 @@ impl Table {
         })
     }
 
+    /// Get the number of buckets
+    pub fn bucket_count(&self) -> usize {
+        self.buckets.len()
+    }
+
+    /// Get the number of rows
+    pub fn row_count(&self) -> usize {
+        self.rows.len()
+    }
+
     /// Push a row into this table
+    ///
+    /// The row is placed in the bucket `pk % bucket_count`. If the table has no
+    /// buckets yet, it is placed when calling [`Table::resize_buckets`].
     pub fn push_row(&mut self, pk: usize, fields: &[crate::value::owned::Field]) {
         let first_field_index = self.fields.len();
         let row = self.rows.len();
 
-        // find out where to place it
-        let bucket_index = pk % self.buckets.len();
-        let bucket = &mut self.buckets[bucket_index];
-
-        // Add to linked list
-        if let Some((_, last)) = &mut bucket.first_row_last {
-            self.rows[*last].next_row = Some(row);
-            *last = row;
-        } else {
-            bucket.first_row_last = Some((row, row))
-        }
-
         self.rows.push(Row {
+            pk,
             first_field_index,
             count: fields.len().try_into().unwrap(),
             next_row: None,
         });
+        if !self.buckets.is_empty() {
+            self.link_row(row);
+        }
 
         let mut mapper = StoreMapper {
             strings: &mut self.strings,
@@ -244,9 +389,72 @@ impl Table {
         }
     }
 
+    /// Push a row into this table, using the [`FdbHash`] of the first field as the key
+    ///
+    /// ```
+    /// use assembly_fdb::{store::Table, value::owned::Field};
+    ///
+    /// let mut table = Table::new(0);
+    /// table.push_row_hashed(&[Field::Text(String::from("foo"))]);
+    /// table.push_row_hashed(&[Field::Integer(17)]);
+    /// table.auto_resize_buckets();
+    /// assert_eq!(table.bucket_count(), 2);
+    /// ```
+    pub fn push_row_hashed(&mut self, fields: &[crate::value::owned::Field]) {
+        let pk = fields.first().map_or(0, FdbHash::hash);
+        self.push_row(pk as usize, fields);
+    }
+
+    /// Add the row at `index` to the end of the linked list of its bucket
+    fn link_row(&mut self, row: usize) {
+        let bucket_index = self.rows[row].pk % self.buckets.len();
+        let bucket = &mut self.buckets[bucket_index];
+
+        // Add to linked list
+        if let Some((_, last)) = &mut bucket.first_row_last {
+            self.rows[*last].next_row = Some(row);
+            *last = row;
+        } else {
+            bucket.first_row_last = Some((row, row))
+        }
+    }
+
+    /// Change the number of buckets and move every row to `pk % bucket_count`
+    ///
+    /// The rows within one bucket keep the order in which they were pushed.
+    pub fn resize_buckets(&mut self, bucket_count: usize) {
+        self.buckets = vec![
+            Bucket {
+                first_row_last: None
+            };
+            bucket_count
+        ];
+        for row in &mut self.rows {
+            row.next_row = None;
+        }
+        if bucket_count > 0 {
+            for row in 0..self.rows.len() {
+                self.link_row(row);
+            }
+        }
+    }
+
+    /// Resize the buckets to the size used in the original database files
+    ///
+    /// This is the smallest power of two that is larger than or equal to the
+    /// number of distinct keys, or zero if the table is empty.
+    pub fn auto_resize_buckets(&mut self) {
+        let keys: BTreeSet<usize> = self.rows.iter().map(|r| r.pk).collect();
+        let bucket_count = match keys.len() {
+            0 => 0,
+            n => n.next_power_of_two(),
+        };
+        self.resize_buckets(bucket_count);
+    }
+
     fn write_header<IO: io::Write>(
         start: &mut u32,
-        len: &TableSize,
+        len: &TableLayout,
         out: &mut IO,
     ) -> io::Result<()> {
         let table_def_header_addr = *start;
@@ -266,6 +474,7 @@ impl Table {
         &self,
         table_name: &Latin1Str,
         start: u32,
+        layout: &TableLayout,
         out: &mut IO,
     ) -> io::Result<u32> {
         // Serialize table definition
@@ -352,18 +561,6 @@ impl Table {
             FDBRowHeader { fields }.write_le(out)?;
         }
 
-        let i64s_base_offset =
-            field_base_offset + (self.fields.len() * size_of::<FDBFieldData>()) as u32;
-        let strings_base_offset = i64s_base_offset + (self.i64s.len() * size_of::<u64>()) as u32;
-
-        let mut string_len_base = strings_base_offset;
-        let mut string_len_offsets = BTreeMap::new();
-        for (&key, value) in &self.strings {
-            let string_len = key * 4;
-            string_len_offsets.insert(key, string_len_base);
-            string_len_base += (string_len * value.len()) as u32;
-        }
-
         const TRUE_LE32: [u8; 4] = [1, 0, 0, 0];
         const FALSE_LE32: [u8; 4] = [0, 0, 0, 0];
 
@@ -372,38 +569,36 @@ impl Table {
                 Field::Nothing => (0, [0; 4]),
                 Field::Integer(i) => (1, i.to_le_bytes()),
                 Field::Float(f) => (3, f.to_le_bytes()),
-                Field::Text(TextRef { outer, inner }) => (4, {
-                    let v = string_len_offsets.get(outer).unwrap() + (inner * outer * 4) as u32;
-                    v.to_le_bytes()
-                }),
+                Field::Text(TextRef { outer, inner }) => {
+                    (4, layout.strings[outer][*inner].to_le_bytes())
+                }
                 Field::Boolean(b) => (5, if *b { TRUE_LE32 } else { FALSE_LE32 }),
-                Field::BigInt(i64_ref) => (6, {
-                    let v = i64s_base_offset + (i64_ref.index * size_of::<u64>()) as u32;
-                    v.to_le_bytes()
-                }),
-                Field::VarChar(text_ref) => (8, {
-                    let v = string_len_offsets.get(&text_ref.outer).unwrap()
-                        + (text_ref.inner * text_ref.outer * 4) as u32;
-                    v.to_le_bytes()
-                }),
+                Field::BigInt(I64Ref { index }) => (6, layout.i64s[*index].to_le_bytes()),
+                Field::VarChar(TextRef { outer, inner }) => {
+                    (8, layout.strings[outer][*inner].to_le_bytes())
+                }
             };
             FDBFieldData { data_type, value }.write_le(out)?;
         }
 
         // Write out all i64s
-        for &num in &self.i64s {
-            out.write_all(&num.to_le_bytes())?;
+        for (&num, &own) in self.i64s.iter().zip(&layout.own_i64s) {
+            if own {
+                out.write_all(&num.to_le_bytes())?;
+            }
         }
 
         // Write out all strings
-        for value in self.strings.values() {
-            for string in value {
-                string.write_le(out)?;
+        for (value, own) in self.strings.values().zip(layout.own_strings.values()) {
+            for (string, &own) in value.iter().zip(own) {
+                if own {
+                    string.write_le(out)?;
+                }
             }
         }
 
         // Increment final offset
-        Ok(string_len_base)
+        Ok(start + (layout.def + layout.data) as u32)
     }
 
     fn compute_def_size(&self, name: &Latin1Str) -> usize {
@@ -418,22 +613,102 @@ impl Table {
                 * 4
     }
 
-    fn compute_data_size(&self) -> usize {
-        let string_size: usize = self.strings.iter().map(|(k, v)| k * v.len()).sum(); // Strings
-        size_of::<FDBTableDataHeader>()
+    /// Compute the layout of this table, if it starts at `start`
+    ///
+    /// If a `pool` is passed, every value that is already in the pool is not
+    /// written again and all other values are added to the pool.
+    fn layout<'a>(
+        &'a self,
+        name: &Latin1Str,
+        start: u32,
+        mut pool: Option<&mut Pool<'a>>,
+    ) -> TableLayout {
+        let def = self.compute_def_size(name);
+        let i64s_base_offset = start as usize
+            + def
+            + size_of::<FDBTableDataHeader>()
             + size_of::<FDBBucketHeader>() * self.buckets.len()
-            + size_of::<FDBRowHeaderListEntry>() * self.rows.len()
-            + size_of::<FDBRowHeader>() * self.rows.len()
-            + size_of::<FDBFieldData>() * self.fields.len()
-            + 4 * string_size
-            + size_of::<u64>() * self.i64s.len()
+            + (size_of::<FDBRowHeaderListEntry>() + size_of::<FDBRowHeader>()) * self.rows.len()
+            + size_of::<FDBFieldData>() * self.fields.len();
+
+        let mut next = i64s_base_offset as u32;
+        let mut i64s = Vec::with_capacity(self.i64s.len());
+        let mut own_i64s = Vec::with_capacity(self.i64s.len());
+        for &num in &self.i64s {
+            let addr = pool
+                .as_deref_mut()
+                .map_or(next, |p| *p.i64s.entry(num).or_insert(next));
+            let own = addr == next;
+            i64s.push(addr);
+            own_i64s.push(own);
+            if own {
+                next += size_of::<u64>() as u32;
+            }
+        }
+
+        let mut strings = BTreeMap::new();
+        let mut own_strings = BTreeMap::new();
+        for (&key, value) in &self.strings {
+            let addrs: &mut Vec<u32> = strings.entry(key).or_default();
+            let owns: &mut Vec<bool> = own_strings.entry(key).or_default();
+            for string in value {
+                let addr = pool
+                    .as_deref_mut()
+                    .map_or(next, |p| *p.strings.entry(&**string).or_insert(next));
+                let own = addr == next;
+                addrs.push(addr);
+                owns.push(own);
+                if own {
+                    next += (key * 4) as u32;
+                }
+            }
+        }
+
+        TableLayout {
+            def,
+            data: next as usize - start as usize - def,
+            strings,
+            i64s,
+            own_strings,
+            own_i64s,
+        }
+    }
+}
+
+/// Rows are keyed by the [`FdbHash`] of their first field, so they stay in
+/// their bucket when that matches the hash and the table can be resized.
+impl<'a> From<mem::Table<'a>> for Table {
+    fn from(src: mem::Table<'a>) -> Self {
+        let mut table = Table::new(src.bucket_count());
+        for column in src.column_iter() {
+            table.push_column(column.name_raw(), column.value_type());
+        }
+        let mut row_buffer = Vec::with_capacity(src.column_count());
+        for bucket in src.bucket_iter() {
+            for row in bucket.row_iter() {
+                row_buffer.extend(row.field_iter().map(|f| f.map(&mut mem::MemToOwned)));
+                table.push_row_hashed(&row_buffer);
+                row_buffer.clear();
+            }
+        }
+        table
     }
+}
 
-    fn compute_size(&self, name: &Latin1Str) -> TableSize {
-        TableSize {
-            def: self.compute_def_size(name),
-            data: self.compute_data_size(),
+/// Rows are keyed by the [`FdbHash`] of their first field, like for [`mem::Table`]
+#[cfg(feature = "core")]
+impl From<&crate::core::Table> for Table {
+    fn from(src: &crate::core::Table) -> Self {
+        let mut table = Table::new(src.buckets().len());
+        for column in src.columns() {
+            table.push_column(Latin1String::encode(&column.name), column.field_type);
+        }
+        for bucket in src.buckets() {
+            for row in bucket.rows_ref() {
+                table.push_row_hashed(row.fields());
+            }
         }
+        table
     }
 }
 
@@ -458,6 +733,7 @@ struct Bucket {
 
 /// A single row
 struct Row {
+    pk: usize,
     first_field_index: usize,
     count: u32,
     next_row: Option<usize>,
@@ -1,4 +1,7 @@
-use crate::mem;
+use crate::{
+    mem,
+    util::tests::{build, TableBuilder},
+};
 
 use super::super::core;
 use super::*;
@@ -346,3 +349,212 @@ fn test_write_tables_with_data() {
     );
     assert!(rows3.next().is_none());
 }
+
+fn write_items() -> Vec<u8> {
+    let mut table = TableBuilder::new("Items")
+        .column("id", ValueType::Integer)
+        .column("name", ValueType::Text)
+        .buckets(4);
+    table.row(vec![core::Field::Integer(1), core::Field::Text("a".into())]);
+    table.row(vec![core::Field::Integer(2), core::Field::BigInt(7)]);
+    table.row(vec![core::Field::Integer(5), core::Field::Nothing]);
+    build(vec![table])
+}
+
+#[test]
+fn test_copy_from_mem() {
+    let src = write_items();
+    let db = Database::try_from(mem::Database::new(&src)).unwrap();
+    let mut out = Vec::new();
+    db.write(&mut out).unwrap();
+    let changes =
+        crate::diff::diff_databases(mem::Database::new(&src), mem::Database::new(&out)).unwrap();
+    assert!(changes.is_empty(), "{}", changes);
+}
+
+#[test]
+fn test_copy_resized() {
+    let src = write_items();
+    let mdb = mem::Database::new(&src);
+    let schema = core::Schema::try_from(mdb).unwrap();
+    let items = mdb.tables().unwrap().by_name("Items").unwrap().unwrap();
+    for mut table in [
+        Table::from(items),
+        Table::from(schema.table("Items").unwrap()),
+    ] {
+        table.resize_buckets(3);
+        let mut db = Database::new();
+        db.push_table(Latin1String::encode("Items"), table);
+        let mut out = Vec::new();
+        db.write(&mut out).unwrap();
+
+        let odb = mem::Database::new(&out);
+        let items = odb.tables().unwrap().by_name("Items").unwrap().unwrap();
+        assert_eq!(items.bucket_count(), 3);
+        for id in [1, 2, 5] {
+            assert_eq!(items.index_iter(id).count(), 1);
+        }
+    }
+}
+
+#[test]
+fn test_edit_schema() {
+    let src = write_items();
+    let mut schema = core::Schema::try_from(mem::Database::new(&src)).unwrap();
+    let items = schema.table_mut("Items").unwrap();
+    items.delete_rows(&core::Field::Integer(1));
+    items
+        .update_field(
+            &core::Field::Integer(5),
+            "name",
+            core::Field::Text("e".into()),
+        )
+        .unwrap();
+
+    let mut out = Vec::new();
+    Database::from(&schema).write(&mut out).unwrap();
+
+    let odb = mem::Database::new(&out);
+    let items = odb.tables().unwrap().by_name("Items").unwrap().unwrap();
+    assert_eq!(items.bucket_count(), 4);
+    assert_eq!(items.index_iter(1).count(), 0);
+    let row = items.index_iter(5).next().unwrap();
+    assert_eq!(
+        row.field_at(1),
+        Some(mem::Field::Text(Latin1Str::from_bytes_until_nul(b"e\0")))
+    );
+}
+
+#[test]
+fn test_push_row_hashed() {
+    let mut table = Table::new(0);
+    table.push_column(Latin1String::encode("key"), ValueType::Text);
+    for key in ["a", "b", "c", "a", "LUP"] {
+        table.push_row_hashed(&[core::Field::Text(key.into())]);
+    }
+    let mut bigints = Table::new(0);
+    bigints.push_column(Latin1String::encode("key"), ValueType::BigInt);
+    for key in [-1, 1 << 40, 3] {
+        bigints.push_row_hashed(&[core::Field::BigInt(key)]);
+    }
+
+    let mut db = Database::new();
+    db.push_table(Latin1String::encode("Texts"), table);
+    db.push_table(Latin1String::encode("BigInts"), bigints);
+    db.auto_resize_buckets();
+
+    let mut out = Vec::new();
+    db.write(&mut out).unwrap();
+    let report = crate::validate::validate_buffer(&out);
+    assert!(report.is_valid(), "{}", report);
+
+    let odb = mem::Database::new(&out);
+    let tables = odb.tables().unwrap();
+    let texts = tables.by_name("Texts").unwrap().unwrap();
+    assert_eq!(texts.bucket_count(), 4);
+    assert_eq!(texts.row_iter().count(), 5);
+    let bigints = tables.by_name("BigInts").unwrap().unwrap();
+    assert_eq!(bigints.bucket_count(), 4);
+}
+
+#[test]
+fn test_write_rows_without_buckets() {
+    let mut table = Table::new(0);
+    table.push_column(Latin1String::encode("id"), ValueType::Integer);
+    table.push_row(1, &[core::Field::Integer(1)]);
+
+    let mut db = Database::new();
+    db.push_table(Latin1String::encode("Lost"), table);
+    let err = db.write(&mut Vec::new()).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+
+    db.auto_resize_buckets();
+    db.write(&mut Vec::new()).unwrap();
+}
+
+#[test]
+fn test_interning() {
+    let mut db = Database::new();
+    for name in ["A", "B"] {
+        let mut table = Table::new(2);
+        table.push_column(Latin1String::encode("id"), ValueType::BigInt);
+        table.push_column(Latin1String::encode("path"), ValueType::Text);
+        for (id, path) in [(1, "a.lua"), (1 << 40, "b.lua"), (1, "a.lua")] {
+            table.push_row_hashed(&[core::Field::BigInt(id), core::Field::Text(path.to_string())]);
+        }
+        db.push_table(Latin1String::encode(name), table);
+    }
+
+    let mut plain = Vec::new();
+    db.write(&mut plain).unwrap();
+    assert_eq!(plain.len(), db.compute_size());
+
+    for interning in [Interning::Table, Interning::Database] {
+        db.set_interning(interning);
+        let mut out = Vec::new();
+        db.write(&mut out).unwrap();
+        assert_eq!(out.len(), db.compute_size());
+        assert!(out.len() < plain.len());
+
+        let report = crate::validate::validate_buffer(&out);
+        assert!(report.is_valid(), "{}", report);
+        let diff =
+            crate::diff::diff_databases(mem::Database::new(&plain), mem::Database::new(&out))
+                .unwrap();
+        assert!(diff.is_empty(), "{}", diff);
+    }
+}
+
+#[test]
+fn test_write_with_layout() {
+    let mut db = Database::new();
+    for name in ["A", "B"] {
+        let mut table = Table::new(0);
+        table.push_column(Latin1String::encode("id"), ValueType::Integer);
+        table.push_column(Latin1String::encode("path"), ValueType::Text);
+        table.push_column(Latin1String::encode("big"), ValueType::BigInt);
+        for (id, path) in [(3, "a.lua"), (1, "b.lua"), (5, "a.lua")] {
+            table.push_row_hashed(&[
+                core::Field::Integer(id),
+                core::Field::Text(path.to_string()),
+                core::Field::BigInt(i64::from(id) << 40),
+            ]);
+        }
+        table.auto_resize_buckets();
+        db.push_table(Latin1String::encode(name), table);
+    }
+    db.set_interning(Interning::Database);
+    let mut src = Vec::new();
+    db.write(&mut src).unwrap();
+    src.extend_from_slice(&[1, 2, 3]);
+
+    let src_db = mem::Database::new(&src);
+    let layout = Layout::capture(src_db).unwrap();
+    let mut copy = Database::try_from(src_db).unwrap();
+
+    let mut plain = Vec::new();
+    copy.write(&mut plain).unwrap();
+    assert_ne!(plain, src);
+
+    let mut exact = Vec::new();
+    copy.write_with_layout(&layout, &mut exact).unwrap();
+    assert_eq!(exact, src);
+
+    let mut table = Table::new(1);
+    table.push_column(Latin1String::encode("id"), ValueType::Integer);
+    copy.push_table(Latin1String::encode("A"), table);
+    let err = copy
+        .write_with_layout(&layout, &mut Vec::new())
+        .unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+}
+
+#[test]
+fn test_capture_out_of_bounds() {
+    let src: &[u8] = &[0xff, 0xff, 0xff, 0xff, 8, 0, 0, 0];
+    let err = Layout::capture(mem::Database::new(src)).err().unwrap();
+    assert!(matches!(err, CastError::OutOfBounds { offset: 8 }));
+
+    let src: &[u8] = &[1, 0, 0, 0, 0xfc, 0xff, 0xff, 0xff];
+    assert!(Layout::capture(mem::Database::new(src)).is_err());
+}
@@ -0,0 +1,250 @@
+//! # Comma separated values
+//!
+//! ```text
+//! id:INTEGER,name:TEXT,chance:FLOAT
+//! 1,Sword,0.5
+//! 2,\N,1
+//! ```
+//!
+//! `NULL` is written as `\N`, and a backslash at the start of a string is
+//! doubled so that it can't be mistaken for it. Empty cells in columns other
+//! than `TEXT` and `VARCHAR` are read as `NULL` as well. Booleans are written
+//! as `0` and `1`, `true` and `false` are accepted as well.
+//!
+//! Strings must not contain NUL, and every character must exist in
+//! Windows-1252, the encoding of the database.
+
+use std::{borrow::Cow, io};
+
+#[cfg(feature = "store")]
+use assembly_fdb_core::value::ValueType;
+
+use super::TextError;
+#[cfg(feature = "store")]
+use super::{new_table, Column};
+use crate::mem::{Field, Table};
+#[cfg(feature = "store")]
+use crate::{store, util::is_latin1, value::owned};
+
+/// The cell for `NULL`
+const NULL: &str = "\\N";
+
+/// Double a leading backslash, so that a string can't be read as [`NULL`]
+fn escape(text: Cow<str>) -> Cow<str> {
+    if text.starts_with('\\') {
+        Cow::Owned(format!("\\{}", text))
+    } else {
+        text
+    }
+}
+
+/// Write a table as CSV
+///
+/// ```
+/// # use assembly_fdb::mem;
+/// use assembly_fdb::text::{csv, TextError};
+///
+/// fn export(table: mem::Table) -> Result<String, TextError> {
+///     let mut out = Vec::new();
+///     csv::write_table(table, &mut out)?;
+///     Ok(String::from_utf8(out).unwrap())
+/// }
+/// ```
+pub fn write_table<W: io::Write>(table: Table<'_>, out: W) -> Result<(), TextError> {
+    let mut writer = ::csv::Writer::from_writer(out);
+    writer.write_record(
+        table
+            .column_iter()
+            .map(|column| format!("{}:{}", column.name(), column.value_type())),
+    )?;
+    for row in table.row_iter() {
+        writer.write_record(row.field_iter().map(|field| match field {
+            Field::Nothing => String::from(NULL),
+            Field::Integer(i) => i.to_string(),
+            Field::Float(f) => f.to_string(),
+            Field::Text(s) | Field::VarChar(s) => escape(s.decode()).into_owned(),
+            Field::Boolean(b) => u8::from(b).to_string(),
+            Field::BigInt(i) => i.to_string(),
+        }))?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Read a table from CSV
+///
+/// The rows are hashed into buckets by their first field.
+#[cfg(feature = "store")]
+pub fn read_table<R: io::Read>(input: R) -> Result<store::Table, TextError> {
+    let mut reader = ::csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(input);
+    let mut records = reader.records();
+
+    let header = records.next().ok_or(TextError::MissingHeader)??;
+    let columns = header
+        .iter()
+        .map(|cell| {
+            cell.rsplit_once(':')
+                .and_then(|(name, ty)| Some((name.to_owned(), ValueType::from_static_name(ty)?)))
+                .ok_or_else(|| TextError::InvalidColumn(cell.to_owned()))
+        })
+        .collect::<Result<Vec<Column>, _>>()?;
+
+    let mut table = new_table(&columns);
+    let mut fields = Vec::with_capacity(columns.len());
+    for record in records {
+        let record = record?;
+        let line = record.position().map_or(0, |p| p.line());
+        if record.len() != columns.len() {
+            return Err(TextError::FieldCount {
+                line,
+                expected: columns.len(),
+                found: record.len(),
+            });
+        }
+
+        fields.clear();
+        for (cell, (name, value_type)) in record.iter().zip(&columns) {
+            let field = parse(*value_type, cell).ok_or_else(|| TextError::Value {
+                line,
+                column: name.clone(),
+                expected: *value_type,
+                found: format!("{:?}", cell),
+            })?;
+            fields.push(field);
+        }
+        table.push_row_hashed(&fields);
+    }
+    table.auto_resize_buckets();
+    Ok(table)
+}
+
+/// Parse a cell as a value of the given type
+#[cfg(feature = "store")]
+fn parse(value_type: ValueType, cell: &str) -> Option<owned::Field> {
+    let is_text = matches!(value_type, ValueType::Text | ValueType::VarChar);
+    if cell == NULL || (cell.is_empty() && !is_text) {
+        return Some(owned::Field::Nothing);
+    }
+    // Undo `escape`
+    let text = cell.strip_prefix('\\').unwrap_or(cell);
+    if is_text && !is_latin1(text) {
+        return None;
+    }
+    match value_type {
+        ValueType::Nothing => None,
+        ValueType::Integer => cell.parse().ok().map(owned::Field::Integer),
+        ValueType::Float => cell.parse().ok().map(owned::Field::Float),
+        ValueType::Text => Some(owned::Field::Text(text.to_owned())),
+        ValueType::Boolean => match cell {
+            "0" | "false" => Some(owned::Field::Boolean(false)),
+            "1" | "true" => Some(owned::Field::Boolean(true)),
+            _ => None,
+        },
+        ValueType::BigInt => cell.parse().ok().map(owned::Field::BigInt),
+        ValueType::VarChar => Some(owned::Field::VarChar(text.to_owned())),
+    }
+}
+
+#[cfg(all(test, feature = "store"))]
+mod tests {
+    use super::*;
+    use crate::{
+        mem,
+        text::{rows, sample, table_rows},
+        util::tests::{build, TableBuilder},
+    };
+
+    fn items(buf: &[u8]) -> Table<'_> {
+        mem::Database::new(buf)
+            .tables()
+            .unwrap()
+            .by_name("Items")
+            .unwrap()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let buf = sample();
+        let mut out = Vec::new();
+        write_table(items(&buf), &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.starts_with(
+            "id:INTEGER,name:TEXT,chance:FLOAT,enabled:BOOLEAN,big:BIGINT,xml:VARCHAR\n"
+        ));
+
+        let table = read_table(text.as_bytes()).unwrap();
+        assert_eq!(table.columns()[5].value_type(), ValueType::VarChar);
+        assert_eq!(rows(table), table_rows(&buf));
+    }
+
+    #[test]
+    fn test_errors() {
+        let input = "id:INTEGER,flag:BOOLEAN\n1,0\n2,yes\n";
+        assert_eq!(
+            read_table(input.as_bytes()).err().unwrap().to_string(),
+            "Line 3, column \"flag\": cannot convert \"yes\" to BOOLEAN"
+        );
+
+        let input = "id:INTEGER,flag:BOOLEAN\n1\n";
+        assert!(matches!(
+            read_table(input.as_bytes()),
+            Err(TextError::FieldCount { line: 2, .. })
+        ));
+        assert!(matches!(
+            read_table("id:INT32\n".as_bytes()),
+            Err(TextError::InvalidColumn(c)) if c == "id:INT32"
+        ));
+
+        let table = read_table("id:INTEGER,name:TEXT\n3000000000,\n".as_bytes());
+        assert!(matches!(table, Err(TextError::Value { .. })));
+        for name in ["日本", "a\0b"] {
+            let input = format!("id:INTEGER,name:VARCHAR\n1,{}\n", name);
+            let table = read_table(input.as_bytes());
+            assert!(matches!(table, Err(TextError::Value { line: 2, .. })));
+        }
+        let table = read_table("id:INTEGER,name:TEXT\n1,Grüße €\n".as_bytes()).unwrap();
+        assert_eq!(
+            rows(table),
+            vec![vec![
+                owned::Field::Integer(1),
+                owned::Field::Text(String::from("Grüße €"))
+            ]]
+        );
+        let table = read_table("id:INTEGER,name:TEXT\n3,\\N\n,\n".as_bytes()).unwrap();
+        assert_eq!(
+            rows(table),
+            vec![
+                vec![owned::Field::Nothing, owned::Field::Text(String::new())],
+                vec![owned::Field::Integer(3), owned::Field::Nothing],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_text() {
+        let mut table = TableBuilder::new("Items")
+            .column("id", ValueType::Integer)
+            .column("name", ValueType::Text)
+            .buckets(4);
+        for (id, name) in [(1, ""), (2, "\\N"), (3, "\\")] {
+            table.row(vec![
+                owned::Field::Integer(id),
+                owned::Field::Text(name.to_owned()),
+            ]);
+        }
+        table.row(vec![owned::Field::Integer(4), owned::Field::Nothing]);
+        let buf = build(vec![table]);
+
+        let mut out = Vec::new();
+        write_table(items(&buf), &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        assert!(text.contains("\n1,\n"), "{}", text);
+        assert!(text.contains("\n4,\\N\n"), "{}", text);
+        let table = read_table(text.as_bytes()).unwrap();
+        assert_eq!(rows(table), table_rows(&buf));
+    }
+}
@@ -0,0 +1,147 @@
+//! # Text formats for single tables
+//!
+//! This module converts a single table to and from text files, so that it can
+//! be edited in a spreadsheet or reviewed in version control.
+//!
+//! - [`csv`] writes one record per row, with a header of `name:TYPE` cells.
+//! - [`ndjson`] writes a header object with the columns, followed by one JSON
+//!   array per row.
+//!
+//! The types in the header are the names of [`ValueType::static_name`]. When
+//! reading a file, every value is converted to the type of its column, and the
+//! rows are hashed into the buckets of a [`store::Table`](crate::store::Table).
+
+use std::io;
+
+use assembly_fdb_core::value::ValueType;
+use displaydoc::Display;
+use thiserror::Error;
+
+#[cfg(feature = "csv")]
+pub mod csv;
+#[cfg(feature = "ndjson")]
+pub mod ndjson;
+
+/// Error when reading or writing a table as text
+#[derive(Debug, Display, Error)]
+pub enum TextError {
+    /// I/O error: {0}
+    Io(#[from] io::Error),
+    /// CSV error: {0}
+    #[cfg(feature = "csv")]
+    Csv(#[from] ::csv::Error),
+    /// Line {line}: invalid JSON: {source}
+    #[cfg(feature = "ndjson")]
+    Json {
+        /// The line of the input, starting at 1
+        line: u64,
+        /// The error from the parser
+        source: serde_json::Error,
+    },
+    /// The input has no header
+    MissingHeader,
+    /// Invalid column {0:?} in the header
+    InvalidColumn(String),
+    /// Line {line}: expected a list of fields
+    InvalidRow {
+        /// The line of the input, starting at 1
+        line: u64,
+    },
+    /// Line {line}: expected {expected} fields, found {found}
+    FieldCount {
+        /// The line of the input, starting at 1
+        line: u64,
+        /// The number of columns
+        expected: usize,
+        /// The number of fields in the row
+        found: usize,
+    },
+    /// Line {line}, column {column:?}: cannot convert {found} to {expected}
+    Value {
+        /// The line of the input, starting at 1
+        line: u64,
+        /// The name of the column
+        column: String,
+        /// The type of the column
+        expected: ValueType,
+        /// The value in the input
+        found: String,
+    },
+}
+
+/// A column of the header
+#[cfg(feature = "store")]
+type Column = (String, ValueType);
+
+/// Create an empty table with the given columns
+#[cfg(feature = "store")]
+fn new_table(columns: &[Column]) -> crate::store::Table {
+    let mut table = crate::store::Table::new(0);
+    for (name, value_type) in columns {
+        table.push_column(latin1str::Latin1String::encode(name), *value_type);
+    }
+    table
+}
+
+/// A database with a table `Items` that uses every type
+#[cfg(all(test, feature = "store"))]
+fn sample() -> Vec<u8> {
+    use crate::{
+        util::tests::{build, TableBuilder},
+        value::owned::Field,
+    };
+
+    let mut table = TableBuilder::new("Items");
+    for (name, value_type) in [
+        ("id", ValueType::Integer),
+        ("name", ValueType::Text),
+        ("chance", ValueType::Float),
+        ("enabled", ValueType::Boolean),
+        ("big", ValueType::BigInt),
+        ("xml", ValueType::VarChar),
+    ] {
+        table = table.column(name, value_type);
+    }
+    for i in 0..8 {
+        table.row(vec![
+            Field::Integer(i * 1000 - 3000),
+            Field::Text(format!("Item, \"{}\"", i)),
+            Field::Float(i as f32 / 3.0),
+            Field::Boolean(i % 3 == 0),
+            Field::BigInt(i64::from(i) << 40),
+            if i % 2 == 0 {
+                Field::Nothing
+            } else {
+                Field::VarChar(format!("<a b=\"{}\"/>\nü", i))
+            },
+        ]);
+    }
+    build(vec![table])
+}
+
+/// Write `table` back and return the rows of the result
+#[cfg(all(test, feature = "store"))]
+fn rows(table: crate::store::Table) -> Vec<Vec<crate::value::owned::Field>> {
+    let mut db = crate::store::Database::new();
+    db.push_table(latin1str::Latin1String::encode("Items"), table);
+    let mut buf = Vec::new();
+    db.write(&mut buf).unwrap();
+    table_rows(&buf)
+}
+
+/// Get the rows of the table `Items`
+#[cfg(all(test, feature = "store"))]
+fn table_rows(buf: &[u8]) -> Vec<Vec<crate::value::owned::Field>> {
+    use crate::mem::{self, MemToOwned};
+
+    let table = mem::Database::new(buf)
+        .tables()
+        .unwrap()
+        .by_name("Items")
+        .unwrap()
+        .unwrap();
+    table
+        .row_iter()
+        .map(|row| row.field_iter().map(|f| f.map(&mut MemToOwned)).collect())
+        .collect()
+}
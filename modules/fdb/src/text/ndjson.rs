@@ -0,0 +1,220 @@
+//! # Newline delimited JSON
+//!
+//! ```text
+//! {"columns":[{"name":"id","type":"INTEGER"},{"name":"name","type":"TEXT"}]}
+//! [1,"Sword"]
+//! [2,null]
+//! ```
+//!
+//! The first line lists the columns, every following line is an array with the
+//! fields of one row. Strings must not contain NUL, and every character must
+//! exist in Windows-1252, the encoding of the database.
+
+use std::io;
+
+#[cfg(feature = "store")]
+use std::{
+    convert::TryFrom,
+    io::{BufRead, BufReader},
+};
+
+#[cfg(feature = "store")]
+use assembly_fdb_core::value::ValueType;
+use serde_json::{json, Value};
+
+use super::TextError;
+#[cfg(feature = "store")]
+use super::{new_table, Column};
+use crate::mem::{Field, Table};
+#[cfg(feature = "store")]
+use crate::{store, util::is_latin1, value::owned};
+
+/// Write a table as NDJSON
+pub fn write_table<W: io::Write>(table: Table<'_>, mut out: W) -> Result<(), TextError> {
+    let columns: Vec<Value> = table
+        .column_iter()
+        .map(|column| json!({ "name": column.name(), "type": column.value_type().static_name() }))
+        .collect();
+    let header = json!({ "columns": columns });
+    writeln!(out, "{}", header)?;
+
+    let mut fields: Vec<Field> = Vec::with_capacity(table.column_count());
+    for row in table.row_iter() {
+        fields.clear();
+        fields.extend(row.field_iter());
+        serde_json::to_writer(&mut out, &fields).map_err(io::Error::from)?;
+        writeln!(out)?;
+    }
+    Ok(())
+}
+
+/// Read a table from NDJSON
+///
+/// Empty lines are skipped. The rows are hashed into buckets by their first field.
+#[cfg(feature = "store")]
+pub fn read_table<R: io::Read>(input: R) -> Result<store::Table, TextError> {
+    let mut lines = BufReader::new(input)
+        .lines()
+        .zip(1..)
+        .filter(|(line, _)| !matches!(line, Ok(line) if line.trim().is_empty()));
+    let parse_line = |(line, number): (io::Result<String>, u64)| {
+        serde_json::from_str::<Value>(&line?)
+            .map(|value| (value, number))
+            .map_err(|source| TextError::Json {
+                line: number,
+                source,
+            })
+    };
+
+    let (header, _) = lines
+        .next()
+        .map(parse_line)
+        .ok_or(TextError::MissingHeader)??;
+    let columns = header
+        .get("columns")
+        .and_then(Value::as_array)
+        .ok_or(TextError::MissingHeader)?
+        .iter()
+        .map(|column| {
+            let name = column.get("name").and_then(Value::as_str);
+            let value_type = column
+                .get("type")
+                .and_then(Value::as_str)
+                .and_then(ValueType::from_static_name);
+            match (name, value_type) {
+                (Some(name), Some(value_type)) => Ok((name.to_owned(), value_type)),
+                _ => Err(TextError::InvalidColumn(column.to_string())),
+            }
+        })
+        .collect::<Result<Vec<Column>, _>>()?;
+
+    let mut table = new_table(&columns);
+    let mut fields = Vec::with_capacity(columns.len());
+    for entry in lines {
+        let (row, line) = parse_line(entry)?;
+        let values = match row {
+            Value::Array(values) => values,
+            _ => return Err(TextError::InvalidRow { line }),
+        };
+        if values.len() != columns.len() {
+            return Err(TextError::FieldCount {
+                line,
+                expected: columns.len(),
+                found: values.len(),
+            });
+        }
+
+        fields.clear();
+        for (value, (name, value_type)) in values.iter().zip(&columns) {
+            let field = convert(*value_type, value).ok_or_else(|| TextError::Value {
+                line,
+                column: name.clone(),
+                expected: *value_type,
+                found: value.to_string(),
+            })?;
+            fields.push(field);
+        }
+        table.push_row_hashed(&fields);
+    }
+    table.auto_resize_buckets();
+    Ok(table)
+}
+
+/// Convert a JSON value to a field of the given type
+#[cfg(feature = "store")]
+fn convert(value_type: ValueType, value: &Value) -> Option<owned::Field> {
+    match (value_type, value) {
+        (_, Value::Null) => Some(owned::Field::Nothing),
+        (ValueType::Integer, Value::Number(n)) => n
+            .as_i64()
+            .and_then(|i| i32::try_from(i).ok())
+            .map(owned::Field::Integer),
+        (ValueType::Float, Value::Number(n)) => n.as_f64().map(|f| owned::Field::Float(f as f32)),
+        (ValueType::Text, Value::String(s)) if is_latin1(s) => Some(owned::Field::Text(s.clone())),
+        (ValueType::Boolean, Value::Bool(b)) => Some(owned::Field::Boolean(*b)),
+        (ValueType::BigInt, Value::Number(n)) => n.as_i64().map(owned::Field::BigInt),
+        (ValueType::VarChar, Value::String(s)) if is_latin1(s) => {
+            Some(owned::Field::VarChar(s.clone()))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(all(test, feature = "store"))]
+mod tests {
+    use super::*;
+    use crate::{
+        mem,
+        text::{rows, sample, table_rows},
+    };
+
+    #[test]
+    fn test_round_trip() {
+        let buf = sample();
+        let table = mem::Database::new(&buf)
+            .tables()
+            .unwrap()
+            .by_name("Items")
+            .unwrap()
+            .unwrap();
+        let mut out = Vec::new();
+        write_table(table, &mut out).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let mut lines = text.lines();
+        assert_eq!(
+            lines.next(),
+            Some(concat!(
+                r#"{"columns":[{"name":"id","type":"INTEGER"},{"name":"name","type":"TEXT"},"#,
+                r#"{"name":"chance","type":"FLOAT"},{"name":"enabled","type":"BOOLEAN"},"#,
+                r#"{"name":"big","type":"BIGINT"},{"name":"xml","type":"VARCHAR"}]}"#
+            ))
+        );
+        assert_eq!(lines.count(), 8);
+
+        let table = read_table(text.as_bytes()).unwrap();
+        assert_eq!(rows(table), table_rows(&buf));
+    }
+
+    #[test]
+    fn test_errors() {
+        let header =
+            r#"{"columns":[{"name":"id","type":"INTEGER"},{"name":"name","type":"TEXT"}]}"#;
+        let input = format!("{}\n\n[1, \"\"]\n[2, 3]\n", header);
+        assert_eq!(
+            read_table(input.as_bytes()).err().unwrap().to_string(),
+            "Line 4, column \"name\": cannot convert 3 to TEXT"
+        );
+
+        let input = format!("{}\n[1, \"\"]\n{{}}\n", header);
+        assert!(matches!(
+            read_table(input.as_bytes()),
+            Err(TextError::InvalidRow { line: 3 })
+        ));
+        let input = format!("{}\n[1, \"\", null]\n", header);
+        assert!(matches!(
+            read_table(input.as_bytes()),
+            Err(TextError::FieldCount { line: 2, .. })
+        ));
+        assert!(matches!(
+            read_table("[1\n".as_bytes()),
+            Err(TextError::Json { line: 1, .. })
+        ));
+        for name in ["日本", "a\\u0000b"] {
+            let input = format!("{}\n[1, \"{}\"]\n", header, name);
+            assert!(matches!(
+                read_table(input.as_bytes()),
+                Err(TextError::Value { line: 2, .. })
+            ));
+        }
+
+        // Two buckets, the rows are listed in bucket order
+        let input = format!("{}\n[1, \"\"]\n[2, null]\n", header);
+        assert_eq!(
+            rows(read_table(input.as_bytes()).unwrap()),
+            vec![
+                vec![owned::Field::Integer(2), owned::Field::Nothing],
+                vec![owned::Field::Integer(1), owned::Field::Text(String::new())],
+            ]
+        );
+    }
+}
@@ -0,0 +1,519 @@
+//! # Typed rows
+//!
+//! This module contains the traits to load rows of a [`mem::Table`] into
+//! Rust structs. The indices of the columns are resolved once per table by a
+//! [`Loader`], which can then be used to convert every row.
+//!
+//! With the `derive` feature, the [`FdbRow`] trait can be derived for structs
+//! with named fields:
+//!
+//! ```
+//! # #[cfg(feature = "derive")]
+//! # mod example {
+//! use assembly_fdb::{
+//!     mem::Database,
+//!     typed::{FdbRow, LoadError, Loader},
+//! };
+//!
+//! #[derive(Debug, FdbRow)]
+//! #[fdb(table = "Preconditions")]
+//! #[allow(non_snake_case)]
+//! struct Precondition {
+//!     id: i32,
+//!     #[fdb(column = "type")]
+//!     kind: Option<i32>,
+//!     targetLOT: Option<String>,
+//! }
+//!
+//! fn print_preconditions(db: Database) -> Result<(), LoadError> {
+//!     let loader = Loader::<Precondition>::from_tables(db.tables()?)?;
+//!     for precondition in loader.iter() {
+//!         println!("{:?}", precondition?);
+//!     }
+//!     Ok(())
+//! }
+//! # }
+//! ```
+//!
+//! Fields are converted with the [`FromField`] trait, which is implemented
+//! for `i32`, `i64`, `f32`, `bool`, `&Latin1Str`, `String` and `Option<T>`.
+//! NULL values are only accepted for fields of type `Option<T>`.
+//!
+//! The same conversions are available for single fields with [`Row::get`]
+//! and [`Row::get_by_name`]:
+//!
+//! ```
+//! use assembly_fdb::{mem::Table, typed::FieldError};
+//!
+//! fn print_names(table: Table) -> Result<(), FieldError> {
+//!     for row in table.row_iter() {
+//!         let id: i32 = row.get(0)?;
+//!         let name: Option<String> = row.get_by_name(table, "name")?;
+//!         println!("{}: {:?}", id, name);
+//!     }
+//!     Ok(())
+//! }
+//! ```
+
+use std::{any::type_name, fmt};
+
+use assembly_core::buffer::CastError;
+use assembly_fdb_core::value::{owned::OwnedContext, Context, Value, ValueType};
+use displaydoc::Display;
+use latin1str::Latin1Str;
+use thiserror::Error;
+
+use crate::mem::{self, Field, MemContext, Row, Table, Tables};
+
+#[cfg(feature = "derive")]
+pub use assembly_fdb_derive::FdbRow;
+
+/// Errors when loading typed rows
+#[derive(Debug, Error, Display)]
+pub enum LoadError {
+    /// Failed to load table: {0}
+    Cast(#[from] CastError),
+    /// Table {0:?} does not exist
+    MissingTable(&'static str),
+    /// Column {column:?} does not exist in table {table:?}
+    MissingColumn {
+        /// The name of the table
+        table: String,
+        /// The name of the column
+        column: &'static str,
+    },
+    /// Column {table}.{column} has type {actual}, which can't be loaded as {expected}
+    ColumnType {
+        /// The name of the table
+        table: String,
+        /// The name of the column
+        column: &'static str,
+        /// The declared type of the column
+        actual: ValueType,
+        /// The name of the Rust type
+        expected: &'static str,
+    },
+    /// Field {table}.{column} has type {actual}, which can't be loaded as {expected}
+    FieldType {
+        /// The name of the table
+        table: String,
+        /// The name of the column
+        column: &'static str,
+        /// The type of the field value
+        actual: ValueType,
+        /// The name of the Rust type
+        expected: &'static str,
+    },
+    /// Row of table {table:?} has no field for column {column:?}
+    MissingField {
+        /// The name of the table
+        table: String,
+        /// The name of the column
+        column: &'static str,
+    },
+    /// {0}
+    Field(#[from] FieldError),
+}
+
+/// Conversion from a [`Value`] into a Rust type
+///
+/// This is implemented for `i32`, `i64`, `f32` and `bool` in every context,
+/// for `&Latin1Str` on [`mem::Field`], for `String` on [`mem::Field`] and
+/// [`owned::Field`](crate::value::owned::Field), and for `Option<T>`.
+pub trait FromValue<C: Context>: Sized {
+    /// The type of the columns that hold values of `Self`
+    const VALUE_TYPE: ValueType;
+
+    /// Check whether a column of that type can be loaded as `Self`
+    fn accepts(value_type: ValueType) -> bool;
+
+    /// Convert the value, returning its type if that is not possible
+    fn from_value(value: Value<C>) -> Result<Self, ValueType>;
+}
+
+/// Conversion from a [`mem::Field`] into a Rust type
+///
+/// This is implemented for all types that implement [`FromValue`] for
+/// [`MemContext`].
+pub trait FromField<'a>: FromValue<MemContext<'a>> {}
+
+impl<'a, T: FromValue<MemContext<'a>>> FromField<'a> for T {}
+
+macro_rules! impl_from_value {
+    ($ty:ty, $value_type:ident, $($variant:ident)|+) => {
+        impl<C: Context> FromValue<C> for $ty {
+            const VALUE_TYPE: ValueType = ValueType::$value_type;
+
+            fn accepts(value_type: ValueType) -> bool {
+                matches!(value_type, $(ValueType::$variant)|+)
+            }
+
+            fn from_value(value: Value<C>) -> Result<Self, ValueType> {
+                match value {
+                    $(Value::$variant(v) => Ok(v.into()),)+
+                    _ => Err(ValueType::from(&value)),
+                }
+            }
+        }
+    };
+}
+
+impl_from_value!(i32, Integer, Integer);
+impl_from_value!(f32, Float, Float);
+impl_from_value!(bool, Boolean, Boolean);
+
+impl<C: Context<I64 = i64>> FromValue<C> for i64 {
+    const VALUE_TYPE: ValueType = ValueType::BigInt;
+
+    fn accepts(value_type: ValueType) -> bool {
+        matches!(value_type, ValueType::BigInt | ValueType::Integer)
+    }
+
+    fn from_value(value: Value<C>) -> Result<Self, ValueType> {
+        match value {
+            Value::BigInt(v) => Ok(v),
+            Value::Integer(v) => Ok(v.into()),
+            _ => Err(ValueType::from(&value)),
+        }
+    }
+}
+
+impl<'a> FromValue<MemContext<'a>> for &'a Latin1Str {
+    const VALUE_TYPE: ValueType = ValueType::Text;
+
+    fn accepts(value_type: ValueType) -> bool {
+        matches!(value_type, ValueType::Text | ValueType::VarChar)
+    }
+
+    fn from_value(value: Field<'a>) -> Result<Self, ValueType> {
+        match value {
+            Value::Text(v) | Value::VarChar(v) => Ok(v),
+            _ => Err(ValueType::from(&value)),
+        }
+    }
+}
+
+impl<'a> FromValue<MemContext<'a>> for String {
+    const VALUE_TYPE: ValueType = ValueType::Text;
+
+    fn accepts(value_type: ValueType) -> bool {
+        <&Latin1Str as FromValue<MemContext>>::accepts(value_type)
+    }
+
+    fn from_value(value: Field<'a>) -> Result<Self, ValueType> {
+        <&Latin1Str>::from_value(value).map(|s| s.decode().into_owned())
+    }
+}
+
+impl FromValue<OwnedContext> for String {
+    const VALUE_TYPE: ValueType = ValueType::Text;
+
+    fn accepts(value_type: ValueType) -> bool {
+        matches!(value_type, ValueType::Text | ValueType::VarChar)
+    }
+
+    fn from_value(value: Value<OwnedContext>) -> Result<Self, ValueType> {
+        match value {
+            Value::Text(v) | Value::VarChar(v) => Ok(v),
+            _ => Err(ValueType::from(&value)),
+        }
+    }
+}
+
+impl<C: Context, T: FromValue<C>> FromValue<C> for Option<T> {
+    const VALUE_TYPE: ValueType = T::VALUE_TYPE;
+
+    fn accepts(value_type: ValueType) -> bool {
+        value_type == ValueType::Nothing || T::accepts(value_type)
+    }
+
+    fn from_value(value: Value<C>) -> Result<Self, ValueType> {
+        match value {
+            Value::Nothing => Ok(None),
+            _ => T::from_value(value).map(Some),
+        }
+    }
+}
+
+/// Error when getting a typed value from a [`Row`]
+///
+/// A [`Row`] doesn't know its table, so only [`Row::get_by_name`] sets the
+/// `table` and the name of the `column`. This converts into a [`LoadError`],
+/// so both getters can be used with `?` when implementing [`FdbRow`].
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub struct FieldError {
+    /// The name of the table, if it is known
+    pub table: Option<String>,
+    /// The name of the column, or `#<index>` if it is not known
+    pub column: String,
+    /// What went wrong
+    pub kind: FieldErrorKind,
+}
+
+impl fmt::Display for FieldError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.table {
+            Some(table) => write!(f, "Field {}.{}: {}", table, self.column, self.kind),
+            None => write!(f, "Field {}: {}", self.column, self.kind),
+        }
+    }
+}
+
+/// The kind of a [`FieldError`]
+#[derive(Debug, Clone, PartialEq, Eq, Display)]
+pub enum FieldErrorKind {
+    /// the table has no such column
+    MissingColumn,
+    /// the row has no field for this column
+    MissingField,
+    /// expected {expected}, found {actual}
+    Type {
+        /// The type of the Rust value
+        expected: ValueType,
+        /// The type of the field
+        actual: ValueType,
+    },
+}
+
+/// A type that can be loaded from a row of a specific table
+///
+/// This trait is usually implemented with `#[derive(FdbRow)]`.
+pub trait FdbRow<'a>: Sized {
+    /// The name of the table
+    const TABLE: &'static str;
+    /// The resolved column indices
+    type Columns;
+
+    /// Find the indices of all columns that are needed to load `Self`
+    fn resolve_columns(table: Table<'a>) -> Result<Self::Columns, LoadError>;
+
+    /// Load a single row
+    fn load_row(table: Table<'a>, columns: &Self::Columns, row: Row<'a>)
+        -> Result<Self, LoadError>;
+}
+
+/// Find the index of the column `name` and check whether it can be loaded as `T`
+pub fn column_index<'a, T: FromField<'a>>(
+    table: Table<'a>,
+    name: &'static str,
+) -> Result<usize, LoadError> {
+    let (index, column) = table
+        .column_iter()
+        .enumerate()
+        .find(|(_, c)| c.name() == name)
+        .ok_or_else(|| LoadError::MissingColumn {
+            table: table.name().into_owned(),
+            column: name,
+        })?;
+    if T::accepts(column.value_type()) {
+        Ok(index)
+    } else {
+        Err(LoadError::ColumnType {
+            table: table.name().into_owned(),
+            column: name,
+            actual: column.value_type(),
+            expected: type_name::<T>(),
+        })
+    }
+}
+
+/// Load the field at `index` of `row` as `T`
+///
+/// The `table` and `column` are used for the error message
+pub fn field_at<'a, T: FromField<'a>>(
+    table: Table<'a>,
+    row: Row<'a>,
+    index: usize,
+    column: &'static str,
+) -> Result<T, LoadError> {
+    let field = row.field_at(index).ok_or_else(|| LoadError::MissingField {
+        table: table.name().into_owned(),
+        column,
+    })?;
+    T::from_value(field).map_err(|actual| LoadError::FieldType {
+        table: table.name().into_owned(),
+        column,
+        actual,
+        expected: type_name::<T>(),
+    })
+}
+
+/// Loads rows of a table as `T`
+pub struct Loader<'a, T: FdbRow<'a>> {
+    table: Table<'a>,
+    columns: T::Columns,
+}
+
+impl<'a, T: FdbRow<'a>> Loader<'a, T> {
+    /// Create a new loader for the given table
+    pub fn new(table: Table<'a>) -> Result<Self, LoadError> {
+        let columns = T::resolve_columns(table)?;
+        Ok(Self { table, columns })
+    }
+
+    /// Create a new loader for the table named [`FdbRow::TABLE`]
+    pub fn from_tables(tables: Tables<'a>) -> Result<Self, LoadError> {
+        let table = tables
+            .by_name(T::TABLE)
+            .ok_or(LoadError::MissingTable(T::TABLE))??;
+        Self::new(table)
+    }
+
+    /// Get the table
+    pub fn table(&self) -> Table<'a> {
+        self.table
+    }
+
+    /// Load a single row
+    pub fn load(&self, row: Row<'a>) -> Result<T, LoadError> {
+        T::load_row(self.table, &self.columns, row)
+    }
+
+    /// Load all rows of the table
+    pub fn iter(&self) -> LoaderIter<'_, 'a, T> {
+        LoaderIter {
+            loader: self,
+            rows: self.table.row_iter(),
+        }
+    }
+}
+
+/// Iterator created by [`Loader::iter`]
+pub struct LoaderIter<'l, 'a, T: FdbRow<'a>> {
+    loader: &'l Loader<'a, T>,
+    rows: mem::iter::TableRowIter<'a>,
+}
+
+impl<'l, 'a, T: FdbRow<'a>> Iterator for LoaderIter<'l, 'a, T> {
+    type Item = Result<T, LoadError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.next().map(|row| self.loader.load(row))
+    }
+}
+
+#[cfg(all(test, feature = "derive", feature = "store"))]
+mod tests {
+    use super::*;
+    use crate::{
+        mem::Database,
+        util::tests::{build, TableBuilder},
+        value::owned,
+    };
+
+    #[derive(Debug, FdbRow)]
+    #[fdb(table = "Items")]
+    struct Item<'a> {
+        id: i32,
+        name: &'a Latin1Str,
+        #[fdb(column = "sell price")]
+        price: Option<i64>,
+    }
+
+    #[derive(Debug, FdbRow)]
+    #[fdb(table = "Items")]
+    struct BadItem {
+        #[allow(dead_code)]
+        name: i32,
+    }
+
+    fn items() -> Vec<u8> {
+        let mut table = TableBuilder::new("Items")
+            .column("id", ValueType::Integer)
+            .column("name", ValueType::Text)
+            .column("sell price", ValueType::BigInt)
+            .buckets(2);
+        table.row(vec![
+            owned::Field::Integer(1),
+            owned::Field::Text("Sword".to_string()),
+            owned::Field::BigInt(100),
+        ]);
+        table.row(vec![
+            owned::Field::Integer(2),
+            owned::Field::Text("Shield".to_string()),
+            owned::Field::Nothing,
+        ]);
+        build(vec![table])
+    }
+
+    #[test]
+    fn test_load_rows() {
+        let buf = items();
+        let db = Database::new(&buf);
+        let loader = Loader::<Item>::from_tables(db.tables().unwrap()).unwrap();
+        let items: Vec<Item> = loader.iter().collect::<Result<_, _>>().unwrap();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].id, 2);
+        assert_eq!(items[0].name.decode(), "Shield");
+        assert_eq!(items[0].price, None);
+        assert_eq!(items[1].id, 1);
+        assert_eq!(items[1].price, Some(100));
+    }
+
+    #[test]
+    fn test_wrong_column_type() {
+        let buf = items();
+        let db = Database::new(&buf);
+        let err = Loader::<BadItem>::from_tables(db.tables().unwrap())
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            LoadError::ColumnType {
+                column: "name",
+                actual: ValueType::Text,
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_row_get() {
+        let buf = items();
+        let table = Database::new(&buf)
+            .tables()
+            .unwrap()
+            .by_name("Items")
+            .unwrap()
+            .unwrap();
+        let row = table.row_iter().next().unwrap();
+        assert_eq!(row.get::<i32>(0), Ok(2));
+        assert_eq!(row.get::<i64>(0), Ok(2));
+        assert_eq!(row.get::<String>(1).unwrap(), "Shield");
+        assert_eq!(
+            row.get_by_name::<Option<i64>>(table, "sell price"),
+            Ok(None)
+        );
+
+        let err = row.get_by_name::<i64>(table, "sell price").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Field Items.sell price: expected BIGINT, found NULL"
+        );
+        let err = row.get::<Option<bool>>(1).unwrap_err();
+        assert_eq!(err.to_string(), "Field #1: expected BOOLEAN, found TEXT");
+        let err = row.get_by_name::<i32>(table, "price").unwrap_err();
+        assert_eq!(err.kind, FieldErrorKind::MissingColumn);
+        assert_eq!(
+            row.get::<i32>(3).unwrap_err().kind,
+            FieldErrorKind::MissingField
+        );
+
+        let err = LoadError::from(row.get_by_name::<bool>(table, "name").unwrap_err());
+        assert_eq!(
+            err.to_string(),
+            "Field Items.name: expected BOOLEAN, found TEXT"
+        );
+    }
+
+    #[test]
+    fn test_from_owned_value() {
+        let value = owned::Field::Text(String::from("Sword"));
+        assert_eq!(String::from_value(value), Ok(String::from("Sword")));
+        assert_eq!(<Option<f32>>::from_value(owned::Field::Nothing), Ok(None));
+        assert_eq!(
+            <i32 as FromValue<OwnedContext>>::from_value(owned::Field::Float(1.0)),
+            Err(ValueType::Float)
+        );
+    }
+}
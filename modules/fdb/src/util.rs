@@ -1,5 +1,11 @@
 use std::cmp::Ordering;
 
+#[cfg(all(
+    feature = "store",
+    any(feature = "csv", feature = "ndjson", feature = "sqlite")
+))]
+use latin1str::Latin1String;
+
 /// Compares two name strings
 ///
 /// ## Safety
@@ -24,3 +30,72 @@ pub(crate) fn compare_bytes(bytes: &[u8], name_bytes: &[u8]) -> Ordering {
         Ordering::Greater
     }
 }
+
+/// Check whether `text` can be stored as a string in the database
+///
+/// The database has NUL-terminated Windows-1252 strings, so `text` must not
+/// contain NUL, and every character must exist in that encoding.
+#[cfg(all(
+    feature = "store",
+    any(feature = "csv", feature = "ndjson", feature = "sqlite")
+))]
+pub(crate) fn is_latin1(text: &str) -> bool {
+    !text.contains('\0') && Latin1String::encode(text).decode() == text
+}
+
+#[cfg(all(test, feature = "store"))]
+pub(crate) mod tests {
+    use crate::{
+        store,
+        value::{owned::Field, ValueType},
+    };
+    use latin1str::Latin1String;
+
+    /// A table for a test database
+    pub(crate) struct TableBuilder {
+        name: String,
+        table: store::Table,
+        buckets: Option<usize>,
+    }
+
+    impl TableBuilder {
+        pub(crate) fn new(name: &str) -> Self {
+            Self {
+                name: name.to_owned(),
+                table: store::Table::new(0),
+                buckets: None,
+            }
+        }
+
+        pub(crate) fn column(mut self, name: &str, value_type: ValueType) -> Self {
+            self.table
+                .push_column(Latin1String::encode(name), value_type);
+            self
+        }
+
+        /// Use this number of buckets instead of [`store::Table::auto_resize_buckets`]
+        pub(crate) fn buckets(mut self, bucket_count: usize) -> Self {
+            self.buckets = Some(bucket_count);
+            self
+        }
+
+        pub(crate) fn row(&mut self, fields: Vec<Field>) {
+            self.table.push_row_hashed(&fields);
+        }
+    }
+
+    /// Write the tables into a database file
+    pub(crate) fn build(tables: Vec<TableBuilder>) -> Vec<u8> {
+        let mut db = store::Database::new();
+        for mut t in tables {
+            match t.buckets {
+                Some(bucket_count) => t.table.resize_buckets(bucket_count),
+                None => t.table.auto_resize_buckets(),
+            }
+            db.push_table(Latin1String::encode(&t.name), t.table);
+        }
+        let mut buf = Vec::new();
+        db.write(&mut buf).unwrap();
+        buf
+    }
+}
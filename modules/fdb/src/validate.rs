@@ -0,0 +1,455 @@
+//! # Check the structure of a database file
+//!
+//! The [`mem`](crate::mem) and [`ro`](crate::ro) APIs assume that the file they
+//! are reading is well-formed. This module walks an untrusted buffer and reports
+//! every structural problem, together with the file offset where it was found.
+//!
+//! The following properties are checked:
+//!
+//! - All offsets and arrays are within the bounds of the buffer
+//! - All strings are null-terminated
+//! - The table list is sorted by name, as needed by [`Tables::by_name`](crate::mem::Tables::by_name)
+//! - All column and field types are known
+//! - Every row has one field per column
+//! - Every field has the declared type of its column, or is NULL
+//! - Every row is in the bucket that the [`FdbHash`] of its primary key maps to
+//! - The row lists of the buckets contain no cycles
+//!
+//! ```
+//! use assembly_fdb::validate::validate_buffer;
+//!
+//! let file: &[u8] = &[0,0,0,0,8,0,0,0];
+//! assert!(validate_buffer(file).is_valid());
+//!
+//! let file: &[u8] = &[1,0,0,0,8,0,0,0];
+//! assert!(!validate_buffer(file).is_valid());
+//! ```
+
+use std::{collections::HashSet, convert::TryFrom, fmt};
+
+use assembly_fdb_core::{value::ValueType, FdbHash};
+use displaydoc::Display;
+use latin1str::Latin1Str;
+
+/// A single problem in a database file
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct Issue {
+    /// The offset of the structure that contains the problem
+    pub offset: u32,
+    /// The name of the table, if the problem is within a table
+    pub table: Option<String>,
+    /// What is wrong
+    pub kind: IssueKind,
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:#010x}: ", self.offset)?;
+        if let Some(table) = &self.table {
+            write!(f, "[{}] ", table)?;
+        }
+        write!(f, "{}", self.kind)
+    }
+}
+
+/// The kind of an [`Issue`]
+#[derive(Debug, Display, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub enum IssueKind {
+    /// {what} of {size} bytes is out of bounds
+    OutOfBounds {
+        /// The structure that was expected at the offset
+        what: &'static str,
+        /// The number of bytes needed
+        size: u64,
+    },
+    /// String is not null-terminated
+    UnterminatedString,
+    /// Table {name:?} is not sorted after {previous:?}
+    UnsortedTable {
+        /// The name of the preceding table
+        previous: String,
+        /// The name of the table
+        name: String,
+    },
+    /// Unknown column type {0}
+    UnknownColumnType(u32),
+    /// Unknown field type {0}
+    UnknownFieldType(u32),
+    /// Row has {fields} fields, but the table has {columns} columns
+    FieldCount {
+        /// The number of fields in the row
+        fields: u32,
+        /// The number of columns in the table
+        columns: u32,
+    },
+    /// Field #{index} has type {actual}, but the column has type {expected}
+    FieldType {
+        /// The index of the field in the row
+        index: u32,
+        /// The type of the column
+        expected: ValueType,
+        /// The type of the field
+        actual: ValueType,
+    },
+    /// Row is in bucket {actual}, but its primary key maps to bucket {expected}
+    WrongBucket {
+        /// The bucket the primary key hash maps to
+        expected: u32,
+        /// The bucket the row was found in
+        actual: u32,
+    },
+    /// The row list of bucket {bucket} contains a cycle
+    Cycle {
+        /// The index of the bucket
+        bucket: u32,
+    },
+}
+
+/// The result of [`validate_buffer`]
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde-derives", derive(serde::Serialize))]
+pub struct Report {
+    /// All problems in the order they were found
+    pub issues: Vec<Issue>,
+}
+
+impl Report {
+    /// Check whether no problems were found
+    pub fn is_valid(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for issue in &self.issues {
+            writeln!(f, "{}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check the structure of a database file
+pub fn validate_buffer(buf: &[u8]) -> Report {
+    let mut validator = Validator {
+        buf,
+        table: None,
+        report: Report::default(),
+    };
+    validator.check_database();
+    validator.report
+}
+
+const NO_ROW: u32 = u32::MAX;
+
+struct Validator<'a> {
+    buf: &'a [u8],
+    table: Option<String>,
+    report: Report,
+}
+
+impl<'a> Validator<'a> {
+    fn issue(&mut self, offset: u32, kind: IssueKind) {
+        self.report.issues.push(Issue {
+            offset,
+            table: self.table.clone(),
+            kind,
+        });
+    }
+
+    /// Get `size` bytes at `offset`
+    fn bytes(&mut self, offset: u32, size: u64, what: &'static str) -> Option<&'a [u8]> {
+        let start = u64::from(offset);
+        let end = start + size;
+        if end > self.buf.len() as u64 {
+            self.issue(offset, IssueKind::OutOfBounds { what, size });
+            None
+        } else {
+            Some(&self.buf[start as usize..end as usize])
+        }
+    }
+
+    /// Read `N` little-endian words at `offset`
+    fn words<const N: usize>(&mut self, offset: u32, what: &'static str) -> Option<[u32; N]> {
+        let bytes = self.bytes(offset, 4 * N as u64, what)?;
+        let mut words = [0; N];
+        for (word, chunk) in words.iter_mut().zip(bytes.chunks_exact(4)) {
+            *word = u32::from_le_bytes(<[u8; 4]>::try_from(chunk).unwrap());
+        }
+        Some(words)
+    }
+
+    fn string(&mut self, offset: u32) -> Option<&'a Latin1Str> {
+        let rest = match self.buf.get(offset as usize..) {
+            Some(rest) => rest,
+            None => {
+                let kind = IssueKind::OutOfBounds {
+                    what: "string",
+                    size: 1,
+                };
+                self.issue(offset, kind);
+                return None;
+            }
+        };
+        if rest.contains(&0) {
+            Some(Latin1Str::from_bytes_until_nul(rest))
+        } else {
+            self.issue(offset, IssueKind::UnterminatedString);
+            None
+        }
+    }
+
+    fn check_database(&mut self) {
+        let [count, base] = match self.words(0, "database header") {
+            Some(header) => header,
+            None => return,
+        };
+        if self
+            .bytes(base, u64::from(count) * 8, "table header list")
+            .is_none()
+        {
+            return;
+        }
+
+        let mut previous: Option<&Latin1Str> = None;
+        for index in 0..count {
+            let addr = base + index * 8;
+            let [def_addr, data_addr] = self.words(addr, "table header").unwrap();
+            self.table = None;
+            if let Some((name, columns)) = self.check_table_def(def_addr) {
+                if let Some(prev) = previous {
+                    if prev.as_bytes() >= name.as_bytes() {
+                        let kind = IssueKind::UnsortedTable {
+                            previous: prev.decode().into_owned(),
+                            name: name.decode().into_owned(),
+                        };
+                        self.issue(addr, kind);
+                    }
+                }
+                previous = Some(name);
+                self.table = Some(name.decode().into_owned());
+                self.check_table_data(data_addr, &columns);
+            }
+        }
+        self.table = None;
+    }
+
+    /// Returns the name of the table and the column types
+    fn check_table_def(&mut self, addr: u32) -> Option<(&'a Latin1Str, Vec<Option<ValueType>>)> {
+        let [column_count, name_addr, columns_addr] = self.words(addr, "table def header")?;
+        let name = self.string(name_addr)?;
+        self.table = Some(name.decode().into_owned());
+
+        self.bytes(
+            columns_addr,
+            u64::from(column_count) * 8,
+            "column header list",
+        )?;
+        let mut columns = Vec::with_capacity(column_count as usize);
+        for index in 0..column_count {
+            let column_addr = columns_addr + index * 8;
+            let [data_type, column_name_addr] = self.words(column_addr, "column header").unwrap();
+            self.string(column_name_addr);
+            match ValueType::try_from(data_type) {
+                Ok(value_type) => columns.push(Some(value_type)),
+                Err(_) => {
+                    self.issue(column_addr, IssueKind::UnknownColumnType(data_type));
+                    columns.push(None);
+                }
+            }
+        }
+        Some((name, columns))
+    }
+
+    fn check_table_data(&mut self, addr: u32, columns: &[Option<ValueType>]) {
+        let [bucket_count, buckets_addr] = match self.words(addr, "table data header") {
+            Some(header) => header,
+            None => return,
+        };
+        if self
+            .bytes(
+                buckets_addr,
+                u64::from(bucket_count) * 4,
+                "bucket header list",
+            )
+            .is_none()
+        {
+            return;
+        }
+
+        for bucket in 0..bucket_count {
+            let [mut next] = self
+                .words(buckets_addr + bucket * 4, "bucket header")
+                .unwrap();
+            let mut visited = HashSet::new();
+            while next != NO_ROW {
+                if !visited.insert(next) {
+                    self.issue(next, IssueKind::Cycle { bucket });
+                    break;
+                }
+                let [row_addr, next_addr] = match self.words(next, "row header list entry") {
+                    Some(entry) => entry,
+                    None => break,
+                };
+                if let Some(hash) = self.check_row(row_addr, columns) {
+                    let expected = hash % bucket_count;
+                    if expected != bucket {
+                        let kind = IssueKind::WrongBucket {
+                            expected,
+                            actual: bucket,
+                        };
+                        self.issue(row_addr, kind);
+                    }
+                }
+                next = next_addr;
+            }
+        }
+    }
+
+    /// Returns the hash of the primary key, if it could be read
+    fn check_row(&mut self, addr: u32, columns: &[Option<ValueType>]) -> Option<u32> {
+        let [field_count, fields_addr] = self.words(addr, "row header")?;
+        if field_count as usize != columns.len() {
+            let kind = IssueKind::FieldCount {
+                fields: field_count,
+                columns: columns.len() as u32,
+            };
+            self.issue(addr, kind);
+        }
+        self.bytes(fields_addr, u64::from(field_count) * 8, "field list")?;
+
+        let mut hash = None;
+        for index in 0..field_count {
+            let field_addr = fields_addr + index * 8;
+            let [data_type, value] = self.words(field_addr, "field").unwrap();
+            let value_type = match ValueType::try_from(data_type) {
+                Ok(value_type) => value_type,
+                Err(_) => {
+                    self.issue(field_addr, IssueKind::UnknownFieldType(data_type));
+                    continue;
+                }
+            };
+            if let Some(Some(expected)) = columns.get(index as usize) {
+                if value_type != ValueType::Nothing && value_type != *expected {
+                    let kind = IssueKind::FieldType {
+                        index,
+                        expected: *expected,
+                        actual: value_type,
+                    };
+                    self.issue(field_addr, kind);
+                }
+            }
+            let field_hash = self.check_value(value_type, value);
+            if index == 0 {
+                hash = field_hash;
+            }
+        }
+        hash
+    }
+
+    /// Returns the hash of the value, if it could be read
+    fn check_value(&mut self, value_type: ValueType, value: u32) -> Option<u32> {
+        match value_type {
+            ValueType::Nothing => Some(0),
+            ValueType::Integer => Some(FdbHash::hash(&(value as i32))),
+            ValueType::Float => Some(FdbHash::hash(&f32::from_bits(value))),
+            ValueType::Boolean => Some(FdbHash::hash(&(value != 0))),
+            ValueType::Text | ValueType::VarChar => self.string(value).map(FdbHash::hash),
+            ValueType::BigInt => {
+                let bytes = self.bytes(value, 8, "bigint")?;
+                let num = i64::from_le_bytes(<[u8; 8]>::try_from(bytes).unwrap());
+                Some(FdbHash::hash(&num))
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "store"))]
+mod tests {
+    use super::*;
+    use crate::{store, value::owned::Field};
+    use latin1str::Latin1String;
+
+    fn write(db: &store::Database) -> Vec<u8> {
+        let mut out = Vec::new();
+        db.write(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_valid() {
+        let mut table = store::Table::new(4);
+        table.push_column(Latin1String::encode("id"), ValueType::Integer);
+        table.push_column(Latin1String::encode("name"), ValueType::Text);
+        for id in 0..10 {
+            let name = Field::Text(format!("Item {}", id));
+            table.push_row(id as usize, &[Field::Integer(id), name]);
+        }
+        let mut db = store::Database::new();
+        db.push_table(Latin1String::encode("A"), store::Table::new(0));
+        db.push_table(Latin1String::encode("Items"), table);
+
+        let report = validate_buffer(&write(&db));
+        assert!(report.is_valid(), "{}", report);
+    }
+
+    #[test]
+    fn test_wrong_bucket_and_type() {
+        let mut table = store::Table::new(4);
+        table.push_column(Latin1String::encode("id"), ValueType::Integer);
+        table.push_row(1, &[Field::Integer(2)]);
+        table.push_row(3, &[Field::Float(3.0)]);
+        let mut db = store::Database::new();
+        db.push_table(Latin1String::encode("Items"), table);
+
+        let report = validate_buffer(&write(&db));
+        let kinds: Vec<_> = report.issues.iter().map(|i| &i.kind).collect();
+        assert_eq!(kinds.len(), 3, "{}", report);
+        assert_eq!(
+            kinds[0],
+            &IssueKind::WrongBucket {
+                expected: 2,
+                actual: 1
+            }
+        );
+        assert!(matches!(kinds[1], IssueKind::FieldType { index: 0, .. }));
+        assert!(matches!(kinds[2], IssueKind::WrongBucket { .. }));
+        assert_eq!(report.issues[0].table.as_deref(), Some("Items"));
+    }
+
+    #[test]
+    fn test_cycle_and_bounds() {
+        let mut table = store::Table::new(1);
+        table.push_column(Latin1String::encode("id"), ValueType::Integer);
+        table.push_row(0, &[Field::Integer(0)]);
+        let mut db = store::Database::new();
+        db.push_table(Latin1String::encode("Items"), table);
+        let mut buf = write(&db);
+
+        // Point the only row list entry at itself
+        let entry = validate_entry_addr(&buf);
+        buf[entry as usize + 4..entry as usize + 8].copy_from_slice(&entry.to_le_bytes());
+        let report = validate_buffer(&buf);
+        assert_eq!(report.issues.len(), 1, "{}", report);
+        assert_eq!(report.issues[0].kind, IssueKind::Cycle { bucket: 0 });
+
+        let report = validate_buffer(&buf[..buf.len() - 4]);
+        assert!(matches!(
+            report.issues[0].kind,
+            IssueKind::OutOfBounds { .. }
+        ));
+    }
+
+    /// Get the address of the first row list entry of the first table
+    fn validate_entry_addr(buf: &[u8]) -> u32 {
+        let word = |addr: u32| {
+            let a = addr as usize;
+            u32::from_le_bytes(<[u8; 4]>::try_from(&buf[a..a + 4]).unwrap())
+        };
+        let data_header = word(12);
+        let buckets = word(data_header + 4);
+        word(buckets)
+    }
+}